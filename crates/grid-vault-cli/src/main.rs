@@ -0,0 +1,207 @@
+//! Operator CLI for a grid-vault deployment.
+//!
+//! Every transaction is simulated first; `--dry-run` stops after the simulation.
+
+mod report;
+mod rpc;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, Subcommand};
+use grid_vault_client::{
+    grid_vault::{
+        PAUSE_ALL, PAUSE_DEPLOYMENTS, PAUSE_DEPOSITS, PAUSE_FEE_COLLECTION, PAUSE_VALUATIONS, PAUSE_WITHDRAWALS,
+    },
+    instructions, nav, pda, BaseMint,
+};
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use report::{print, BatchFeeReport, ConfigReport, NavReport, PositionList, PositionReport};
+use rpc::Vault;
+
+/// Positions per `collect_batch_fees` transaction; keeps a legacy transaction under the size limit
+const DEFAULT_BATCH_SIZE: usize = 20;
+
+#[derive(Parser)]
+#[command(name = "grid-vault-cli", version, about = "Administer a grid-vault deployment")]
+struct Cli {
+    /// RPC endpoint; defaults to a local validator
+    #[arg(long, short = 'u', global = true, env = "GRID_VAULT_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Signer and fee payer
+    #[arg(long, short = 'k', global = true, env = "GRID_VAULT_KEYPAIR", default_value = "~/.config/solana/id.json")]
+    keypair: String,
+
+    /// Print machine-readable JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Simulate transactions without sending them
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the protocol config and treasury for `mint`
+    Initialize {
+        #[arg(long)]
+        mint: Pubkey,
+        /// Defaults to the signer
+        #[arg(long)]
+        admin: Option<Pubkey>,
+        #[arg(long)]
+        trading_bot: Pubkey,
+    },
+    /// Pause operations (admin or guardian)
+    Pause(PauseArgs),
+    /// Resume paused operations (admin)
+    Unpause(PauseArgs),
+    /// Collect the performance fee from one position
+    CollectFees {
+        #[arg(long)]
+        owner: Pubkey,
+    },
+    /// Collect performance fees from every eligible position
+    BatchFees {
+        #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+    /// Flag or clear a liquidity buffer deficit (permissionless)
+    CheckBuffer,
+    /// Sweep accumulated fees to the admin's token account
+    WithdrawFees {
+        #[arg(long)]
+        destination: Pubkey,
+    },
+    /// Show the protocol config
+    Config,
+    /// List positions, or one owner's position
+    Positions {
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Report vault NAV and share price
+    Nav,
+}
+
+#[derive(Args)]
+struct PauseArgs {
+    #[arg(long)]
+    deposits: bool,
+    #[arg(long)]
+    withdrawals: bool,
+    #[arg(long)]
+    deployments: bool,
+    #[arg(long)]
+    valuations: bool,
+    #[arg(long)]
+    fee_collection: bool,
+    /// Every operation; the default when no operation is given
+    #[arg(long)]
+    all: bool,
+    /// Reason code recorded in the emitted event
+    #[arg(long, default_value_t = 0)]
+    reason: u16,
+}
+
+impl PauseArgs {
+    fn operations(&self) -> u8 {
+        let flags = [
+            (self.deposits, PAUSE_DEPOSITS),
+            (self.withdrawals, PAUSE_WITHDRAWALS),
+            (self.deployments, PAUSE_DEPLOYMENTS),
+            (self.valuations, PAUSE_VALUATIONS),
+            (self.fee_collection, PAUSE_FEE_COLLECTION),
+        ];
+        let operations = flags.iter().filter(|(set, _)| *set).fold(0, |acc, (_, flag)| acc | flag);
+        if self.all || operations == 0 {
+            PAUSE_ALL
+        } else {
+            operations
+        }
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{home}/{rest}"),
+        _ => path.to_string(),
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let payer = read_keypair_file(expand_home(&cli.keypair))
+        .map_err(|err| anyhow!("failed to read keypair {}: {err}", cli.keypair))?;
+    let vault = Vault::new(&cli.url, payer, cli.dry_run);
+    let signer = vault.payer.pubkey();
+
+    match cli.command {
+        Command::Initialize { mint, admin, trading_bot } => {
+            let base = BaseMint { mint, token_program: vault.token_program_of(&mint)? };
+            let ix = instructions::initialize_protocol(&signer, &base, admin.unwrap_or(signer), trading_bot);
+            print(cli.json, &vault.submit(&[ix])?)
+        }
+        Command::Pause(args) => {
+            let ix = instructions::pause_protocol(&signer, args.operations(), args.reason);
+            print(cli.json, &vault.submit(&[ix])?)
+        }
+        Command::Unpause(args) => {
+            let ix = instructions::unpause_protocol(&signer, args.operations(), args.reason);
+            print(cli.json, &vault.submit(&[ix])?)
+        }
+        Command::CollectFees { owner } => {
+            let ix = instructions::collect_user_fees(&signer, &owner);
+            print(cli.json, &vault.submit(&[ix])?)
+        }
+        Command::BatchFees { batch_size } => {
+            if batch_size == 0 {
+                bail!("--batch-size must be at least 1");
+            }
+            let now = vault.now()?;
+            let scan = vault.positions()?;
+            let owners: Vec<Pubkey> = scan
+                .positions
+                .into_iter()
+                .filter(|(_, position)| position.user_shares > 0 && nav::fee_eligible(position, now))
+                .map(|(_, position)| position.owner)
+                .collect();
+            let batches = owners
+                .chunks(batch_size)
+                .map(|chunk| vault.submit(&[instructions::collect_batch_fees(&signer, chunk)]))
+                .collect::<Result<_>>()?;
+            let report =
+                BatchFeeReport { eligible_positions: owners.len(), unmigrated_positions: scan.unmigrated.len(), batches };
+            print(cli.json, &report)
+        }
+        Command::CheckBuffer => print(cli.json, &vault.submit(&[instructions::check_buffer()])?),
+        Command::WithdrawFees { destination } => {
+            let ix = instructions::collect_performance_fees(&vault.base_mint()?, &signer, &destination);
+            print(cli.json, &vault.submit(&[ix])?)
+        }
+        Command::Config => print(cli.json, &ConfigReport::new(pda::protocol_config(), &vault.config()?)),
+        Command::Positions { owner } => {
+            let config = vault.config()?;
+            let treasury_balance = vault.treasury_balance()?;
+            let now = vault.now()?;
+            let positions = match owner {
+                Some(owner) => vec![(pda::user_position(&owner), vault.position(&owner)?)],
+                None => vault.positions()?.positions,
+            };
+            let reports = positions
+                .iter()
+                .map(|(address, position)| PositionReport::new(*address, position, &config, treasury_balance, now))
+                .collect::<Result<_>>()?;
+            print(cli.json, &PositionList(reports))
+        }
+        Command::Nav => {
+            let config = vault.config()?;
+            print(cli.json, &NavReport::new(&config, vault.treasury_balance()?, vault.now()?)?)
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Command output, printed as text or as JSON with `--json`.
+
+use std::fmt;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use grid_vault_client::{
+    grid_vault::{ProtocolConfig, UserPosition},
+    nav,
+};
+use serde::Serialize;
+
+use crate::rpc::TxReport;
+
+pub fn print<T: Serialize + fmt::Display>(json: bool, value: &T) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        println!("{value}");
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct ConfigReport {
+    pub address: String,
+    pub version: u8,
+    pub admin: String,
+    pub trading_bot: String,
+    pub guardian: String,
+    pub treasury: String,
+    pub lifecycle: String,
+    pub access_mode: String,
+    pub pause_flags: u8,
+    pub emergency_mode: bool,
+    pub performance_fee_bps: u16,
+    pub total_shares: u64,
+    pub total_trading_deployed: u64,
+    pub deployed_current_value: u64,
+    pub last_valuation_timestamp: i64,
+    pub accumulated_fees: u64,
+    pub pending_unrealized_fees: u64,
+    pub asset_holdings_value: u64,
+    pub max_total_deposits: u64,
+    pub max_position_size: u64,
+    pub min_deposit: u64,
+}
+
+impl ConfigReport {
+    pub fn new(address: Pubkey, config: &ProtocolConfig) -> Self {
+        ConfigReport {
+            address: address.to_string(),
+            version: config.version,
+            admin: config.admin.to_string(),
+            trading_bot: config.trading_bot.to_string(),
+            guardian: config.guardian.to_string(),
+            treasury: config.treasury.to_string(),
+            lifecycle: format!("{:?}", config.lifecycle),
+            access_mode: format!("{:?}", config.access_mode),
+            pause_flags: config.pause_flags,
+            emergency_mode: config.emergency_mode,
+            performance_fee_bps: config.performance_fee_bps,
+            total_shares: config.total_shares,
+            total_trading_deployed: config.total_trading_deployed,
+            deployed_current_value: config.deployed_current_value,
+            last_valuation_timestamp: config.last_valuation_timestamp,
+            accumulated_fees: config.accumulated_fees,
+            pending_unrealized_fees: config.pending_unrealized_fees,
+            asset_holdings_value: config.asset_holdings_value,
+            max_total_deposits: config.max_total_deposits,
+            max_position_size: config.max_position_size,
+            min_deposit: config.min_deposit,
+        }
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Protocol config {} (v{})", self.address, self.version)?;
+        writeln!(f, "  admin:                   {}", self.admin)?;
+        writeln!(f, "  trading bot:             {}", self.trading_bot)?;
+        writeln!(f, "  guardian:                {}", self.guardian)?;
+        writeln!(f, "  treasury:                {}", self.treasury)?;
+        writeln!(f, "  lifecycle:               {}", self.lifecycle)?;
+        writeln!(f, "  access mode:             {}", self.access_mode)?;
+        writeln!(f, "  pause flags:             {:#07b}", self.pause_flags)?;
+        writeln!(f, "  emergency mode:          {}", self.emergency_mode)?;
+        writeln!(f, "  performance fee:         {} bps", self.performance_fee_bps)?;
+        writeln!(f, "  total shares:            {}", self.total_shares)?;
+        writeln!(f, "  deployed (cost):         {}", self.total_trading_deployed)?;
+        writeln!(f, "  deployed (valuation):    {} @ {}", self.deployed_current_value, self.last_valuation_timestamp)?;
+        writeln!(f, "  accumulated fees:        {}", self.accumulated_fees)?;
+        writeln!(f, "  pending unrealized fees: {}", self.pending_unrealized_fees)?;
+        writeln!(f, "  asset holdings value:    {}", self.asset_holdings_value)?;
+        write!(
+            f,
+            "  limits:                  total {} / position {} / min deposit {}",
+            self.max_total_deposits, self.max_position_size, self.min_deposit
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub struct PositionReport {
+    pub address: String,
+    pub owner: String,
+    pub version: u8,
+    pub shares: u64,
+    pub balance: u64,
+    pub cost_basis: u64,
+    pub high_water_mark: u64,
+    pub fee_due: u64,
+    pub fee_eligible: bool,
+    pub last_fee_collection: i64,
+    pub lifetime_fees_paid: u64,
+}
+
+impl PositionReport {
+    pub fn new(
+        address: Pubkey,
+        position: &UserPosition,
+        config: &ProtocolConfig,
+        treasury_balance: u64,
+        now: i64,
+    ) -> Result<Self> {
+        let overflow = || anyhow!("math overflow valuing position {address}");
+        Ok(PositionReport {
+            address: address.to_string(),
+            owner: position.owner.to_string(),
+            version: position.version,
+            shares: position.user_shares,
+            balance: nav::user_balance(config, position, treasury_balance, now).ok_or_else(overflow)?,
+            cost_basis: position.cost_basis,
+            high_water_mark: position.high_water_mark,
+            fee_due: nav::fee_due(config, position, treasury_balance, now).ok_or_else(overflow)?,
+            fee_eligible: nav::fee_eligible(position, now),
+            last_fee_collection: position.last_fee_collection,
+            lifetime_fees_paid: position.lifetime_fees_paid,
+        })
+    }
+}
+
+impl fmt::Display for PositionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  shares {:>14}  balance {:>14}  hwm {:>14}  fee due {:>10}{}",
+            self.owner,
+            self.shares,
+            self.balance,
+            self.high_water_mark,
+            self.fee_due,
+            if self.fee_eligible { " (eligible)" } else { "" }
+        )
+    }
+}
+
+/// Positions printed one per line
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct PositionList(pub Vec<PositionReport>);
+
+impl fmt::Display for PositionList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for position in &self.0 {
+            writeln!(f, "{position}")?;
+        }
+        write!(f, "{} position(s)", self.0.len())
+    }
+}
+
+#[derive(Serialize)]
+pub struct NavReport {
+    pub timestamp: i64,
+    pub treasury_balance: u64,
+    pub deployed_value: u64,
+    pub valuation_is_fresh: bool,
+    pub asset_holdings_value: u64,
+    pub total_value: u64,
+    pub buffer_target: u64,
+    /// Set while a flagged deficit blocks deployment
+    pub buffer_deficit_since: Option<i64>,
+    pub accumulated_fees: u64,
+    pub pending_unrealized_fees: u64,
+    pub user_value_pool: u64,
+    pub total_shares: u64,
+    /// Base-asset units per share, for display only
+    pub share_price: f64,
+}
+
+impl NavReport {
+    pub fn new(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Result<Self> {
+        let (user_value_pool, total_value) =
+            nav::vault_value_pool(config, treasury_balance, now).ok_or_else(|| anyhow!("fees exceed vault value"))?;
+        let deployed_value = nav::deployed_value(config, now);
+        let (buffer_target, _) =
+            nav::buffer_shortfall(config, treasury_balance, now).ok_or_else(|| anyhow!("fees exceed vault value"))?;
+        let share_price = if config.total_shares == 0 {
+            1.0
+        } else {
+            user_value_pool as f64 / config.total_shares as f64
+        };
+        Ok(NavReport {
+            timestamp: now,
+            treasury_balance,
+            deployed_value,
+            valuation_is_fresh: nav::valuation_is_fresh(config, now),
+            asset_holdings_value: nav::asset_holdings_value(config),
+            total_value,
+            buffer_target,
+            buffer_deficit_since: (config.buffer_deficit_since > 0).then_some(config.buffer_deficit_since),
+            accumulated_fees: config.accumulated_fees,
+            pending_unrealized_fees: config.pending_unrealized_fees,
+            user_value_pool,
+            total_shares: config.total_shares,
+            share_price,
+        })
+    }
+}
+
+impl fmt::Display for NavReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "NAV at {}", self.timestamp)?;
+        writeln!(f, "  treasury:                {}", self.treasury_balance)?;
+        writeln!(
+            f,
+            "  deployed:                {} ({})",
+            self.deployed_value,
+            if self.valuation_is_fresh { "valuation" } else { "cost basis" }
+        )?;
+        writeln!(f, "  accepted assets:         {}", self.asset_holdings_value)?;
+        writeln!(f, "  total value:             {}", self.total_value)?;
+        match self.buffer_deficit_since {
+            Some(since) => writeln!(f, "  buffer target:           {} (deficit since {since})", self.buffer_target)?,
+            None => writeln!(f, "  buffer target:           {}", self.buffer_target)?,
+        }
+        writeln!(f, "  accumulated fees:        {}", self.accumulated_fees)?;
+        writeln!(f, "  pending unrealized fees: {}", self.pending_unrealized_fees)?;
+        writeln!(f, "  user value pool:         {}", self.user_value_pool)?;
+        writeln!(f, "  total shares:            {}", self.total_shares)?;
+        write!(f, "  share price:             {:.9}", self.share_price)
+    }
+}
+
+impl fmt::Display for TxReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.signature {
+            Some(signature) => write!(f, "sent {signature}")?,
+            None => write!(f, "simulated (dry run)")?,
+        }
+        if let Some(units) = self.units_consumed {
+            write!(f, ", {units} compute units")?;
+        }
+        if self.simulated_only {
+            for line in &self.logs {
+                write!(f, "\n  {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Results of a batch fee run, one transaction per batch
+#[derive(Serialize)]
+pub struct BatchFeeReport {
+    pub eligible_positions: usize,
+    /// Positions left out until `migrate_position` brings them to the current layout
+    pub unmigrated_positions: usize,
+    pub batches: Vec<TxReport>,
+}
+
+impl fmt::Display for BatchFeeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} eligible position(s) in {} batch(es)", self.eligible_positions, self.batches.len())?;
+        if self.unmigrated_positions > 0 {
+            write!(f, ", {} unmigrated position(s) skipped", self.unmigrated_positions)?;
+        }
+        for (i, batch) in self.batches.iter().enumerate() {
+            write!(f, "\n[{}] {batch}", i + 1)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! RPC access: account fetching and simulate-then-send transaction submission.
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use anyhow::{anyhow, bail, Context, Result};
+use grid_vault_client::{
+    grid_vault::{ProtocolConfig, UserPosition},
+    pda,
+    state::{decode_protocol_config, decode_user_position, PositionScan},
+    BaseMint,
+};
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    sysvar,
+    transaction::Transaction,
+};
+
+/// Outcome of submitting (or, in dry-run mode, only simulating) a transaction
+#[derive(Debug, Serialize)]
+pub struct TxReport {
+    pub signature: Option<String>,
+    pub simulated_only: bool,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+pub struct Vault {
+    pub client: RpcClient,
+    pub payer: Keypair,
+    pub dry_run: bool,
+}
+
+impl Vault {
+    pub fn new(url: &str, payer: Keypair, dry_run: bool) -> Self {
+        let client = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        Vault { client, payer, dry_run }
+    }
+
+    pub fn config(&self) -> Result<ProtocolConfig> {
+        let account = self.client.get_account(&pda::protocol_config()).context("protocol config not found")?;
+        Ok(decode_protocol_config(&account.data)?)
+    }
+
+    pub fn position(&self, owner: &Pubkey) -> Result<UserPosition> {
+        let account = self
+            .client
+            .get_account(&pda::user_position(owner))
+            .with_context(|| format!("no position for {owner}"))?;
+        Ok(decode_user_position(&account.data)?)
+    }
+
+    /// Every `UserPosition` owned by the program, keyed by address. There's no size filter,
+    /// so positions still in an older layout come back as `unmigrated`.
+    pub fn positions(&self) -> Result<PositionScan> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, UserPosition::DISCRIMINATOR.to_vec()))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let accounts = self.client.get_program_accounts_with_config(&grid_vault_client::PROGRAM_ID, config)?;
+        let scan = PositionScan::from_accounts(accounts.into_iter().map(|(address, account)| (address, account.data)))?;
+        if !scan.unmigrated.is_empty() {
+            eprintln!("skipping {} unmigrated position(s): {:?}", scan.unmigrated.len(), scan.unmigrated);
+        }
+        Ok(scan)
+    }
+
+    /// Base mint and its token program, read from the treasury account
+    pub fn base_mint(&self) -> Result<BaseMint> {
+        let treasury = self.client.get_account(&pda::treasury()).context("treasury not found")?;
+        // SPL Token and Token-2022 share the base account layout
+        let mint = treasury.data.get(..32).ok_or_else(|| anyhow!("malformed treasury account"))?;
+        let mint = Pubkey::try_from(mint)?;
+        Ok(BaseMint { mint, token_program: treasury.owner })
+    }
+
+    /// Token program that owns `mint`
+    pub fn token_program_of(&self, mint: &Pubkey) -> Result<Pubkey> {
+        Ok(self.client.get_account(mint).with_context(|| format!("mint {mint} not found"))?.owner)
+    }
+
+    pub fn treasury_balance(&self) -> Result<u64> {
+        let treasury = self.client.get_account(&pda::treasury()).context("treasury not found")?;
+        // `amount` follows the mint and owner in both token programs
+        let amount = treasury.data.get(64..72).ok_or_else(|| anyhow!("malformed treasury account"))?;
+        Ok(u64::from_le_bytes(amount.try_into()?))
+    }
+
+    /// Cluster time as the program sees it
+    pub fn now(&self) -> Result<i64> {
+        let account = self.client.get_account(&sysvar::clock::ID)?;
+        let clock: Clock = from_account(&account).ok_or_else(|| anyhow!("malformed clock sysvar"))?;
+        Ok(clock.unix_timestamp)
+    }
+
+    /// Simulates `ixs`, then sends them unless in dry-run mode
+    pub fn submit(&self, ixs: &[Instruction]) -> Result<TxReport> {
+        let blockhash = self.client.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(ixs, Some(&self.payer.pubkey()), &[&self.payer], blockhash);
+
+        let simulation = self.client.simulate_transaction(&tx)?.value;
+        let logs = simulation.logs.unwrap_or_default();
+        if let Some(err) = simulation.err {
+            bail!("simulation failed: {err}\n{}", logs.join("\n"));
+        }
+        if self.dry_run {
+            return Ok(TxReport {
+                signature: None,
+                simulated_only: true,
+                units_consumed: simulation.units_consumed,
+                logs,
+            });
+        }
+
+        let signature = self.client.send_and_confirm_transaction(&tx)?;
+        Ok(TxReport {
+            signature: Some(signature.to_string()),
+            simulated_only: false,
+            units_consumed: simulation.units_consumed,
+            logs,
+        })
+    }
+}
@@ -0,0 +1,842 @@
+//! Typed builders for every instruction in `vault_with_treasury`.
+//!
+//! PDAs are derived here; callers only pass signers and their own token accounts.
+//! Entry instructions always carry the holder's `Attestation` PDA, issued or not.
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, system_program, sysvar},
+    InstructionData, ToAccountMetas,
+};
+use anchor_spl::token::spl_token;
+use grid_vault::{accounts, instruction, AccessMode, DeploymentValuation, ID};
+
+use crate::pda;
+
+/// Base-asset mint of the vault and the token program that owns it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BaseMint {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl BaseMint {
+    /// Mint owned by the classic SPL Token program
+    pub fn spl(mint: Pubkey) -> Self {
+        BaseMint { mint, token_program: anchor_spl::token::ID }
+    }
+
+    /// Mint owned by Token-2022
+    pub fn token_2022(mint: Pubkey) -> Self {
+        BaseMint { mint, token_program: anchor_spl::token_2022::ID }
+    }
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// ============ SETUP & POSITIONS ============
+
+pub fn initialize_protocol(authority: &Pubkey, base: &BaseMint, admin: Pubkey, trading_bot: Pubkey) -> Instruction {
+    build(
+        accounts::InitializeProtocol {
+            authority: *authority,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            system_program: system_program::ID,
+            token_program: base.token_program,
+            rent: sysvar::rent::ID,
+        },
+        instruction::InitializeProtocol { admin, trading_bot },
+    )
+}
+
+pub fn create_user_position(owner: &Pubkey, proof: Vec<[u8; 32]>) -> Instruction {
+    build(
+        accounts::CreateUserPosition {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            attestation: pda::attestation(owner),
+            system_program: system_program::ID,
+        },
+        instruction::CreateUserPosition { proof },
+    )
+}
+
+pub fn close_user_position(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::CloseUserPosition {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+        },
+        instruction::CloseUserPosition {},
+    )
+}
+
+pub fn migrate_config(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::MigrateConfig {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            system_program: system_program::ID,
+        },
+        instruction::MigrateConfig {},
+    )
+}
+
+pub fn migrate_position(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::MigratePosition {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            system_program: system_program::ID,
+        },
+        instruction::MigratePosition {},
+    )
+}
+
+// ============ DEPOSITS ============
+
+fn deposit_accounts(
+    base: &BaseMint,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+) -> accounts::Deposit {
+    accounts::Deposit {
+        owner: *owner,
+        user_position: pda::user_position(owner),
+        protocol_config: pda::protocol_config(),
+        treasury_account: pda::treasury(),
+        token_mint: base.mint,
+        user_token_account: *user_token_account,
+        attestation: pda::attestation(owner),
+        token_program: base.token_program,
+    }
+}
+
+pub fn deposit(
+    base: &BaseMint,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+    amount: u64,
+    min_shares: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    build(
+        deposit_accounts(base, owner, user_token_account),
+        instruction::Deposit { amount, min_shares, proof },
+    )
+}
+
+/// `mint` in the program: buy exactly `shares`, paying at most `max_assets`
+pub fn mint_shares(
+    base: &BaseMint,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+    shares: u64,
+    max_assets: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    build(
+        deposit_accounts(base, owner, user_token_account),
+        instruction::Mint { shares, max_assets, proof },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_for(
+    base: &BaseMint,
+    payer: &Pubkey,
+    payer_token_account: &Pubkey,
+    beneficiary: &Pubkey,
+    amount: u64,
+    min_shares: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    build(
+        accounts::DepositFor {
+            payer: *payer,
+            beneficiary: *beneficiary,
+            user_position: pda::user_position(beneficiary),
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            payer_token_account: *payer_token_account,
+            attestation: pda::attestation(beneficiary),
+            token_program: base.token_program,
+        },
+        instruction::DepositFor { amount, min_shares, proof },
+    )
+}
+
+/// Only valid for vaults whose base mint is wSOL
+pub fn deposit_sol(owner: &Pubkey, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Instruction {
+    build(
+        accounts::DepositSol {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            wsol_account: pda::wsol(owner),
+            native_mint: spl_token::native_mint::ID,
+            attestation: pda::attestation(owner),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DepositSol { amount, min_shares, proof },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_asset(
+    asset_mint: &Pubkey,
+    asset_token_program: &Pubkey,
+    owner: &Pubkey,
+    user_asset_account: &Pubkey,
+    amount: u64,
+    min_shares: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    build(
+        accounts::DepositAsset {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            accepted_asset: pda::accepted_asset(asset_mint),
+            asset_treasury: pda::asset_treasury(asset_mint),
+            asset_mint: *asset_mint,
+            user_asset_account: *user_asset_account,
+            attestation: pda::attestation(owner),
+            token_program: *asset_token_program,
+        },
+        instruction::DepositAsset { amount, min_shares, proof },
+    )
+}
+
+// ============ WITHDRAWALS ============
+
+fn withdraw_accounts(base: &BaseMint, owner: &Pubkey, user_token_account: &Pubkey) -> accounts::Withdraw {
+    accounts::Withdraw {
+        owner: *owner,
+        user_position: pda::user_position(owner),
+        protocol_config: pda::protocol_config(),
+        treasury_account: pda::treasury(),
+        token_mint: base.mint,
+        user_token_account: *user_token_account,
+        token_program: base.token_program,
+    }
+}
+
+pub fn withdraw(base: &BaseMint, owner: &Pubkey, user_token_account: &Pubkey, amount: u64, max_shares: u64) -> Instruction {
+    build(
+        withdraw_accounts(base, owner, user_token_account),
+        instruction::Withdraw { amount, max_shares },
+    )
+}
+
+pub fn redeem(base: &BaseMint, owner: &Pubkey, user_token_account: &Pubkey, shares: u64, min_assets: u64) -> Instruction {
+    build(
+        withdraw_accounts(base, owner, user_token_account),
+        instruction::Redeem { shares, min_assets },
+    )
+}
+
+pub fn redeem_all(base: &BaseMint, owner: &Pubkey, user_token_account: &Pubkey, min_assets: u64) -> Instruction {
+    build(
+        withdraw_accounts(base, owner, user_token_account),
+        instruction::RedeemAll { min_assets },
+    )
+}
+
+/// `assets` pairs every accepted asset with holdings with the owner's account for it
+pub fn emergency_withdraw(
+    base: &BaseMint,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+    shares: u64,
+    assets: &[(BaseMint, Pubkey)],
+) -> Instruction {
+    let mut ix = build(
+        withdraw_accounts(base, owner, user_token_account),
+        instruction::EmergencyWithdraw { shares },
+    );
+    for (asset, user_asset_account) in assets {
+        ix.accounts.extend([
+            AccountMeta::new(pda::accepted_asset(&asset.mint), false),
+            AccountMeta::new(pda::asset_treasury(&asset.mint), false),
+            AccountMeta::new_readonly(asset.mint, false),
+            AccountMeta::new(*user_asset_account, false),
+            AccountMeta::new_readonly(asset.token_program, false),
+        ]);
+    }
+    ix
+}
+
+pub fn claim_final_balance(base: &BaseMint, owner: &Pubkey, user_token_account: &Pubkey) -> Instruction {
+    build(
+        withdraw_accounts(base, owner, user_token_account),
+        instruction::ClaimFinalBalance {},
+    )
+}
+
+/// Only valid for vaults whose base mint is wSOL
+pub fn withdraw_sol(owner: &Pubkey, amount: u64, max_shares: u64) -> Instruction {
+    build(
+        accounts::WithdrawSol {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            wsol_account: pda::wsol(owner),
+            native_mint: spl_token::native_mint::ID,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawSol { amount, max_shares },
+    )
+}
+
+pub fn set_withdrawal_delegate(owner: &Pubkey, destination: &Pubkey, delegate: Pubkey) -> Instruction {
+    build(
+        accounts::SetWithdrawalDelegate {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            withdrawal_delegate: pda::withdrawal_delegate(owner),
+            treasury_account: pda::treasury(),
+            destination: *destination,
+            system_program: system_program::ID,
+        },
+        instruction::SetWithdrawalDelegate { delegate },
+    )
+}
+
+pub fn revoke_withdrawal_delegate(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::RevokeWithdrawalDelegate {
+            owner: *owner,
+            withdrawal_delegate: pda::withdrawal_delegate(owner),
+        },
+        instruction::RevokeWithdrawalDelegate {},
+    )
+}
+
+pub fn withdraw_as_delegate(
+    base: &BaseMint,
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    max_shares: u64,
+) -> Instruction {
+    build(
+        accounts::WithdrawAsDelegate {
+            delegate: *delegate,
+            owner: *owner,
+            withdrawal_delegate: pda::withdrawal_delegate(owner),
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            destination: *destination,
+            token_program: base.token_program,
+        },
+        instruction::WithdrawAsDelegate { amount, max_shares },
+    )
+}
+
+/// `admin_token_account` receives the final performance fee
+pub fn claim_from_escrow(
+    base: &BaseMint,
+    owner: &Pubkey,
+    user_token_account: &Pubkey,
+    admin_token_account: &Pubkey,
+) -> Instruction {
+    build(
+        accounts::ClaimFromEscrow {
+            owner: *owner,
+            user_position: pda::user_position(owner),
+            protocol_config: pda::protocol_config(),
+            claims_escrow: pda::claims_escrow(),
+            escrow_token_account: pda::claims_escrow_vault(),
+            token_mint: base.mint,
+            user_token_account: *user_token_account,
+            admin_token_account: *admin_token_account,
+            token_program: base.token_program,
+        },
+        instruction::ClaimFromEscrow {},
+    )
+}
+
+// ============ TRADING ============
+
+pub fn deploy_capital_for_trading(base: &BaseMint, trading_bot: &Pubkey, trading_account: &Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::DeployCapital {
+            trading_bot: *trading_bot,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            trading_account: *trading_account,
+            token_program: base.token_program,
+        },
+        instruction::DeployCapitalForTrading { amount },
+    )
+}
+
+pub fn return_capital_from_trading(
+    base: &BaseMint,
+    trading_bot: &Pubkey,
+    trading_account: &Pubkey,
+    returned_amount: u64,
+    original_deployed: u64,
+) -> Instruction {
+    build(
+        accounts::ReturnCapital {
+            trading_bot: *trading_bot,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            trading_account: *trading_account,
+            token_program: base.token_program,
+        },
+        instruction::ReturnCapitalFromTrading { returned_amount, original_deployed },
+    )
+}
+
+/// Permissionless; a flagged deficit blocks deployment until capital comes back
+pub fn check_buffer() -> Instruction {
+    build(
+        accounts::CheckBuffer { protocol_config: pda::protocol_config(), treasury_account: pda::treasury() },
+        instruction::CheckBuffer {},
+    )
+}
+
+pub fn update_deployment_valuation(trading_bot: &Pubkey, valuation: DeploymentValuation) -> Instruction {
+    build(
+        accounts::UpdateValuation {
+            trading_bot: *trading_bot,
+            protocol_config: pda::protocol_config(),
+        },
+        instruction::UpdateDeploymentValuation { valuation },
+    )
+}
+
+pub fn sweep_asset_to_trading(
+    asset_mint: &Pubkey,
+    asset_token_program: &Pubkey,
+    trading_bot: &Pubkey,
+    trading_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    build(
+        accounts::SweepAsset {
+            trading_bot: *trading_bot,
+            protocol_config: pda::protocol_config(),
+            accepted_asset: pda::accepted_asset(asset_mint),
+            asset_treasury: pda::asset_treasury(asset_mint),
+            asset_mint: *asset_mint,
+            trading_account: *trading_account,
+            token_program: *asset_token_program,
+        },
+        instruction::SweepAssetToTrading { amount },
+    )
+}
+
+// ============ FEES ============
+
+pub fn collect_user_fees(caller: &Pubkey, owner: &Pubkey) -> Instruction {
+    build(
+        accounts::CollectUserFees {
+            caller: *caller,
+            protocol_config: pda::protocol_config(),
+            user_position: pda::user_position(owner),
+            treasury_account: pda::treasury(),
+        },
+        instruction::CollectUserFees {},
+    )
+}
+
+/// Positions are passed as writable remaining accounts; ineligible ones are skipped on-chain
+pub fn collect_batch_fees(caller: &Pubkey, owners: &[Pubkey]) -> Instruction {
+    let mut ix = build(
+        accounts::CollectBatchFees {
+            caller: *caller,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::CollectBatchFees {},
+    );
+    ix.accounts.extend(owners.iter().map(|owner| AccountMeta::new(pda::user_position(owner), false)));
+    ix
+}
+
+pub fn collect_performance_fees(base: &BaseMint, admin: &Pubkey, admin_token_account: &Pubkey) -> Instruction {
+    build(
+        accounts::CollectFees {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            token_mint: base.mint,
+            admin_token_account: *admin_token_account,
+            token_program: base.token_program,
+        },
+        instruction::CollectPerformanceFees {},
+    )
+}
+
+// ============ SHARE PRICE HISTORY ============
+
+pub fn initialize_share_price_history(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::InitializeSharePriceHistory {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            share_price_history: pda::share_price_history(),
+            system_program: system_program::ID,
+        },
+        instruction::InitializeSharePriceHistory {},
+    )
+}
+
+/// Permissionless; needs no signer beyond the fee payer
+pub fn snapshot() -> Instruction {
+    build(
+        accounts::Snapshot {
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            share_price_history: pda::share_price_history(),
+        },
+        instruction::Snapshot {},
+    )
+}
+
+// ============ VIEWS ============
+// Read results from simulateTransaction return data with `state::decode_return_data`.
+
+fn position_view_accounts(owner: &Pubkey) -> accounts::PreviewLimits {
+    accounts::PreviewLimits {
+        user_position: pda::user_position(owner),
+        owner: *owner,
+        protocol_config: pda::protocol_config(),
+        treasury_account: pda::treasury(),
+    }
+}
+
+/// Returns `u64`
+pub fn calculate_user_balance(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::CalculateBalance {
+            user_position: pda::user_position(owner),
+            owner: *owner,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::CalculateUserBalance {},
+    )
+}
+
+/// Returns `(u64, u64, u64)`
+pub fn get_protocol_stats() -> Instruction {
+    build(
+        accounts::GetProtocolStats {
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::GetProtocolStats {},
+    )
+}
+
+/// Returns `(u64, u64, i64)`
+pub fn get_user_stats(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::GetUserStats {
+            user_position: pda::user_position(owner),
+            owner: *owner,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::GetUserStats {},
+    )
+}
+
+/// Returns `VaultSnapshot`
+pub fn get_vault_snapshot() -> Instruction {
+    build(
+        accounts::GetProtocolStats {
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::GetVaultSnapshot {},
+    )
+}
+
+/// Returns `PositionSnapshot`
+pub fn get_position_snapshot(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::GetUserStats {
+            user_position: pda::user_position(owner),
+            owner: *owner,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::GetPositionSnapshot {},
+    )
+}
+
+/// Returns `Vec<Option<WindowReturn>>`, one per window in seconds
+pub fn get_share_price_returns(windows: Vec<i64>) -> Instruction {
+    build(
+        accounts::GetSharePriceReturns { share_price_history: pda::share_price_history() },
+        instruction::GetSharePriceReturns { windows },
+    )
+}
+
+/// Returns `i64`
+pub fn get_unrealized_pnl(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::GetUnrealizedPnl {
+            user_position: pda::user_position(owner),
+            owner: *owner,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+        },
+        instruction::GetUnrealizedPnl {},
+    )
+}
+
+/// Returns `bool`
+pub fn check_fee_eligibility(owner: &Pubkey) -> Instruction {
+    build(
+        accounts::CheckFeeEligibility {
+            user_position: pda::user_position(owner),
+            owner: *owner,
+        },
+        instruction::CheckFeeEligibility {},
+    )
+}
+
+fn preview_accounts(base: &BaseMint) -> accounts::Preview {
+    accounts::Preview {
+        protocol_config: pda::protocol_config(),
+        treasury_account: pda::treasury(),
+        token_mint: base.mint,
+    }
+}
+
+fn preview_position_accounts(base: &BaseMint, owner: &Pubkey) -> accounts::PreviewPosition {
+    accounts::PreviewPosition {
+        user_position: pda::user_position(owner),
+        owner: *owner,
+        protocol_config: pda::protocol_config(),
+        treasury_account: pda::treasury(),
+        token_mint: base.mint,
+    }
+}
+
+/// Returns `DepositPreview`; its `shares` is an exact `min_shares` for `deposit`
+pub fn preview_deposit(base: &BaseMint, owner: &Pubkey, assets: u64) -> Instruction {
+    build(preview_position_accounts(base, owner), instruction::PreviewDeposit { assets })
+}
+
+/// Returns `u64` assets
+pub fn preview_mint(base: &BaseMint, shares: u64) -> Instruction {
+    build(preview_accounts(base), instruction::PreviewMint { shares })
+}
+
+/// Returns `WithdrawPreview`; its `shares` is an exact `max_shares` for `withdraw`
+pub fn preview_withdraw(base: &BaseMint, owner: &Pubkey, assets: u64) -> Instruction {
+    build(preview_position_accounts(base, owner), instruction::PreviewWithdraw { assets })
+}
+
+/// Returns `u64` assets
+pub fn preview_redeem(base: &BaseMint, shares: u64) -> Instruction {
+    build(preview_accounts(base), instruction::PreviewRedeem { shares })
+}
+
+/// Returns `FeePreview`
+pub fn preview_fee(owner: &Pubkey) -> Instruction {
+    build(position_view_accounts(owner), instruction::PreviewFee {})
+}
+
+/// Returns `u64`
+pub fn max_deposit(owner: &Pubkey) -> Instruction {
+    build(position_view_accounts(owner), instruction::MaxDeposit {})
+}
+
+/// Returns `u64`
+pub fn max_mint(owner: &Pubkey) -> Instruction {
+    build(position_view_accounts(owner), instruction::MaxMint {})
+}
+
+/// Returns `u64`
+pub fn max_withdraw(owner: &Pubkey) -> Instruction {
+    build(position_view_accounts(owner), instruction::MaxWithdraw {})
+}
+
+/// Returns `u64`
+pub fn max_redeem(owner: &Pubkey) -> Instruction {
+    build(position_view_accounts(owner), instruction::MaxRedeem {})
+}
+
+// ============ ADMINISTRATION ============
+
+fn admin_accounts(admin: &Pubkey) -> accounts::AdminAction {
+    accounts::AdminAction {
+        admin: *admin,
+        protocol_config: pda::protocol_config(),
+    }
+}
+
+fn pause_accounts(authority: &Pubkey) -> accounts::PauseAction {
+    accounts::PauseAction {
+        authority: *authority,
+        protocol_config: pda::protocol_config(),
+    }
+}
+
+/// Admin or guardian
+pub fn pause_protocol(authority: &Pubkey, operations: u8, reason_code: u16) -> Instruction {
+    build(pause_accounts(authority), instruction::PauseProtocol { operations, reason_code })
+}
+
+pub fn unpause_protocol(admin: &Pubkey, operations: u8, reason_code: u16) -> Instruction {
+    build(admin_accounts(admin), instruction::UnpauseProtocol { operations, reason_code })
+}
+
+/// Admin or guardian
+pub fn enter_emergency_mode(authority: &Pubkey, reason_code: u16) -> Instruction {
+    build(pause_accounts(authority), instruction::EnterEmergencyMode { reason_code })
+}
+
+pub fn exit_emergency_mode(admin: &Pubkey, reason_code: u16) -> Instruction {
+    build(admin_accounts(admin), instruction::ExitEmergencyMode { reason_code })
+}
+
+pub fn set_guardian(admin: &Pubkey, guardian: Pubkey) -> Instruction {
+    build(admin_accounts(admin), instruction::SetGuardian { guardian })
+}
+
+pub fn set_deposit_limits(admin: &Pubkey, max_total_deposits: u64, max_position_size: u64, min_deposit: u64) -> Instruction {
+    build(
+        admin_accounts(admin),
+        instruction::SetDepositLimits { max_total_deposits, max_position_size, min_deposit },
+    )
+}
+
+pub fn set_access_mode(admin: &Pubkey, access_mode: AccessMode, merkle_root: [u8; 32]) -> Instruction {
+    build(admin_accounts(admin), instruction::SetAccessMode { access_mode, merkle_root })
+}
+
+fn attestation_accounts(admin: &Pubkey, user: &Pubkey) -> accounts::ManageAttestation {
+    accounts::ManageAttestation {
+        admin: *admin,
+        protocol_config: pda::protocol_config(),
+        attestation: pda::attestation(user),
+        system_program: system_program::ID,
+    }
+}
+
+pub fn issue_attestation(admin: &Pubkey, user: Pubkey, expires_at: i64) -> Instruction {
+    build(attestation_accounts(admin, &user), instruction::IssueAttestation { user, expires_at })
+}
+
+pub fn revoke_access(admin: &Pubkey, user: Pubkey) -> Instruction {
+    build(attestation_accounts(admin, &user), instruction::RevokeAccess { user })
+}
+
+pub fn add_accepted_asset(
+    admin: &Pubkey,
+    asset_mint: &Pubkey,
+    asset_token_program: &Pubkey,
+    oracle: Pubkey,
+    haircut_bps: u16,
+    price: u64,
+) -> Instruction {
+    build(
+        accounts::AddAcceptedAsset {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            asset_mint: *asset_mint,
+            accepted_asset: pda::accepted_asset(asset_mint),
+            asset_treasury: pda::asset_treasury(asset_mint),
+            token_program: *asset_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::AddAcceptedAsset { oracle, haircut_bps, price },
+    )
+}
+
+pub fn configure_accepted_asset(
+    admin: &Pubkey,
+    asset_mint: &Pubkey,
+    oracle: Pubkey,
+    haircut_bps: u16,
+    enabled: bool,
+) -> Instruction {
+    build(
+        accounts::ConfigureAcceptedAsset {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            accepted_asset: pda::accepted_asset(asset_mint),
+        },
+        instruction::ConfigureAcceptedAsset { oracle, haircut_bps, enabled },
+    )
+}
+
+/// Asset oracle or admin
+/// Listing every other asset with holdings in `other_asset_mints` refreshes the vault's oldest price
+pub fn update_asset_price(
+    authority: &Pubkey,
+    asset_mint: &Pubkey,
+    price: u64,
+    other_asset_mints: &[Pubkey],
+) -> Instruction {
+    let mut ix = build(
+        accounts::UpdateAssetPrice {
+            authority: *authority,
+            protocol_config: pda::protocol_config(),
+            accepted_asset: pda::accepted_asset(asset_mint),
+            asset_treasury: pda::asset_treasury(asset_mint),
+        },
+        instruction::UpdateAssetPrice { price },
+    );
+    ix.accounts.extend(other_asset_mints.iter().map(|mint| AccountMeta::new_readonly(pda::accepted_asset(mint), false)));
+    ix
+}
+
+// ============ LIFECYCLE ============
+
+pub fn close_deposits(admin: &Pubkey) -> Instruction {
+    build(admin_accounts(admin), instruction::CloseDeposits {})
+}
+
+pub fn begin_wind_down(admin: &Pubkey) -> Instruction {
+    build(admin_accounts(admin), instruction::BeginWindDown {})
+}
+
+pub fn close_vault(base: &BaseMint, admin: &Pubkey, admin_token_account: &Pubkey) -> Instruction {
+    build(
+        accounts::CloseVault {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            treasury_account: pda::treasury(),
+            admin_token_account: *admin_token_account,
+            claims_escrow: pda::claims_escrow(),
+            escrow_token_account: pda::claims_escrow_vault(),
+            token_mint: base.mint,
+            system_program: system_program::ID,
+            token_program: base.token_program,
+            rent: sysvar::rent::ID,
+        },
+        instruction::CloseVault {},
+    )
+}
@@ -0,0 +1,15 @@
+//! Off-chain client for the grid-vault program.
+//!
+//! - [`pda`]: addresses for every program-derived account
+//! - [`instructions`]: typed builders for each instruction in `vault_with_treasury`
+//! - [`state`]: account and return-data decoders
+//! - [`nav`]: off-chain mirror of the program's NAV and share math
+
+pub mod instructions;
+pub mod nav;
+pub mod pda;
+pub mod state;
+
+pub use grid_vault::{self, ID as PROGRAM_ID};
+pub use instructions::BaseMint;
+pub use state::ClientError;
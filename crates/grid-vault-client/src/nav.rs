@@ -0,0 +1,87 @@
+//! Off-chain view of the program's NAV and share math.
+//!
+//! Every function delegates to `grid_vault::accounting`, the code the instructions run,
+//! so results match on-chain bit for bit, including rounding. Clients can derive
+//! `min_shares`/`max_shares` bounds without a round trip. Functions return `None` where
+//! the program would fail with `MathOverflow`.
+
+use grid_vault::{
+    accounting, PositionSnapshot, ProtocolConfig, SharePriceHistory, UserPosition, VaultSnapshot, WindowReturn,
+    FEE_COLLECTION_INTERVAL,
+};
+
+pub use grid_vault::accounting::{asset_holdings_value, asset_prices_fresh, deployed_value, valuation_is_fresh, Rounding};
+
+/// `value * numerator / denominator` in u128 with explicit rounding
+pub fn mul_div(value: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Option<u64> {
+    accounting::mul_div(value, numerator, denominator, rounding).ok()
+}
+
+/// Returns (user_value_pool, total_value), as `vault_value_pool` does on-chain
+pub fn vault_value_pool(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Option<(u64, u64)> {
+    accounting::vault_value_pool(config, treasury_balance, now).ok()
+}
+
+/// Assets to shares at `user_value_pool` (1:1 for an empty vault)
+pub fn convert_to_shares(config: &ProtocolConfig, user_value_pool: u64, assets: u64, rounding: Rounding) -> Option<u64> {
+    accounting::convert_to_shares(config, user_value_pool, assets, rounding).ok()
+}
+
+/// Shares to assets at `user_value_pool` (1:1 for an empty vault)
+pub fn convert_to_assets(config: &ProtocolConfig, user_value_pool: u64, shares: u64, rounding: Rounding) -> Option<u64> {
+    accounting::convert_to_assets(config, user_value_pool, shares, rounding).ok()
+}
+
+/// Current value of `position`, rounded down
+pub fn user_balance(config: &ProtocolConfig, position: &UserPosition, treasury_balance: u64, now: i64) -> Option<u64> {
+    accounting::user_balance(config, position, treasury_balance, now).ok().map(|(balance, _, _)| balance)
+}
+
+/// Shares `deposit(amount)` mints, before any transfer fee
+pub fn shares_for_deposit(config: &ProtocolConfig, treasury_balance: u64, amount: u64, now: i64) -> Option<u64> {
+    let (user_value_pool, _) = vault_value_pool(config, treasury_balance, now)?;
+    convert_to_shares(config, user_value_pool, amount, Rounding::Down)
+}
+
+/// Shares `withdraw(amount)` burns
+pub fn shares_for_withdraw(config: &ProtocolConfig, treasury_balance: u64, amount: u64, now: i64) -> Option<u64> {
+    let (user_value_pool, _) = vault_value_pool(config, treasury_balance, now)?;
+    convert_to_shares(config, user_value_pool, amount, Rounding::Up)
+}
+
+/// Whether `collect_user_fees` would accept `position` at `now`
+pub fn fee_eligible(position: &UserPosition, now: i64) -> bool {
+    now >= position.last_fee_collection + FEE_COLLECTION_INTERVAL
+}
+
+/// Performance fee `collect_user_fees` would charge `position` at `now`, ignoring eligibility
+pub fn fee_due(config: &ProtocolConfig, position: &UserPosition, treasury_balance: u64, now: i64) -> Option<u64> {
+    let balance = user_balance(config, position, treasury_balance, now)?;
+    let profit = balance.saturating_sub(position.high_water_mark);
+    accounting::performance_fee(profit, config.performance_fee_bps).ok()
+}
+
+/// Liquidity buffer target and how far the treasury is below it: returns (target, shortfall)
+pub fn buffer_shortfall(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Option<(u64, u64)> {
+    accounting::buffer_shortfall(config, treasury_balance, now).ok()
+}
+
+/// What `get_vault_snapshot` returns at `now`
+pub fn vault_snapshot(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Option<VaultSnapshot> {
+    accounting::vault_snapshot(config, treasury_balance, now).ok()
+}
+
+/// What `get_position_snapshot` returns at `now`
+pub fn position_snapshot(
+    config: &ProtocolConfig,
+    position: &UserPosition,
+    treasury_balance: u64,
+    now: i64,
+) -> Option<PositionSnapshot> {
+    accounting::position_snapshot(config, position, treasury_balance, now).ok()
+}
+
+/// What `get_share_price_returns` reports for one window of `window` seconds
+pub fn window_return(history: &SharePriceHistory, window: i64) -> Option<WindowReturn> {
+    accounting::window_return(history, window).ok().flatten()
+}
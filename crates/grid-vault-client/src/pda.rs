@@ -0,0 +1,49 @@
+//! Program-derived addresses, mirroring the `seeds` constraints in the program.
+
+use anchor_lang::prelude::Pubkey;
+use grid_vault::ID;
+
+pub fn protocol_config() -> Pubkey {
+    Pubkey::find_program_address(&[b"protocol_config"], &ID).0
+}
+
+pub fn treasury() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &ID).0
+}
+
+pub fn user_position(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"user_position", owner.as_ref()], &ID).0
+}
+
+pub fn attestation(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"attestation", user.as_ref()], &ID).0
+}
+
+pub fn withdrawal_delegate(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"withdrawal_delegate", owner.as_ref()], &ID).0
+}
+
+/// Transient wSOL account used by `deposit_sol` / `withdraw_sol`
+pub fn wsol(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"wsol", owner.as_ref()], &ID).0
+}
+
+pub fn accepted_asset(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"accepted_asset", mint.as_ref()], &ID).0
+}
+
+pub fn asset_treasury(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"asset_treasury", mint.as_ref()], &ID).0
+}
+
+pub fn share_price_history() -> Pubkey {
+    Pubkey::find_program_address(&[b"share_price_history"], &ID).0
+}
+
+pub fn claims_escrow() -> Pubkey {
+    Pubkey::find_program_address(&[b"claims_escrow"], &ID).0
+}
+
+pub fn claims_escrow_vault() -> Pubkey {
+    Pubkey::find_program_address(&[b"claims_escrow_vault"], &ID).0
+}
@@ -0,0 +1,91 @@
+//! Decoders for program accounts and view-instruction return data.
+
+use std::fmt;
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize, AnchorDeserialize, Discriminator};
+use grid_vault::{ProtocolConfig, SharePriceHistory, UserPosition};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// Account data does not start with the expected discriminator
+    InvalidDiscriminator,
+    /// Account data is shorter than the current layout; run the migration instruction first
+    OutdatedLayout { len: usize, expected: usize },
+    Deserialize(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidDiscriminator => write!(f, "account discriminator mismatch"),
+            ClientError::OutdatedLayout { len, expected } => {
+                write!(f, "account is {len} bytes, current layout needs {expected}")
+            }
+            ClientError::Deserialize(err) => write!(f, "failed to deserialize: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Decodes any Anchor (borsh) account owned by the program, e.g. `Attestation` or `AcceptedAsset`
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> Result<T, ClientError> {
+    T::try_deserialize(&mut &data[..]).map_err(|err| ClientError::Deserialize(err.to_string()))
+}
+
+pub fn decode_protocol_config(data: &[u8]) -> Result<ProtocolConfig, ClientError> {
+    if data.len() < ProtocolConfig::LEN {
+        return Err(ClientError::OutdatedLayout { len: data.len(), expected: ProtocolConfig::LEN });
+    }
+    decode_account(data)
+}
+
+/// `UserPosition` is zero-copy, so it is read in place rather than through borsh
+pub fn decode_user_position(data: &[u8]) -> Result<UserPosition, ClientError> {
+    if data.len() < 8 || data[..8] != UserPosition::DISCRIMINATOR {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    if data.len() < UserPosition::LEN {
+        return Err(ClientError::OutdatedLayout { len: data.len(), expected: UserPosition::LEN });
+    }
+    Ok(bytemuck::pod_read_unaligned(&data[8..UserPosition::LEN]))
+}
+
+/// Positions found by a program-account scan
+#[derive(Debug, Default)]
+pub struct PositionScan {
+    pub positions: Vec<(Pubkey, UserPosition)>,
+    /// Positions in an older layout; fees can't be collected until `migrate_position` runs
+    pub unmigrated: Vec<Pubkey>,
+}
+
+impl PositionScan {
+    /// Decodes raw position accounts, setting aside the unmigrated ones
+    pub fn from_accounts(accounts: impl IntoIterator<Item = (Pubkey, Vec<u8>)>) -> Result<Self, ClientError> {
+        let mut scan = PositionScan::default();
+        for (address, data) in accounts {
+            match decode_user_position(&data) {
+                Ok(position) => scan.positions.push((address, position)),
+                Err(ClientError::OutdatedLayout { .. }) => scan.unmigrated.push(address),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+/// `SharePriceHistory` is zero-copy as well
+pub fn decode_share_price_history(data: &[u8]) -> Result<SharePriceHistory, ClientError> {
+    if data.len() < 8 || data[..8] != SharePriceHistory::DISCRIMINATOR {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    if data.len() < SharePriceHistory::LEN {
+        return Err(ClientError::OutdatedLayout { len: data.len(), expected: SharePriceHistory::LEN });
+    }
+    Ok(bytemuck::pod_read_unaligned(&data[8..SharePriceHistory::LEN]))
+}
+
+/// Decodes the return data of a view instruction (e.g. `preview_deposit` -> `DepositPreview`)
+pub fn decode_return_data<T: AnchorDeserialize>(data: &[u8]) -> Result<T, ClientError> {
+    T::try_from_slice(data).map_err(|err| ClientError::Deserialize(err.to_string()))
+}
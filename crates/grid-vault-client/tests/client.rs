@@ -0,0 +1,200 @@
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::{entrypoint::ProgramResult, instruction::Instruction, program_pack::Pack},
+};
+use anchor_spl::token::spl_token;
+use grid_vault_client::{
+    grid_vault::{DepositPreview, FeePreview, PositionSnapshot, VaultSnapshot, WithdrawPreview},
+    instructions, nav, pda,
+    state::{decode_protocol_config, decode_return_data, decode_user_position},
+    BaseMint, ClientError,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signer},
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entry ties the slice and account lifetimes together
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    grid_vault::entry(program_id, accounts, data)
+}
+
+struct Vault {
+    ctx: ProgramTestContext,
+    base: BaseMint,
+    admin: Keypair,
+    user: Keypair,
+    user_token_account: Pubkey,
+}
+
+impl Vault {
+    /// Vault over a fresh SPL mint with one user holding 1_000_000 tokens and an open position
+    async fn start() -> Vault {
+        let admin = keypair_from_seed(&[1; 32]).unwrap();
+        let user = keypair_from_seed(&[4; 32]).unwrap();
+        let mut program_test = ProgramTest::new("grid_vault", grid_vault::ID, processor!(process_instruction));
+        program_test.prefer_bpf(false);
+        for signer in [&admin, &user] {
+            program_test.add_account(signer.pubkey(), Account::new(10_000_000_000, 0, &system_program::ID));
+        }
+        let ctx = program_test.start_with_context().await;
+
+        let mint = Keypair::new();
+        let user_token_account = Keypair::new();
+        let mut vault = Vault {
+            ctx,
+            base: BaseMint::spl(mint.pubkey()),
+            admin,
+            user,
+            user_token_account: user_token_account.pubkey(),
+        };
+        let (admin, user) = (vault.admin.insecure_clone(), vault.user.insecure_clone());
+
+        let ixs = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &mint.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), None, 6).unwrap(),
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &user_token_account.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::ID,
+                &user_token_account.pubkey(),
+                &mint.pubkey(),
+                &user.pubkey(),
+            )
+            .unwrap(),
+            spl_token::instruction::mint_to(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &user_token_account.pubkey(),
+                &admin.pubkey(),
+                &[],
+                1_000_000,
+            )
+            .unwrap(),
+            instructions::initialize_protocol(
+                &admin.pubkey(),
+                &vault.base,
+                admin.pubkey(),
+                keypair_from_seed(&[2; 32]).unwrap().pubkey(),
+            ),
+        ];
+        vault.send(&ixs, &[&admin, &mint, &user_token_account]).await;
+        vault.send(&[instructions::create_user_position(&user.pubkey(), vec![])], &[&user]).await;
+        vault
+    }
+
+    async fn send(&mut self, ixs: &[Instruction], signers: &[&Keypair]) {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(ixs, Some(&signers[0].pubkey()), signers, blockhash);
+        self.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    async fn view<T: anchor_lang::AnchorDeserialize>(&mut self, ix: Instruction) -> T {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(&[ix], Some(&self.user.pubkey()), &[&self.user], blockhash);
+        let simulation = self.ctx.banks_client.simulate_transaction(tx).await.unwrap();
+        let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+        decode_return_data(&return_data.data).unwrap()
+    }
+
+    async fn data(&mut self, address: Pubkey) -> Vec<u8> {
+        self.ctx.banks_client.get_account(address).await.unwrap().unwrap().data
+    }
+
+    async fn treasury_balance(&mut self) -> u64 {
+        spl_token::state::Account::unpack(&self.data(pda::treasury()).await).unwrap().amount
+    }
+
+    async fn now(&mut self) -> i64 {
+        self.ctx.banks_client.get_sysvar::<solana_sdk::clock::Clock>().await.unwrap().unix_timestamp
+    }
+}
+
+#[test]
+fn pdas_match_program_seeds() {
+    let owner = Pubkey::new_unique();
+    let derive = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &grid_vault::ID).0;
+    assert_eq!(pda::protocol_config(), derive(&[b"protocol_config"]));
+    assert_eq!(pda::treasury(), derive(&[b"treasury"]));
+    assert_eq!(pda::user_position(&owner), derive(&[b"user_position", owner.as_ref()]));
+    assert_eq!(pda::share_price_history(), derive(&[b"share_price_history"]));
+}
+
+#[test]
+fn mul_div_rounds_in_requested_direction() {
+    assert_eq!(nav::mul_div(10, 1, 3, nav::Rounding::Down), Some(3));
+    assert_eq!(nav::mul_div(10, 1, 3, nav::Rounding::Up), Some(4));
+    assert_eq!(nav::mul_div(9, 1, 3, nav::Rounding::Up), Some(3));
+    assert_eq!(nav::mul_div(1, 1, 0, nav::Rounding::Down), None);
+    assert_eq!(nav::mul_div(u64::MAX, 2, 1, nav::Rounding::Down), None);
+}
+
+#[test]
+fn decoders_reject_foreign_and_legacy_accounts() {
+    assert!(matches!(decode_user_position(&[0; 16]), Err(ClientError::InvalidDiscriminator)));
+    assert!(matches!(decode_protocol_config(&[0; 16]), Err(ClientError::OutdatedLayout { len: 16, .. })));
+}
+
+#[tokio::test]
+async fn builders_drive_program_and_nav_mirror_matches() {
+    let mut vault = Vault::start().await;
+    let (base, user, token_account) = (vault.base, vault.user.insecure_clone(), vault.user_token_account);
+
+    vault
+        .send(&[instructions::deposit(&base, &user.pubkey(), &token_account, 300_000, 300_000, vec![])], &[&user])
+        .await;
+    vault
+        .send(&[instructions::withdraw(&base, &user.pubkey(), &token_account, 100_001, 100_001)], &[&user])
+        .await;
+
+    let config = decode_protocol_config(&vault.data(pda::protocol_config()).await).unwrap();
+    let position = decode_user_position(&vault.data(pda::user_position(&user.pubkey())).await).unwrap();
+    assert_eq!(config.total_shares, 199_999);
+    assert_eq!(position.owner, user.pubkey());
+    assert_eq!(position.user_shares, 199_999);
+
+    let treasury_balance = vault.treasury_balance().await;
+    let now = vault.now().await;
+    let on_chain: DepositPreview = vault.view(instructions::preview_deposit(&base, &user.pubkey(), 12_345)).await;
+    assert_eq!(Some(on_chain.shares), nav::shares_for_deposit(&config, treasury_balance, 12_345, now));
+    let on_chain: WithdrawPreview = vault.view(instructions::preview_withdraw(&base, &user.pubkey(), 12_345)).await;
+    assert_eq!(Some(on_chain.shares), nav::shares_for_withdraw(&config, treasury_balance, 12_345, now));
+    let on_chain: FeePreview = vault.view(instructions::preview_fee(&user.pubkey())).await;
+    assert_eq!(Some(on_chain.fee), nav::fee_due(&config, &position, treasury_balance, now));
+    let on_chain: u64 = vault.view(instructions::calculate_user_balance(&user.pubkey())).await;
+    assert_eq!(Some(on_chain), nav::user_balance(&config, &position, treasury_balance, now));
+    let eligible: bool = vault.view(instructions::check_fee_eligibility(&user.pubkey())).await;
+    assert_eq!(eligible, nav::fee_eligible(&position, now));
+    let on_chain: VaultSnapshot = vault.view(instructions::get_vault_snapshot()).await;
+    assert_eq!(Some(on_chain), nav::vault_snapshot(&config, treasury_balance, now));
+    let on_chain: PositionSnapshot = vault.view(instructions::get_position_snapshot(&user.pubkey())).await;
+    assert_eq!(Some(on_chain), nav::position_snapshot(&config, &position, treasury_balance, now));
+}
+
+#[test]
+fn collect_batch_fees_appends_writable_positions() {
+    let owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let ix = instructions::collect_batch_fees(&Pubkey::new_unique(), &owners);
+    let remaining = &ix.accounts[3..];
+    assert_eq!(remaining.len(), 2);
+    for (meta, owner) in remaining.iter().zip(&owners) {
+        assert_eq!(meta.pubkey, pda::user_position(owner));
+        assert!(meta.is_writable && !meta.is_signer);
+    }
+}
@@ -0,0 +1,112 @@
+//! Extracts vault events from transaction logs (`emit!`) and inner instructions (`emit_cpi!`).
+
+use anchor_lang::{event::EVENT_IX_TAG_LE, prelude::Pubkey, AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use grid_vault_client::grid_vault::*;
+
+macro_rules! vault_events {
+    ($($name:ident),* $(,)?) => {
+        /// Every event the program emits
+        pub enum VaultEvent {
+            $($name($name),)*
+        }
+
+        impl VaultEvent {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(VaultEvent::$name(_) => stringify!($name),)*
+                }
+            }
+
+            /// Cluster time the program stamped on the event
+            pub fn timestamp(&self) -> i64 {
+                match self {
+                    $(VaultEvent::$name(e) => e.timestamp,)*
+                }
+            }
+
+            /// Decodes discriminator-prefixed event bytes; `None` for unknown or malformed events
+            pub fn decode(data: &[u8]) -> Option<VaultEvent> {
+                let discriminator = data.get(..8)?;
+                let mut payload = &data[8..];
+                $(
+                    if discriminator == $name::DISCRIMINATOR {
+                        return $name::deserialize(&mut payload).ok().map(VaultEvent::$name);
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+
+vault_events!(
+    DepositEvent,
+    WithdrawEvent,
+    AssetDepositEvent,
+    AcceptedAssetUpdatedEvent,
+    AssetPriceUpdatedEvent,
+    AssetSweptEvent,
+    WithdrawalDelegateSetEvent,
+    WithdrawalDelegateRevokedEvent,
+    UserPositionClosedEvent,
+    ProtocolPausedEvent,
+    ProtocolUnpausedEvent,
+    GuardianUpdatedEvent,
+    EmergencyModeEnteredEvent,
+    EmergencyModeExitedEvent,
+    EmergencyWithdrawEvent,
+    LifecycleChangedEvent,
+    FinalBalanceClaimedEvent,
+    VaultClosedEvent,
+    EscrowClaimedEvent,
+    DepositLimitsUpdatedEvent,
+    AccessModeUpdatedEvent,
+    AttestationIssuedEvent,
+    AccessRevokedEvent,
+    AccountMigratedEvent,
+    CapitalDeployedEvent,
+    CapitalReturnedEvent,
+    LiquidityBufferCheckedEvent,
+    FeeCollectedEvent,
+    FeesWithdrawnEvent,
+    SharePriceSnapshotEvent,
+    ValuationUpdateEvent,
+);
+
+/// Raw event payloads logged as `Program data:` while `program_id` is the executing program
+pub fn event_data_from_logs(program_id: &Pubkey, logs: &[String]) -> Vec<Vec<u8>> {
+    let program_id = program_id.to_string();
+    // One entry per active invocation: whether it is `program_id`
+    let mut stack: Vec<bool> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let Some(rest) = line.strip_prefix("Program ") else { continue };
+        if let Some(data) = rest.strip_prefix("data: ") {
+            if stack.last() == Some(&true) {
+                // Anchor logs one base64 field per event
+                if let Ok(bytes) = STANDARD.decode(data.trim()) {
+                    events.push(bytes);
+                }
+            }
+            continue;
+        }
+        if rest.starts_with("log: ") || rest.starts_with("return: ") {
+            continue;
+        }
+        let mut words = rest.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(id), Some("invoke")) => stack.push(id == program_id),
+            (Some(_), Some(verb)) if verb == "success" || verb.starts_with("failed") => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Raw event payload of an `emit_cpi!` self-invocation, if `data` is one
+pub fn event_data_from_instruction(data: &[u8]) -> Option<Vec<u8>> {
+    data.strip_prefix(&EVENT_IX_TAG_LE[..]).map(<[u8]>::to_vec)
+}
@@ -0,0 +1,72 @@
+//! Sync loop: reorg check against recent checkpoints, then index new slots in order.
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+
+use crate::{source::Source, store::Store};
+
+/// Checkpoints re-verified on every sync; deeper forks are not expected below `confirmed`
+pub const DEFAULT_REORG_DEPTH: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Slot the store was rolled back to after a fork was detected
+    pub rolled_back_to: Option<u64>,
+    pub slots: usize,
+    pub transactions: usize,
+    pub events: usize,
+    /// Last slot indexed by this sync
+    pub last_slot: Option<u64>,
+}
+
+pub struct Indexer<S: Source> {
+    pub source: S,
+    pub store: Store,
+    program_id: Pubkey,
+    reorg_depth: usize,
+}
+
+impl<S: Source> Indexer<S> {
+    pub fn new(source: S, store: Store, program_id: Pubkey, reorg_depth: usize) -> Self {
+        Indexer { source, store, program_id, reorg_depth }
+    }
+
+    pub fn sync(&mut self) -> Result<SyncReport> {
+        let mut report = SyncReport { rolled_back_to: self.rollback_orphaned_slots()?, ..SyncReport::default() };
+
+        let signatures = self.source.signatures_after(self.store.cursor()?)?;
+        let mut pending = signatures.as_slice();
+        while let Some(&(slot, _)) = pending.first() {
+            let count = pending.iter().take_while(|(s, _)| *s == slot).count();
+            let (batch, rest) = pending.split_at(count);
+            pending = rest;
+
+            // A slot whose block is not yet available is picked up by the next sync
+            let Some(block_hash) = self.source.block_hash(slot)? else { break };
+            let mut transactions = Vec::with_capacity(batch.len());
+            for (_, signature) in batch {
+                transactions.extend(self.source.transaction(signature)?);
+            }
+            report.events += self.store.apply_slot(&self.program_id, slot, &block_hash, &transactions)?;
+            report.transactions += transactions.len();
+            report.slots += 1;
+            report.last_slot = Some(slot);
+        }
+        Ok(report)
+    }
+
+    /// Walks recent checkpoints newest first and drops every slot the cluster no longer agrees on
+    fn rollback_orphaned_slots(&mut self) -> Result<Option<u64>> {
+        let mut fork = None;
+        for (slot, block_hash) in self.store.recent_checkpoints(self.reorg_depth)? {
+            if self.source.block_hash(slot)?.as_deref() == Some(block_hash.as_str()) {
+                break;
+            }
+            fork = Some(slot);
+        }
+        let Some(slot) = fork else { return Ok(None) };
+        let keep = slot.saturating_sub(1);
+        self.store.rollback_after(keep)?;
+        Ok(Some(keep))
+    }
+}
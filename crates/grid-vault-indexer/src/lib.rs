@@ -0,0 +1,18 @@
+//! Indexes grid-vault events into SQLite.
+//!
+//! - [`events`]: decoding from `Program data:` logs and `emit_cpi!` inner instructions
+//! - [`projection`]: NAV and per-user history derived from the events
+//! - [`store`]: SQLite schema, per-slot writes and rollback
+//! - [`source`]: transaction source, RPC-backed in production
+//! - [`indexer`]: reorg check and in-order sync
+
+pub mod events;
+pub mod indexer;
+pub mod projection;
+pub mod source;
+pub mod store;
+
+pub use events::VaultEvent;
+pub use indexer::{Indexer, SyncReport, DEFAULT_REORG_DEPTH};
+pub use source::{IndexedTransaction, RpcSource, Source};
+pub use store::Store;
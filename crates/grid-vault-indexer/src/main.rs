@@ -0,0 +1,70 @@
+use std::{thread, time::Duration};
+
+use anyhow::Result;
+use clap::Parser;
+use grid_vault_client::PROGRAM_ID;
+use grid_vault_indexer::{Indexer, RpcSource, Store, DEFAULT_REORG_DEPTH};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+#[derive(Parser)]
+#[command(name = "grid-vault-indexer", version, about = "Index grid-vault events into SQLite")]
+struct Args {
+    /// RPC endpoint; defaults to a local validator
+    #[arg(long, short = 'u', env = "GRID_VAULT_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// SQLite database, created if missing
+    #[arg(long, env = "GRID_VAULT_INDEX_DB", default_value = "grid-vault-index.sqlite")]
+    db: String,
+
+    /// Drop everything from this slot on and index it again
+    #[arg(long)]
+    from_slot: Option<u64>,
+
+    /// Keep polling for new transactions
+    #[arg(long)]
+    follow: bool,
+
+    /// Seconds between polls with --follow
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+
+    /// Only index finalized transactions; no reorg can then reach the database
+    #[arg(long)]
+    finalized: bool,
+
+    /// Checkpoints re-verified against the cluster on every poll
+    #[arg(long, default_value_t = DEFAULT_REORG_DEPTH)]
+    reorg_depth: usize,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let commitment = if args.finalized { CommitmentConfig::finalized() } else { CommitmentConfig::confirmed() };
+    let mut store = Store::open(&args.db)?;
+    if let Some(slot) = args.from_slot {
+        store.replay_from(slot)?;
+    }
+    let source = RpcSource::new(&args.url, PROGRAM_ID, commitment);
+    let mut indexer = Indexer::new(source, store, PROGRAM_ID, args.reorg_depth);
+
+    loop {
+        let report = indexer.sync()?;
+        if let Some(slot) = report.rolled_back_to {
+            eprintln!("fork detected, rolled back to slot {slot}");
+        }
+        if report.slots > 0 {
+            eprintln!(
+                "indexed {} event(s) from {} transaction(s) in {} slot(s), up to slot {}",
+                report.events,
+                report.transactions,
+                report.slots,
+                report.last_slot.unwrap_or_default()
+            );
+        }
+        if !args.follow {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
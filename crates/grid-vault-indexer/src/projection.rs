@@ -0,0 +1,159 @@
+//! NAV and per-user history derived from the event stream.
+//!
+//! Events carry most, but not all, of the vault's accounting: the fee taken on
+//! `return_capital_from_trading` and accepted-asset holdings are not emitted, so
+//! `NavPoint` tracks the base-asset NAV and is resynced by the figures events do report.
+
+use grid_vault_client::grid_vault::STALE_VALUATION_THRESHOLD;
+
+use crate::events::VaultEvent;
+
+/// Running vault accounting after an event
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NavPoint {
+    pub treasury_balance: u64,
+    pub total_deployed: u64,
+    /// Latest reported valuation of deployed capital, 0 when none is live
+    pub deployed_value: u64,
+    pub valuation_timestamp: i64,
+    pub accumulated_fees: u64,
+    pub pending_fees: u64,
+    pub total_shares: u64,
+    pub emergency_mode: bool,
+}
+
+impl NavPoint {
+    /// Applies `event`; returns whether it moved the NAV inputs
+    pub fn apply(&mut self, event: &VaultEvent) -> bool {
+        match event {
+            VaultEvent::DepositEvent(e) => {
+                self.treasury_balance = e.treasury_balance;
+                self.total_shares = self.total_shares.saturating_add(e.shares_minted);
+            }
+            VaultEvent::AssetDepositEvent(e) => {
+                self.total_shares = self.total_shares.saturating_add(e.shares_minted);
+            }
+            VaultEvent::WithdrawEvent(e) => {
+                self.treasury_balance = self.treasury_balance.saturating_sub(e.amount);
+                self.total_shares = self.total_shares.saturating_sub(e.shares_burned);
+            }
+            VaultEvent::EmergencyWithdrawEvent(e) => {
+                self.treasury_balance = self.treasury_balance.saturating_sub(e.amount);
+                self.total_shares = self.total_shares.saturating_sub(e.shares_burned);
+            }
+            VaultEvent::FinalBalanceClaimedEvent(e) => {
+                // The fee stays in the treasury as accrued protocol fees
+                self.treasury_balance = self.treasury_balance.saturating_sub(e.amount);
+                self.accumulated_fees = self.accumulated_fees.saturating_add(e.fee);
+                self.total_shares = self.total_shares.saturating_sub(e.shares_burned);
+            }
+            VaultEvent::EscrowClaimedEvent(e) => {
+                self.total_shares = self.total_shares.saturating_sub(e.shares_burned);
+            }
+            VaultEvent::VaultClosedEvent(_) => {
+                self.treasury_balance = 0;
+                self.accumulated_fees = 0;
+            }
+            VaultEvent::FeeCollectedEvent(e) => {
+                self.accumulated_fees = self.accumulated_fees.saturating_add(e.fee);
+                self.total_shares = self.total_shares.saturating_sub(e.shares_reduced);
+            }
+            VaultEvent::FeesWithdrawnEvent(e) => {
+                self.treasury_balance = self.treasury_balance.saturating_sub(e.amount);
+                self.accumulated_fees = self.accumulated_fees.saturating_sub(e.amount);
+            }
+            VaultEvent::CapitalDeployedEvent(e) => {
+                self.treasury_balance = e.treasury_remaining;
+                self.total_deployed = e.total_deployed;
+            }
+            VaultEvent::CapitalReturnedEvent(e) => {
+                self.treasury_balance = e.new_treasury_balance;
+                let original = (e.amount as i128 - e.profit_or_loss as i128).clamp(0, u64::MAX as i128) as u64;
+                let remaining = self.total_deployed.saturating_sub(original);
+                if remaining == 0 {
+                    self.deployed_value = 0;
+                    self.valuation_timestamp = 0;
+                    self.pending_fees = 0;
+                } else {
+                    // Same pro-rata scaling the program applies to the live valuation
+                    self.deployed_value =
+                        (self.deployed_value as u128 * remaining as u128 / (remaining as u128 + original as u128)) as u64;
+                }
+                self.total_deployed = remaining;
+            }
+            VaultEvent::AssetSweptEvent(e) => {
+                self.total_deployed = e.total_deployed;
+                if self.deployed_value > 0 {
+                    self.deployed_value = self.deployed_value.saturating_add(e.value);
+                }
+            }
+            VaultEvent::ValuationUpdateEvent(e) => {
+                self.total_deployed = e.total_deployed_original;
+                self.deployed_value = e.total_deployed_current;
+                self.valuation_timestamp = e.timestamp;
+                self.pending_fees = e.pending_fees;
+            }
+            VaultEvent::EmergencyModeEnteredEvent(_) => self.emergency_mode = true,
+            VaultEvent::EmergencyModeExitedEvent(_) => self.emergency_mode = false,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Deployed capital as the program would value it at `now`
+    pub fn deployed_value_at(&self, now: i64) -> u64 {
+        if self.emergency_mode {
+            0
+        } else if self.valuation_timestamp > 0 && now - self.valuation_timestamp <= STALE_VALUATION_THRESHOLD {
+            self.deployed_value
+        } else {
+            self.total_deployed
+        }
+    }
+
+    /// Value attributable to shareholders at `now`
+    pub fn user_value_pool(&self, now: i64) -> u64 {
+        let pending_fees = if self.emergency_mode { 0 } else { self.pending_fees };
+        self.treasury_balance
+            .saturating_add(self.deployed_value_at(now))
+            .saturating_sub(self.accumulated_fees)
+            .saturating_sub(pending_fees)
+    }
+}
+
+/// One row of a user's history
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserEntry {
+    pub user: String,
+    pub kind: &'static str,
+    /// Base-asset value moved, credited for deposits
+    pub amount: u64,
+    pub shares_delta: i64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+impl UserEntry {
+    pub fn from_event(event: &VaultEvent) -> Option<UserEntry> {
+        let (user, kind, amount, shares_delta, fee, timestamp) = match event {
+            VaultEvent::DepositEvent(e) => (e.user, "deposit", e.amount, e.shares_minted as i64, 0, e.timestamp),
+            VaultEvent::AssetDepositEvent(e) => {
+                (e.user, "asset_deposit", e.value, e.shares_minted as i64, 0, e.timestamp)
+            }
+            VaultEvent::WithdrawEvent(e) => (e.user, "withdraw", e.amount, -(e.shares_burned as i64), 0, e.timestamp),
+            VaultEvent::EmergencyWithdrawEvent(e) => {
+                (e.user, "emergency_withdraw", e.amount, -(e.shares_burned as i64), 0, e.timestamp)
+            }
+            VaultEvent::FinalBalanceClaimedEvent(e) => {
+                (e.user, "final_claim", e.amount, -(e.shares_burned as i64), e.fee, e.timestamp)
+            }
+            VaultEvent::EscrowClaimedEvent(e) => {
+                (e.user, "escrow_claim", e.amount, -(e.shares_burned as i64), e.fee, e.timestamp)
+            }
+            VaultEvent::FeeCollectedEvent(e) => (e.user, "fee", 0, -(e.shares_reduced as i64), e.fee, e.timestamp),
+            _ => return None,
+        };
+        Some(UserEntry { user: user.to_string(), kind, amount, shares_delta, fee, timestamp })
+    }
+}
+
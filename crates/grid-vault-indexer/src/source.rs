@@ -0,0 +1,150 @@
+//! Where transactions come from: an RPC node in production.
+
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use solana_client::{
+    client_error::ClientErrorKind,
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::{RpcBlockConfig, RpcTransactionConfig},
+    rpc_request::RpcError,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, TransactionDetails, UiInstruction, UiTransactionEncoding,
+};
+
+/// A successful transaction that touched the program
+#[derive(Clone, Debug)]
+pub struct IndexedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub logs: Vec<String>,
+    /// (program id, data) of every inner instruction, in execution order
+    pub inner_instructions: Vec<(Pubkey, Vec<u8>)>,
+}
+
+pub trait Source {
+    /// Signatures of successful program transactions in slots after `after_slot`, oldest first
+    fn signatures_after(&mut self, after_slot: u64) -> Result<Vec<(u64, String)>>;
+    /// `None` if the transaction failed or is no longer available
+    fn transaction(&mut self, signature: &str) -> Result<Option<IndexedTransaction>>;
+    /// Hash of the block at `slot`, or `None` if the slot was skipped or dropped
+    fn block_hash(&mut self, slot: u64) -> Result<Option<String>>;
+}
+
+pub struct RpcSource {
+    client: RpcClient,
+    program_id: Pubkey,
+    commitment: CommitmentConfig,
+}
+
+impl RpcSource {
+    pub fn new(url: &str, program_id: Pubkey, commitment: CommitmentConfig) -> Self {
+        RpcSource { client: RpcClient::new_with_commitment(url.to_string(), commitment), program_id, commitment }
+    }
+}
+
+/// JSON-RPC codes for slots that were skipped, cleaned up or not yet confirmed
+const MISSING_BLOCK_CODES: [i64; 4] = [-32004, -32007, -32009, -32014];
+
+impl Source for RpcSource {
+    fn signatures_after(&mut self, after_slot: u64) -> Result<Vec<(u64, String)>> {
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(1000),
+                commitment: Some(self.commitment),
+            };
+            let page = self.client.get_signatures_for_address_with_config(&self.program_id, config)?;
+            let Some(last) = page.last() else { break };
+            before = Some(Signature::from_str(&last.signature)?);
+            let done = last.slot <= after_slot;
+            signatures.extend(
+                page.into_iter()
+                    .filter(|status| status.slot > after_slot && status.err.is_none())
+                    .map(|status| (status.slot, status.signature)),
+            );
+            if done {
+                break;
+            }
+        }
+        // Newest first from the node; replay oldest first
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    fn transaction(&mut self, signature: &str) -> Result<Option<IndexedTransaction>> {
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(self.commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let confirmed = self.client.get_transaction_with_config(&Signature::from_str(signature)?, config)?;
+        let meta = confirmed.transaction.meta.ok_or_else(|| anyhow!("{signature} has no status meta"))?;
+        if meta.err.is_some() {
+            return Ok(None);
+        }
+        let tx = confirmed
+            .transaction
+            .transaction
+            .decode()
+            .ok_or_else(|| anyhow!("{signature} could not be decoded"))?;
+
+        // Static keys, then lookup-table keys in the order the runtime loads them
+        let mut account_keys = tx.message.static_account_keys().to_vec();
+        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+            for key in loaded.writable.iter().chain(&loaded.readonly) {
+                account_keys.push(Pubkey::from_str(key)?);
+            }
+        }
+        let mut inner_instructions = Vec::new();
+        if let OptionSerializer::Some(inner) = meta.inner_instructions {
+            for instruction in inner.into_iter().flat_map(|set| set.instructions) {
+                if let UiInstruction::Compiled(compiled) = instruction {
+                    let program_id = *account_keys
+                        .get(compiled.program_id_index as usize)
+                        .ok_or_else(|| anyhow!("{signature} has an out-of-range program index"))?;
+                    inner_instructions.push((program_id, bs58::decode(&compiled.data).into_vec()?));
+                }
+            }
+        }
+        let logs = match meta.log_messages {
+            OptionSerializer::Some(logs) => logs,
+            _ => Vec::new(),
+        };
+        Ok(Some(IndexedTransaction {
+            signature: signature.to_string(),
+            slot: confirmed.slot,
+            block_time: confirmed.block_time,
+            logs,
+            inner_instructions,
+        }))
+    }
+
+    fn block_hash(&mut self, slot: u64) -> Result<Option<String>> {
+        let config = RpcBlockConfig {
+            encoding: None,
+            transaction_details: Some(TransactionDetails::None),
+            rewards: Some(false),
+            commitment: Some(self.commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        match self.client.get_block_with_config(slot, config) {
+            Ok(block) => Ok(Some(block.blockhash)),
+            Err(err) => match err.kind() {
+                ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })
+                    if MISSING_BLOCK_CODES.contains(code) =>
+                {
+                    Ok(None)
+                }
+                _ => Err(err.into()),
+            },
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! SQLite schema and writes. Every slot is applied in one SQLite transaction
+//! together with its checkpoint, so the database never holds half a slot.
+
+use std::path::Path;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    events::{event_data_from_instruction, event_data_from_logs, VaultEvent},
+    projection::{NavPoint, UserEntry},
+    source::IndexedTransaction,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checkpoints (
+    slot INTEGER PRIMARY KEY,
+    block_hash TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS events (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    name TEXT NOT NULL,
+    timestamp INTEGER,
+    data BLOB NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS events_by_slot ON events (slot);
+CREATE INDEX IF NOT EXISTS events_by_name ON events (name, slot);
+CREATE TABLE IF NOT EXISTS user_history (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    shares_delta INTEGER NOT NULL,
+    fee INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS user_history_by_user ON user_history (user, slot);
+CREATE TABLE IF NOT EXISTS nav_history (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    treasury_balance INTEGER NOT NULL,
+    total_deployed INTEGER NOT NULL,
+    deployed_value INTEGER NOT NULL,
+    valuation_timestamp INTEGER NOT NULL,
+    accumulated_fees INTEGER NOT NULL,
+    pending_fees INTEGER NOT NULL,
+    total_shares INTEGER NOT NULL,
+    emergency_mode INTEGER NOT NULL,
+    user_value_pool INTEGER NOT NULL,
+    share_price REAL NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS nav_history_by_slot ON nav_history (slot);
+";
+
+/// Tables keyed by slot that a rollback trims
+const SLOT_TABLES: [&str; 4] = ["checkpoints", "events", "user_history", "nav_history"];
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Store { conn })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Slot indexing resumes after: the last checkpoint, or just before the replay start
+    pub fn cursor(&self) -> Result<u64> {
+        let checkpoint = self.last_checkpoint()?.map(|(slot, _)| slot);
+        let start: Option<u64> = self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = 'start_slot'", [], |row| row.get(0))
+            .optional()?;
+        let floor = start.map(|slot| slot.saturating_sub(1));
+        Ok(checkpoint.max(floor).unwrap_or(0))
+    }
+
+    pub fn last_checkpoint(&self) -> Result<Option<(u64, String)>> {
+        Ok(self
+            .conn
+            .query_row("SELECT slot, block_hash FROM checkpoints ORDER BY slot DESC LIMIT 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?)
+    }
+
+    /// Most recent checkpoints, newest first
+    pub fn recent_checkpoints(&self, limit: usize) -> Result<Vec<(u64, String)>> {
+        let mut stmt = self.conn.prepare("SELECT slot, block_hash FROM checkpoints ORDER BY slot DESC LIMIT ?1")?;
+        let rows = stmt.query_map([limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Drops everything indexed from slots after `slot`
+    pub fn rollback_after(&mut self, slot: u64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for table in SLOT_TABLES {
+            tx.execute(&format!("DELETE FROM {table} WHERE slot > ?1"), [slot])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Re-indexes from `slot`: later data is dropped and the cursor moved back
+    pub fn replay_from(&mut self, slot: u64) -> Result<()> {
+        self.rollback_after(slot.saturating_sub(1))?;
+        self.conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('start_slot', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [slot],
+        )?;
+        Ok(())
+    }
+
+    /// NAV inputs after the last indexed event
+    pub fn nav_state(&self) -> Result<NavPoint> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT treasury_balance, total_deployed, deployed_value, valuation_timestamp, accumulated_fees,
+                        pending_fees, total_shares, emergency_mode
+                 FROM nav_history ORDER BY slot DESC, rowid DESC LIMIT 1",
+                [],
+                |row| {
+                    Ok(NavPoint {
+                        treasury_balance: row.get(0)?,
+                        total_deployed: row.get(1)?,
+                        deployed_value: row.get(2)?,
+                        valuation_timestamp: row.get(3)?,
+                        accumulated_fees: row.get(4)?,
+                        pending_fees: row.get(5)?,
+                        total_shares: row.get(6)?,
+                        emergency_mode: row.get(7)?,
+                    })
+                },
+            )
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    /// Indexes `transactions` (all from `slot`, in order) and checkpoints the slot
+    pub fn apply_slot(
+        &mut self,
+        program_id: &Pubkey,
+        slot: u64,
+        block_hash: &str,
+        transactions: &[IndexedTransaction],
+    ) -> Result<usize> {
+        let mut nav = self.nav_state()?;
+        let tx = self.conn.transaction()?;
+        let mut indexed = 0;
+        for transaction in transactions {
+            let cpi_events = transaction
+                .inner_instructions
+                .iter()
+                .filter(|(program, _)| program == program_id)
+                .filter_map(|(_, data)| event_data_from_instruction(data));
+            let raw_events = event_data_from_logs(program_id, &transaction.logs).into_iter().chain(cpi_events);
+
+            for (index, data) in raw_events.enumerate() {
+                let event = VaultEvent::decode(&data);
+                tx.execute(
+                    "INSERT INTO events (signature, event_index, slot, block_time, name, timestamp, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        transaction.signature,
+                        index,
+                        slot,
+                        transaction.block_time,
+                        event.as_ref().map_or("Unknown", VaultEvent::name),
+                        event.as_ref().map(VaultEvent::timestamp),
+                        data,
+                    ],
+                )?;
+                indexed += 1;
+                let Some(event) = event else { continue };
+
+                if let Some(entry) = UserEntry::from_event(&event) {
+                    tx.execute(
+                        "INSERT INTO user_history
+                         (signature, event_index, slot, user, kind, amount, shares_delta, fee, timestamp)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                        params![
+                            transaction.signature,
+                            index,
+                            slot,
+                            entry.user,
+                            entry.kind,
+                            entry.amount,
+                            entry.shares_delta,
+                            entry.fee,
+                            entry.timestamp,
+                        ],
+                    )?;
+                }
+
+                if nav.apply(&event) {
+                    let now = event.timestamp();
+                    let user_value_pool = nav.user_value_pool(now);
+                    let share_price = if nav.total_shares == 0 {
+                        1.0
+                    } else {
+                        user_value_pool as f64 / nav.total_shares as f64
+                    };
+                    tx.execute(
+                        "INSERT INTO nav_history
+                         (signature, event_index, slot, event, timestamp, treasury_balance, total_deployed,
+                          deployed_value, valuation_timestamp, accumulated_fees, pending_fees, total_shares,
+                          emergency_mode, user_value_pool, share_price)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                        params![
+                            transaction.signature,
+                            index,
+                            slot,
+                            event.name(),
+                            now,
+                            nav.treasury_balance,
+                            nav.total_deployed,
+                            nav.deployed_value,
+                            nav.valuation_timestamp,
+                            nav.accumulated_fees,
+                            nav.pending_fees,
+                            nav.total_shares,
+                            nav.emergency_mode,
+                            user_value_pool,
+                            share_price,
+                        ],
+                    )?;
+                }
+            }
+        }
+        tx.execute("INSERT INTO checkpoints (slot, block_hash) VALUES (?1, ?2)", params![slot, block_hash])?;
+        tx.commit()?;
+        Ok(indexed)
+    }
+}
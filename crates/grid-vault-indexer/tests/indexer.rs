@@ -0,0 +1,379 @@
+use std::{collections::BTreeMap, sync::Once};
+
+use anchor_lang::{
+    event::EVENT_IX_TAG_LE,
+    prelude::{AccountInfo, Pubkey},
+    solana_program::{entrypoint::ProgramResult, instruction::Instruction, program_pack::Pack},
+    Event,
+};
+use anchor_spl::token::spl_token;
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use grid_vault_client::{
+    grid_vault::{self, CapitalDeployedEvent, DepositEvent, FeeCollectedEvent, ValuationUpdateEvent},
+    instructions,
+    state::decode_protocol_config,
+    BaseMint, PROGRAM_ID,
+};
+use grid_vault_indexer::{
+    events::event_data_from_logs, IndexedTransaction, Indexer, Source, Store, VaultEvent, DEFAULT_REORG_DEPTH,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    program_stubs::{self, SyscallStubs},
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signer},
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entry ties the slice and account lifetimes together
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    grid_vault::entry(program_id, accounts, data)
+}
+
+/// Prefix marking `sol_log_data` output that [`DataLogStubs`] routed through `sol_log`
+const DATA_LOG_TAG: &str = "__grid_vault_data__ ";
+
+/// Native program-test prints `sol_log_data` to stdout, so events never reach the
+/// transaction logs. This forwards everything to program-test's stubs and logs the
+/// data as a tagged line that `execute` rewrites to the runtime's `Program data:` form.
+struct DataLogStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for DataLogStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.0.sol_log(&format!("{DATA_LOG_TAG}{}", fields.join(" ")))
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// Must run after program-test has installed its own stubs
+fn route_data_logs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        // Swap in the default stubs only long enough to take ownership of program-test's
+        struct Unset;
+        impl SyscallStubs for Unset {}
+        let inner = program_stubs::set_syscall_stubs(Box::new(Unset));
+        program_stubs::set_syscall_stubs(Box::new(DataLogStubs(inner)));
+    });
+}
+
+/// Runs `ixs` and returns the transaction as the RPC source would report it
+async fn execute(ctx: &mut ProgramTestContext, ixs: &[Instruction], signers: &[&Keypair]) -> IndexedTransaction {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&signers[0].pubkey()), signers, blockhash);
+    let signature = tx.signatures[0].to_string();
+    let result = ctx.banks_client.process_transaction_with_metadata(tx).await.unwrap();
+    result.result.unwrap();
+    IndexedTransaction {
+        signature,
+        slot: 0,
+        block_time: None,
+        logs: result
+            .metadata
+            .unwrap()
+            .log_messages
+            .into_iter()
+            .map(|line| line.replace(&format!("Program log: {DATA_LOG_TAG}"), "Program data: "))
+            .collect(),
+        inner_instructions: vec![],
+    }
+}
+
+fn log_event(event: &impl Event) -> String {
+    format!("Program data: {}", STANDARD.encode(event.data()))
+}
+
+/// Transaction whose only program frame is the vault's, emitting `events`
+fn transaction(signature: &str, slot: u64, events: &[String]) -> IndexedTransaction {
+    let mut logs = vec![format!("Program {PROGRAM_ID} invoke [1]")];
+    logs.extend(events.iter().cloned());
+    logs.push(format!("Program {PROGRAM_ID} success"));
+    IndexedTransaction { signature: signature.to_string(), slot, block_time: None, logs, inner_instructions: vec![] }
+}
+
+fn deposit_event(user: Pubkey, amount: u64, treasury_balance: u64, timestamp: i64) -> String {
+    log_event(&DepositEvent {
+        user,
+        payer: user,
+        amount,
+        shares_minted: amount,
+        treasury_balance,
+        timestamp,
+    })
+}
+
+/// Scripted chain: slot -> (block hash, transactions)
+#[derive(Default)]
+struct MemorySource {
+    blocks: BTreeMap<u64, (String, Vec<IndexedTransaction>)>,
+}
+
+impl Source for MemorySource {
+    fn signatures_after(&mut self, after_slot: u64) -> Result<Vec<(u64, String)>> {
+        Ok(self
+            .blocks
+            .range(after_slot + 1..)
+            .flat_map(|(slot, (_, txs))| txs.iter().map(move |tx| (*slot, tx.signature.clone())))
+            .collect())
+    }
+
+    fn transaction(&mut self, signature: &str) -> Result<Option<IndexedTransaction>> {
+        Ok(self.blocks.values().flat_map(|(_, txs)| txs).find(|tx| tx.signature == signature).cloned())
+    }
+
+    fn block_hash(&mut self, slot: u64) -> Result<Option<String>> {
+        Ok(self.blocks.get(&slot).map(|(hash, _)| hash.clone()))
+    }
+}
+
+fn count(store: &Store, sql: &str) -> i64 {
+    store.connection().query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[tokio::test]
+async fn indexes_events_from_program_logs() {
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+    let user = keypair_from_seed(&[4; 32]).unwrap();
+    let mut program_test = ProgramTest::new("grid_vault", grid_vault::ID, processor!(process_instruction));
+    program_test.prefer_bpf(false);
+    for signer in [&admin, &user] {
+        program_test.add_account(signer.pubkey(), Account::new(10_000_000_000, 0, &system_program::ID));
+    }
+    let mut ctx = program_test.start_with_context().await;
+    route_data_logs();
+
+    let mint = Keypair::new();
+    let token_account = Keypair::new();
+    let base = BaseMint::spl(mint.pubkey());
+    let setup = [
+        system_instruction::create_account(
+            &admin.pubkey(),
+            &mint.pubkey(),
+            Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), None, 6).unwrap(),
+        system_instruction::create_account(
+            &admin.pubkey(),
+            &token_account.pubkey(),
+            Rent::default().minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_account3(&spl_token::ID, &token_account.pubkey(), &mint.pubkey(), &user.pubkey())
+            .unwrap(),
+        spl_token::instruction::mint_to(&spl_token::ID, &mint.pubkey(), &token_account.pubkey(), &admin.pubkey(), &[], 10_000)
+            .unwrap(),
+        instructions::initialize_protocol(&admin.pubkey(), &base, admin.pubkey(), admin.pubkey()),
+    ];
+    execute(&mut ctx, &setup, &[&admin, &mint, &token_account]).await;
+
+    let deposit = [
+        instructions::create_user_position(&user.pubkey(), vec![]),
+        instructions::deposit(&base, &user.pubkey(), &token_account.pubkey(), 6_000, 0, vec![]),
+    ];
+    let deposit = execute(&mut ctx, &deposit, &[&user]).await;
+    let withdraw = [instructions::withdraw(&base, &user.pubkey(), &token_account.pubkey(), 2_500, u64::MAX)];
+    let withdraw = execute(&mut ctx, &withdraw, &[&user]).await;
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert_eq!(store.apply_slot(&PROGRAM_ID, 10, "a", &[deposit]).unwrap(), 1);
+    assert_eq!(store.apply_slot(&PROGRAM_ID, 11, "b", &[withdraw]).unwrap(), 1);
+
+    let history: Vec<(String, String, i64, i64)> = store
+        .connection()
+        .prepare("SELECT user, kind, amount, shares_delta FROM user_history ORDER BY slot")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    let owner = user.pubkey().to_string();
+    assert_eq!(history, vec![(owner.clone(), "deposit".into(), 6_000, 6_000), (owner, "withdraw".into(), 2_500, -2_500)]);
+
+    // Event-derived NAV agrees with the account
+    let config = ctx.banks_client.get_account(grid_vault_client::pda::protocol_config()).await.unwrap().unwrap();
+    let config = decode_protocol_config(&config.data).unwrap();
+    let nav = store.nav_state().unwrap();
+    assert_eq!(nav.total_shares, config.total_shares);
+    assert_eq!(nav.treasury_balance, 3_500);
+    assert_eq!(count(&store, "SELECT COUNT(*) FROM nav_history"), 2);
+}
+
+#[test]
+fn attributes_logged_data_to_the_emitting_program() {
+    let other = Pubkey::new_unique();
+    let event = deposit_event(Pubkey::new_unique(), 5, 5, 0);
+    let logs = vec![
+        format!("Program {other} invoke [1]"),
+        "Program data: b3RoZXI=".to_string(),
+        format!("Program {PROGRAM_ID} invoke [2]"),
+        "Program log: invoke looks like a frame but is not".to_string(),
+        event.clone(),
+        format!("Program {PROGRAM_ID} consumed 100 of 200000 compute units"),
+        format!("Program {PROGRAM_ID} success"),
+        "Program data: b3RoZXI=".to_string(),
+        format!("Program {other} success"),
+    ];
+    let events = event_data_from_logs(&PROGRAM_ID, &logs);
+    assert_eq!(events.len(), 1);
+    assert!(matches!(VaultEvent::decode(&events[0]), Some(VaultEvent::DepositEvent(e)) if e.amount == 5));
+}
+
+#[test]
+fn indexes_cpi_events_from_inner_instructions() {
+    let fee = FeeCollectedEvent { user: Pubkey::new_unique(), fee: 7, shares_reduced: 3, timestamp: 100 };
+    let mut tx = transaction("sig", 5, &[]);
+    tx.inner_instructions = vec![
+        (PROGRAM_ID, [&EVENT_IX_TAG_LE[..], &fee.data()].concat()),
+        (Pubkey::new_unique(), [&EVENT_IX_TAG_LE[..], &fee.data()].concat()),
+    ];
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert_eq!(store.apply_slot(&PROGRAM_ID, 5, "h5", &[tx]).unwrap(), 1);
+    assert_eq!(count(&store, "SELECT fee FROM user_history WHERE kind = 'fee'"), 7);
+    assert_eq!(store.nav_state().unwrap().accumulated_fees, 7);
+}
+
+#[test]
+fn builds_nav_history_across_deploy_and_valuation() {
+    let user = Pubkey::new_unique();
+    let mut source = MemorySource::default();
+    source.blocks.insert(1, ("h1".into(), vec![transaction("d", 1, &[deposit_event(user, 1_000, 1_000, 10)])]));
+    let deploy = log_event(&CapitalDeployedEvent { amount: 900, total_deployed: 900, treasury_remaining: 100, timestamp: 20 });
+    let valuation = log_event(&ValuationUpdateEvent {
+        total_deployed_original: 900,
+        total_deployed_current: 1_100,
+        orca_value: 600,
+        drift_value: 500,
+        uncollected_fees: 0,
+        unrealized_pnl: 200,
+        pending_fees: 50,
+        timestamp: 30,
+    });
+    source.blocks.insert(2, ("h2".into(), vec![transaction("v", 2, &[deploy, valuation])]));
+
+    let mut indexer = Indexer::new(source, Store::open_in_memory().unwrap(), PROGRAM_ID, DEFAULT_REORG_DEPTH);
+    let report = indexer.sync().unwrap();
+    assert_eq!((report.slots, report.events, report.last_slot), (2, 3, Some(2)));
+
+    let pools: Vec<(u64, f64)> = indexer
+        .store
+        .connection()
+        .prepare("SELECT user_value_pool, share_price FROM nav_history ORDER BY slot, event_index")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    // 100 in the treasury + 1_100 marked value - 50 pending fees
+    assert_eq!(pools, vec![(1_000, 1.0), (1_000, 1.0), (1_150, 1.15)]);
+}
+
+#[test]
+fn rolls_back_orphaned_slots_after_fork() {
+    let user = Pubkey::new_unique();
+    let mut source = MemorySource::default();
+    for slot in 1..=3 {
+        let tx = transaction(&format!("s{slot}"), slot, &[deposit_event(user, 100, 100 * slot, slot as i64)]);
+        source.blocks.insert(slot, (format!("h{slot}"), vec![tx]));
+    }
+    let mut indexer = Indexer::new(source, Store::open_in_memory().unwrap(), PROGRAM_ID, DEFAULT_REORG_DEPTH);
+    indexer.sync().unwrap();
+    assert_eq!(indexer.store.nav_state().unwrap().total_shares, 300);
+
+    // Slot 3 is orphaned; the fork lands a different deposit in slot 4
+    indexer.source.blocks.remove(&3);
+    let tx = transaction("s4", 4, &[deposit_event(user, 50, 250, 4)]);
+    indexer.source.blocks.insert(4, ("h4".into(), vec![tx]));
+
+    let report = indexer.sync().unwrap();
+    assert_eq!(report.rolled_back_to, Some(2));
+    assert_eq!(report.last_slot, Some(4));
+    assert_eq!(count(&indexer.store, "SELECT COUNT(*) FROM events WHERE signature = 's3'"), 0);
+    assert_eq!(count(&indexer.store, "SELECT COUNT(*) FROM checkpoints"), 3);
+    let nav = indexer.store.nav_state().unwrap();
+    assert_eq!((nav.total_shares, nav.treasury_balance), (250, 250));
+
+    // A changed block hash is a fork too
+    indexer.source.blocks.get_mut(&4).unwrap().0 = "h4'".into();
+    assert_eq!(indexer.sync().unwrap().rolled_back_to, Some(3));
+    assert_eq!(indexer.store.last_checkpoint().unwrap(), Some((4, "h4'".into())));
+}
+
+#[test]
+fn replays_from_slot_without_duplicates() {
+    let user = Pubkey::new_unique();
+    let mut source = MemorySource::default();
+    for slot in 1..=4 {
+        let tx = transaction(&format!("s{slot}"), slot, &[deposit_event(user, 10, 10 * slot, slot as i64)]);
+        source.blocks.insert(slot, (format!("h{slot}"), vec![tx]));
+    }
+    let mut indexer = Indexer::new(source, Store::open_in_memory().unwrap(), PROGRAM_ID, DEFAULT_REORG_DEPTH);
+    indexer.sync().unwrap();
+
+    indexer.store.replay_from(3).unwrap();
+    assert_eq!(indexer.store.cursor().unwrap(), 2);
+    let report = indexer.sync().unwrap();
+    assert_eq!(report.slots, 2);
+    assert_eq!(count(&indexer.store, "SELECT COUNT(*) FROM events"), 4);
+    assert_eq!(count(&indexer.store, "SELECT SUM(shares_delta) FROM user_history"), 40);
+
+    // Replay on an empty database starts at the requested slot
+    let mut fresh = Indexer::new(indexer.source, Store::open_in_memory().unwrap(), PROGRAM_ID, DEFAULT_REORG_DEPTH);
+    fresh.store.replay_from(4).unwrap();
+    assert_eq!(fresh.sync().unwrap().slots, 1);
+    assert_eq!(count(&fresh.store, "SELECT MIN(slot) FROM events"), 4);
+}
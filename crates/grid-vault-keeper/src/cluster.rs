@@ -0,0 +1,105 @@
+//! Chain access used by the keeper, with an RPC-backed implementation.
+
+use anchor_lang::Discriminator;
+use anyhow::{anyhow, Context, Result};
+use grid_vault_client::{
+    grid_vault::{ProtocolConfig, UserPosition},
+    pda,
+    state::{decode_protocol_config, PositionScan},
+    PROGRAM_ID,
+};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    signature::Signature,
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+/// Result of simulating a transaction that reached the runtime
+#[derive(Debug)]
+pub struct Simulation {
+    pub err: Option<TransactionError>,
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+/// What the keeper needs from a cluster. Transport failures are `Err`;
+/// transactions the runtime rejects come back as `Simulation::err`.
+pub trait Cluster {
+    fn config(&mut self) -> Result<ProtocolConfig>;
+    /// Every `UserPosition` account owned by the program, in any layout version
+    fn positions(&mut self) -> Result<PositionScan>;
+    /// Cluster time as the program sees it
+    fn now(&mut self) -> Result<i64>;
+    fn latest_blockhash(&mut self) -> Result<Hash>;
+    fn simulate(&mut self, tx: &Transaction) -> Result<Simulation>;
+    fn send(&mut self, tx: &Transaction) -> Result<Signature>;
+}
+
+/// Filters selecting `UserPosition` accounts server-side. There's no size filter, so
+/// positions still in an older layout are found too.
+pub fn position_filters() -> Vec<RpcFilterType> {
+    vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, UserPosition::DISCRIMINATOR.to_vec()))]
+}
+
+pub struct RpcCluster {
+    client: RpcClient,
+}
+
+impl RpcCluster {
+    pub fn new(url: &str) -> Self {
+        RpcCluster { client: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()) }
+    }
+}
+
+impl Cluster for RpcCluster {
+    fn config(&mut self) -> Result<ProtocolConfig> {
+        let account = self.client.get_account(&pda::protocol_config()).context("protocol config not found")?;
+        Ok(decode_protocol_config(&account.data)?)
+    }
+
+    fn positions(&mut self) -> Result<PositionScan> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(position_filters()),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let accounts = self.client.get_program_accounts_with_config(&PROGRAM_ID, config)?;
+        Ok(PositionScan::from_accounts(accounts.into_iter().map(|(address, account)| (address, account.data)))?)
+    }
+
+    fn now(&mut self) -> Result<i64> {
+        let account = self.client.get_account(&sysvar::clock::ID)?;
+        let clock: Clock = from_account(&account).ok_or_else(|| anyhow!("malformed clock sysvar"))?;
+        Ok(clock.unix_timestamp)
+    }
+
+    fn latest_blockhash(&mut self) -> Result<Hash> {
+        Ok(self.client.get_latest_blockhash()?)
+    }
+
+    fn simulate(&mut self, tx: &Transaction) -> Result<Simulation> {
+        let result = self.client.simulate_transaction(tx)?.value;
+        Ok(Simulation {
+            err: result.err,
+            units_consumed: result.units_consumed,
+            logs: result.logs.unwrap_or_default(),
+        })
+    }
+
+    fn send(&mut self, tx: &Transaction) -> Result<Signature> {
+        Ok(self.client.send_and_confirm_transaction(tx)?)
+    }
+}
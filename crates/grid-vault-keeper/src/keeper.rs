@@ -0,0 +1,183 @@
+//! One keeper pass: scan, pack, submit with retries.
+
+use std::{
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use grid_vault_client::{grid_vault::PAUSE_FEE_COLLECTION, nav, state::PositionScan};
+use serde::Serialize;
+use solana_sdk::{
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+use crate::{cluster::Cluster, metrics::Metrics, packer::BatchLimits};
+
+#[derive(Clone, Debug)]
+pub struct KeeperSettings {
+    pub limits: BatchLimits,
+    /// Attempts after the first for transport and confirmation failures
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for each later one
+    pub retry_delay: Duration,
+}
+
+impl Default for KeeperSettings {
+    fn default() -> Self {
+        KeeperSettings {
+            limits: BatchLimits::default(),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    pub timestamp: i64,
+    /// Set when the pass did nothing because fee collection is unavailable
+    pub skipped: Option<&'static str>,
+    pub positions_scanned: usize,
+    /// Positions in an older layout that must be migrated before fees can be collected
+    pub positions_unmigrated: usize,
+    pub positions_eligible: usize,
+    pub positions_collected: usize,
+    pub positions_failed: usize,
+    pub fees_collected: u64,
+    pub signatures: Vec<String>,
+}
+
+enum BatchError {
+    /// Deterministic runtime rejection; retrying the same batch cannot succeed
+    Rejected { err: TransactionError, logs: Vec<String> },
+    Transient(anyhow::Error),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Rejected { err, logs } => write!(f, "rejected: {err}\n{}", logs.join("\n")),
+            BatchError::Transient(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+pub struct Keeper<C: Cluster> {
+    pub cluster: C,
+    signer: Keypair,
+    settings: KeeperSettings,
+    metrics: Arc<Metrics>,
+}
+
+impl<C: Cluster> Keeper<C> {
+    /// `signer` must be the vault admin or trading bot
+    pub fn new(cluster: C, signer: Keypair, settings: KeeperSettings, metrics: Arc<Metrics>) -> Self {
+        Keeper { cluster, signer, settings, metrics }
+    }
+
+    /// Collects fees from every position past `FEE_COLLECTION_INTERVAL`
+    pub fn run_once(&mut self) -> Result<RunSummary> {
+        let started = Instant::now();
+        Metrics::add(&self.metrics.runs, 1);
+
+        let config = self.cluster.config()?;
+        let now = self.cluster.now()?;
+        let mut summary = RunSummary { timestamp: now, ..RunSummary::default() };
+        Metrics::set(&self.metrics.last_run_timestamp, now.max(0) as u64);
+        if config.is_paused(PAUSE_FEE_COLLECTION) || config.emergency_mode {
+            summary.skipped = Some(if config.emergency_mode { "emergency mode" } else { "fee collection paused" });
+            Metrics::add(&self.metrics.runs_skipped, 1);
+            return Ok(summary);
+        }
+
+        let PositionScan { positions, unmigrated } = self.cluster.positions()?;
+        if !unmigrated.is_empty() {
+            eprintln!("skipping {} unmigrated position(s): {unmigrated:?}", unmigrated.len());
+        }
+        summary.positions_unmigrated = unmigrated.len();
+        Metrics::add(&self.metrics.positions_unmigrated, unmigrated.len() as u64);
+
+        // Empty positions only get their timestamp bumped, which is not worth a transaction
+        let eligible: Vec<Pubkey> = positions
+            .iter()
+            .filter(|(_, position)| position.user_shares > 0 && nav::fee_eligible(position, now))
+            .map(|(_, position)| position.owner)
+            .collect();
+        summary.positions_scanned = positions.len();
+        summary.positions_eligible = eligible.len();
+        Metrics::add(&self.metrics.positions_scanned, positions.len() as u64);
+        Metrics::add(&self.metrics.positions_eligible, eligible.len() as u64);
+
+        let per_batch = self.settings.limits.positions_per_batch(&self.signer.pubkey());
+        for batch in eligible.chunks(per_batch) {
+            self.process_batch(batch, &mut summary);
+        }
+
+        if !summary.signatures.is_empty() {
+            let after = self.cluster.config()?;
+            summary.fees_collected = after.accumulated_fees.saturating_sub(config.accumulated_fees);
+            Metrics::add(&self.metrics.fees_collected, summary.fees_collected);
+        }
+        Metrics::set(&self.metrics.last_run_duration_ms, started.elapsed().as_millis() as u64);
+        Ok(summary)
+    }
+
+    /// Sends `owners` as one batch; a rejected batch is halved until the bad position is isolated
+    fn process_batch(&mut self, owners: &[Pubkey], summary: &mut RunSummary) {
+        match self.submit_with_retry(owners) {
+            Ok(signature) => {
+                summary.positions_collected += owners.len();
+                summary.signatures.push(signature.to_string());
+                Metrics::add(&self.metrics.batches_sent, 1);
+                Metrics::add(&self.metrics.positions_collected, owners.len() as u64);
+            }
+            Err(BatchError::Rejected { .. }) if owners.len() > 1 => {
+                Metrics::add(&self.metrics.batch_splits, 1);
+                let (left, right) = owners.split_at(owners.len() / 2);
+                self.process_batch(left, summary);
+                self.process_batch(right, summary);
+            }
+            Err(err) => {
+                eprintln!("batch of {} position(s) failed: {err}", owners.len());
+                summary.positions_failed += owners.len();
+                Metrics::add(&self.metrics.batches_failed, 1);
+                Metrics::add(&self.metrics.positions_failed, owners.len() as u64);
+            }
+        }
+    }
+
+    fn submit_with_retry(&mut self, owners: &[Pubkey]) -> std::result::Result<Signature, BatchError> {
+        let mut delay = self.settings.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.submit(owners) {
+                Err(BatchError::Transient(err)) if attempt < self.settings.max_retries => {
+                    attempt += 1;
+                    Metrics::add(&self.metrics.retries, 1);
+                    eprintln!("retrying batch ({attempt}/{}): {err:#}", self.settings.max_retries);
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Simulates first so runtime rejections are told apart from transport failures
+    fn submit(&mut self, owners: &[Pubkey]) -> std::result::Result<Signature, BatchError> {
+        let ixs = self.settings.limits.batch_instructions(&self.signer.pubkey(), owners);
+        let blockhash = self.cluster.latest_blockhash().map_err(BatchError::Transient)?;
+        let tx = Transaction::new_signed_with_payer(&ixs, Some(&self.signer.pubkey()), &[&self.signer], blockhash);
+
+        let simulation = self.cluster.simulate(&tx).map_err(BatchError::Transient)?;
+        if let Some(err) = simulation.err {
+            return Err(BatchError::Rejected { err, logs: simulation.logs });
+        }
+        self.cluster.send(&tx).map_err(BatchError::Transient)
+    }
+}
@@ -0,0 +1,17 @@
+//! Keeper that runs `collect_batch_fees` over every position past the fee interval.
+//!
+//! - [`cluster`]: chain access, RPC-backed in production
+//! - [`packer`]: batch sizing against packet, account-lock and compute limits
+//! - [`keeper`]: scan, pack and submit with retries
+//! - [`metrics`]: Prometheus counters
+
+pub mod cluster;
+pub mod keeper;
+pub mod metrics;
+pub mod packer;
+
+pub use cluster::{Cluster, RpcCluster, Simulation};
+pub use grid_vault_client::state::PositionScan;
+pub use keeper::{Keeper, KeeperSettings, RunSummary};
+pub use metrics::Metrics;
+pub use packer::BatchLimits;
@@ -0,0 +1,80 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use grid_vault_keeper::{metrics, BatchLimits, Keeper, KeeperSettings, Metrics, RpcCluster};
+use solana_sdk::signature::read_keypair_file;
+
+#[derive(Parser)]
+#[command(name = "grid-vault-keeper", version, about = "Collect performance fees from every eligible position")]
+struct Args {
+    /// RPC endpoint; defaults to a local validator
+    #[arg(long, short = 'u', env = "GRID_VAULT_RPC_URL", default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Admin or trading bot keypair; pays for the transactions
+    #[arg(long, short = 'k', env = "GRID_VAULT_KEYPAIR")]
+    keypair: String,
+
+    /// Seconds between passes
+    #[arg(long, default_value_t = 3600)]
+    interval: u64,
+
+    /// Run a single pass and exit
+    #[arg(long)]
+    once: bool,
+
+    /// Address for the Prometheus endpoint, e.g. 0.0.0.0:9464
+    #[arg(long, env = "GRID_VAULT_METRICS_ADDR")]
+    metrics_addr: Option<String>,
+
+    /// Upper bound on positions per transaction
+    #[arg(long, default_value_t = BatchLimits::default().max_positions)]
+    max_batch: usize,
+
+    /// Compute budgeted per position
+    #[arg(long, default_value_t = BatchLimits::default().compute_units_per_position)]
+    compute_units_per_position: u32,
+
+    /// Priority fee in micro-lamports per compute unit
+    #[arg(long)]
+    compute_unit_price: Option<u64>,
+
+    #[arg(long, default_value_t = KeeperSettings::default().max_retries)]
+    max_retries: u32,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let signer = read_keypair_file(&args.keypair).map_err(|err| anyhow!("failed to read {}: {err}", args.keypair))?;
+
+    let metrics = Arc::new(Metrics::default());
+    if let Some(addr) = &args.metrics_addr {
+        metrics::serve(addr, metrics.clone())?;
+    }
+
+    let settings = KeeperSettings {
+        limits: BatchLimits {
+            max_positions: args.max_batch,
+            compute_units_per_position: args.compute_units_per_position,
+            compute_unit_price: args.compute_unit_price,
+            ..BatchLimits::default()
+        },
+        max_retries: args.max_retries,
+        ..KeeperSettings::default()
+    };
+    let mut keeper = Keeper::new(RpcCluster::new(&args.url), signer, settings, metrics);
+
+    loop {
+        match keeper.run_once() {
+            Ok(summary) => println!("{}", serde_json::to_string(&summary)?),
+            // A failed scan is retried on the next pass
+            Err(err) if !args.once => eprintln!("pass failed: {err:#}"),
+            Err(err) => return Err(err),
+        }
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
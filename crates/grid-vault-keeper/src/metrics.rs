@@ -0,0 +1,84 @@
+//! Keeper counters, exported in the Prometheus text format.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{TcpListener, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+#[derive(Default)]
+pub struct Metrics {
+    pub runs: AtomicU64,
+    pub runs_skipped: AtomicU64,
+    pub positions_scanned: AtomicU64,
+    pub positions_unmigrated: AtomicU64,
+    pub positions_eligible: AtomicU64,
+    pub positions_collected: AtomicU64,
+    pub positions_failed: AtomicU64,
+    pub batches_sent: AtomicU64,
+    pub batches_failed: AtomicU64,
+    pub batch_splits: AtomicU64,
+    pub retries: AtomicU64,
+    pub fees_collected: AtomicU64,
+    pub last_run_timestamp: AtomicU64,
+    pub last_run_duration_ms: AtomicU64,
+}
+
+impl Metrics {
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn set(gauge: &AtomicU64, value: u64) {
+        gauge.store(value, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let series = [
+            ("runs_total", "counter", "Keeper passes started", &self.runs),
+            ("runs_skipped_total", "counter", "Passes skipped while fee collection is paused", &self.runs_skipped),
+            ("positions_scanned_total", "counter", "Positions returned by the scan", &self.positions_scanned),
+            ("positions_unmigrated_total", "counter", "Positions skipped until migrated", &self.positions_unmigrated),
+            ("positions_eligible_total", "counter", "Positions past the fee interval", &self.positions_eligible),
+            ("positions_collected_total", "counter", "Positions in confirmed batches", &self.positions_collected),
+            ("positions_failed_total", "counter", "Positions whose batch failed for good", &self.positions_failed),
+            ("batches_sent_total", "counter", "Confirmed collect_batch_fees transactions", &self.batches_sent),
+            ("batches_failed_total", "counter", "Batches dropped after retries", &self.batches_failed),
+            ("batch_splits_total", "counter", "Rejected batches split in half", &self.batch_splits),
+            ("retries_total", "counter", "Transaction retries after transient errors", &self.retries),
+            ("fees_collected_total", "counter", "Base-asset fees accrued by keeper passes", &self.fees_collected),
+            ("last_run_timestamp_seconds", "gauge", "Cluster time of the last pass", &self.last_run_timestamp),
+            ("last_run_duration_milliseconds", "gauge", "Wall time of the last pass", &self.last_run_duration_ms),
+        ];
+        let mut out = String::new();
+        for (name, kind, help, value) in series {
+            let _ = writeln!(out, "# HELP grid_vault_keeper_{name} {help}");
+            let _ = writeln!(out, "# TYPE grid_vault_keeper_{name} {kind}");
+            let _ = writeln!(out, "grid_vault_keeper_{name} {}", value.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
+
+/// Serves `metrics` on every request to `addr` from a background thread
+pub fn serve(addr: impl ToSocketAddrs, metrics: Arc<Metrics>) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr)?;
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut stream = stream;
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let body = metrics.render();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+        }
+    }))
+}
@@ -0,0 +1,75 @@
+//! Packs eligible positions into `collect_batch_fees` transactions that fit the
+//! packet size, account-lock and compute limits.
+
+use anchor_lang::prelude::Pubkey;
+use grid_vault_client::instructions;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, packet::PACKET_DATA_SIZE,
+    transaction::Transaction,
+};
+
+/// Highest compute unit limit a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Accounts a transaction may lock
+pub const MAX_TX_ACCOUNT_LOCKS: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BatchLimits {
+    /// Operator cap on positions per transaction
+    pub max_positions: usize,
+    /// Compute for the instruction itself, independent of batch size
+    pub base_compute_units: u32,
+    /// Compute for each position in the batch
+    pub compute_units_per_position: u32,
+    /// Priority fee in micro-lamports per compute unit
+    pub compute_unit_price: Option<u64>,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        BatchLimits {
+            max_positions: MAX_TX_ACCOUNT_LOCKS,
+            base_compute_units: 20_000,
+            compute_units_per_position: 30_000,
+            compute_unit_price: None,
+        }
+    }
+}
+
+impl BatchLimits {
+    /// Compute unit limit requested for a batch of `positions`
+    pub fn compute_unit_limit(&self, positions: usize) -> u32 {
+        let per_position = (positions as u64).saturating_mul(self.compute_units_per_position as u64);
+        let units = (self.base_compute_units as u64).saturating_add(per_position);
+        units.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+
+    /// Instructions for one batch: compute budget first, then the fee collection
+    pub fn batch_instructions(&self, caller: &Pubkey, owners: &[Pubkey]) -> Vec<Instruction> {
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(self.compute_unit_limit(owners.len()))];
+        if let Some(price) = self.compute_unit_price {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        ixs.push(instructions::collect_batch_fees(caller, owners));
+        ixs
+    }
+
+    /// Largest batch that fits every limit; at least one position
+    pub fn positions_per_batch(&self, caller: &Pubkey) -> usize {
+        let by_compute = MAX_COMPUTE_UNIT_LIMIT.saturating_sub(self.base_compute_units)
+            / self.compute_units_per_position.max(1);
+        let mut count = self.max_positions.min(by_compute as usize).max(1);
+        while count > 1 && !self.fits(caller, count) {
+            count -= 1;
+        }
+        count
+    }
+
+    fn fits(&self, caller: &Pubkey, positions: usize) -> bool {
+        // Distinct keys are what matter, so placeholder owners are enough
+        let owners: Vec<Pubkey> = (0..positions).map(|_| Pubkey::new_unique()).collect();
+        let tx = Transaction::new_with_payer(&self.batch_instructions(caller, &owners), Some(caller));
+        let size = bincode::serialized_size(&tx).unwrap_or(u64::MAX);
+        size <= PACKET_DATA_SIZE as u64 && tx.message.account_keys.len() <= MAX_TX_ACCOUNT_LOCKS
+    }
+}
@@ -0,0 +1,388 @@
+use std::{sync::Arc, time::Duration};
+
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::{entrypoint::ProgramResult, instruction::Instruction, program_pack::Pack},
+    Discriminator,
+};
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, Result};
+use grid_vault_client::{
+    grid_vault::{self, ProtocolConfig, UserPosition, FEE_COLLECTION_INTERVAL, PAUSE_FEE_COLLECTION},
+    instructions, pda,
+    state::{decode_protocol_config, decode_user_position},
+    BaseMint,
+};
+use grid_vault_keeper::{
+    cluster::position_filters, BatchLimits, Cluster, Keeper, KeeperSettings, Metrics, PositionScan, RunSummary,
+    Simulation,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    clock::Clock,
+    hash::Hash,
+    packet::PACKET_DATA_SIZE,
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signature, Signer},
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+use tokio::runtime::Runtime;
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entry ties the slice and account lifetimes together
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    grid_vault::entry(program_id, accounts, data)
+}
+
+/// In-process bank standing in for the validator. Banks has no getProgramAccounts, so the
+/// scan walks tracked accounts through the same filters the RPC node would apply.
+struct BankCluster {
+    runtime: Runtime,
+    ctx: ProgramTestContext,
+    base: BaseMint,
+    owners: Vec<Pubkey>,
+    /// Other program accounts the scan visits
+    scanned_accounts: Vec<Pubkey>,
+    /// Sends to fail before the bank is reached
+    failing_sends: u32,
+    /// Reported by the scan but never created on-chain
+    phantom_owners: Vec<Pubkey>,
+}
+
+impl Cluster for BankCluster {
+    fn config(&mut self) -> Result<ProtocolConfig> {
+        let account = self.runtime.block_on(self.ctx.banks_client.get_account(pda::protocol_config()))?;
+        Ok(decode_protocol_config(&account.ok_or_else(|| anyhow!("no config"))?.data)?)
+    }
+
+    fn positions(&mut self) -> Result<PositionScan> {
+        let addresses: Vec<Pubkey> =
+            self.owners.iter().map(pda::user_position).chain(self.scanned_accounts.iter().copied()).collect();
+        let mut accounts = Vec::new();
+        for address in addresses {
+            let account = self.runtime.block_on(self.ctx.banks_client.get_account(address))?;
+            let account = account.ok_or_else(|| anyhow!("no account"))?;
+            if matches_position_filters(&account.data) {
+                accounts.push((address, account.data));
+            }
+        }
+        let mut scan = PositionScan::from_accounts(accounts)?;
+        for owner in &self.phantom_owners {
+            let position = UserPosition { owner: *owner, user_shares: 1, ..bytemuck::Zeroable::zeroed() };
+            scan.positions.push((pda::user_position(owner), position));
+        }
+        Ok(scan)
+    }
+
+    fn now(&mut self) -> Result<i64> {
+        Ok(self.runtime.block_on(self.ctx.banks_client.get_sysvar::<Clock>())?.unix_timestamp)
+    }
+
+    fn latest_blockhash(&mut self) -> Result<Hash> {
+        Ok(self.runtime.block_on(self.ctx.get_new_latest_blockhash())?)
+    }
+
+    fn simulate(&mut self, tx: &Transaction) -> Result<Simulation> {
+        let result = self.runtime.block_on(self.ctx.banks_client.simulate_transaction(tx.clone()))?;
+        let details = result.simulation_details;
+        Ok(Simulation {
+            err: result.result.and_then(|result| result.err()),
+            units_consumed: details.as_ref().map(|details| details.units_consumed),
+            logs: details.map(|details| details.logs).unwrap_or_default(),
+        })
+    }
+
+    fn send(&mut self, tx: &Transaction) -> Result<Signature> {
+        if self.failing_sends > 0 {
+            self.failing_sends -= 1;
+            return Err(anyhow!("connection reset"));
+        }
+        self.runtime.block_on(self.ctx.banks_client.process_transaction(tx.clone()))?;
+        Ok(tx.signatures[0])
+    }
+}
+
+impl BankCluster {
+    fn admin() -> Keypair {
+        keypair_from_seed(&[1; 32]).unwrap()
+    }
+
+    fn trading_bot() -> Keypair {
+        keypair_from_seed(&[2; 32]).unwrap()
+    }
+
+    /// Vault whose `users` each deposited 1_000
+    fn start(users: u8) -> BankCluster {
+        let admin = Self::admin();
+        let users: Vec<Keypair> = (0..users).map(|i| keypair_from_seed(&[100 + i; 32]).unwrap()).collect();
+        let mut program_test = ProgramTest::new("grid_vault", grid_vault::ID, processor!(process_instruction));
+        program_test.prefer_bpf(false);
+        for signer in users.iter().chain([&admin, &Self::trading_bot()]) {
+            program_test.add_account(signer.pubkey(), Account::new(10_000_000_000, 0, &system_program::ID));
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let ctx = runtime.block_on(program_test.start_with_context());
+        let mint = Keypair::new();
+        let base = BaseMint::spl(mint.pubkey());
+        let mut cluster = BankCluster {
+            runtime,
+            ctx,
+            base,
+            owners: vec![],
+            scanned_accounts: vec![],
+            failing_sends: 0,
+            phantom_owners: vec![],
+        };
+
+        let ixs = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &mint.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), None, 6).unwrap(),
+            instructions::initialize_protocol(&admin.pubkey(), &base, admin.pubkey(), Self::trading_bot().pubkey()),
+        ];
+        cluster.execute(&ixs, &[&admin, &mint]);
+
+        for user in &users {
+            cluster.add_depositor(user);
+        }
+        cluster
+    }
+
+    /// Opens a position for a funded `user` and deposits 1_000
+    fn add_depositor(&mut self, user: &Keypair) {
+        let (admin, base) = (Self::admin(), self.base);
+        let token_account = Keypair::new();
+        let ixs = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &token_account.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(&spl_token::ID, &token_account.pubkey(), &base.mint, &user.pubkey())
+                .unwrap(),
+            spl_token::instruction::mint_to(&spl_token::ID, &base.mint, &token_account.pubkey(), &admin.pubkey(), &[], 1_000)
+                .unwrap(),
+        ];
+        self.execute(&ixs, &[&admin, &token_account]);
+        let ixs = [
+            instructions::create_user_position(&user.pubkey(), vec![]),
+            instructions::deposit(&base, &user.pubkey(), &token_account.pubkey(), 1_000, 0, vec![]),
+        ];
+        self.execute(&ixs, &[user]);
+        self.owners.push(user.pubkey());
+    }
+
+    fn execute(&mut self, ixs: &[Instruction], signers: &[&Keypair]) {
+        let blockhash = self.latest_blockhash().unwrap();
+        let tx = Transaction::new_signed_with_payer(ixs, Some(&signers[0].pubkey()), signers, blockhash);
+        self.runtime.block_on(self.ctx.banks_client.process_transaction(tx)).unwrap();
+    }
+
+    /// Moves cluster time past every position's fee interval
+    fn warp_past_fee_interval(&mut self) {
+        let mut clock = self.runtime.block_on(self.ctx.banks_client.get_sysvar::<Clock>()).unwrap();
+        clock.unix_timestamp += FEE_COLLECTION_INTERVAL + 1;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    fn position(&mut self, owner: &Pubkey) -> UserPosition {
+        let account = self.runtime.block_on(self.ctx.banks_client.get_account(pda::user_position(owner)));
+        decode_user_position(&account.unwrap().unwrap().data).unwrap()
+    }
+}
+
+/// Whether a program account passes the keeper's getProgramAccounts filters
+fn matches_position_filters(data: &[u8]) -> bool {
+    let account = AccountSharedData::from(Account { data: data.to_vec(), owner: grid_vault::ID, ..Account::default() });
+    position_filters().iter().all(|filter| filter.allows(&account))
+}
+
+/// Version 0 `UserPosition` bytes, cut down to `len`
+fn position_data(owner: Pubkey, len: usize) -> Vec<u8> {
+    let position = UserPosition { owner, user_shares: 1_000, ..bytemuck::Zeroable::zeroed() };
+    let mut data = UserPosition::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(bytemuck::bytes_of(&position));
+    data.truncate(len);
+    data
+}
+
+fn keeper(cluster: BankCluster, limits: BatchLimits) -> (Keeper<BankCluster>, Arc<Metrics>) {
+    let metrics = Arc::new(Metrics::default());
+    let settings = KeeperSettings { limits, max_retries: 2, retry_delay: Duration::ZERO };
+    (Keeper::new(cluster, BankCluster::trading_bot(), settings, metrics.clone()), metrics)
+}
+
+fn metric(metrics: &Metrics, name: &str) -> u64 {
+    let rendered = metrics.render();
+    let prefix = format!("grid_vault_keeper_{name} ");
+    let line = rendered.lines().find(|line| line.starts_with(&prefix)).unwrap();
+    line[prefix.len()..].parse().unwrap()
+}
+
+#[test]
+fn batches_fit_packet_and_compute_limits() {
+    let caller = Pubkey::new_unique();
+    let limits = BatchLimits::default();
+    let per_batch = limits.positions_per_batch(&caller);
+    assert!(per_batch > 1);
+
+    let size = |count: usize| {
+        let owners: Vec<Pubkey> = (0..count).map(|_| Pubkey::new_unique()).collect();
+        let tx = Transaction::new_with_payer(&limits.batch_instructions(&caller, &owners), Some(&caller));
+        bincode::serialized_size(&tx).unwrap() as usize
+    };
+    assert!(size(per_batch) <= PACKET_DATA_SIZE);
+    assert!(size(per_batch + 1) > PACKET_DATA_SIZE);
+
+    // Compute caps the batch before the packet does
+    let heavy = BatchLimits { compute_units_per_position: 200_000, ..limits };
+    assert_eq!(heavy.positions_per_batch(&caller), 6);
+    assert!(heavy.compute_unit_limit(6) <= 1_400_000);
+}
+
+#[test]
+fn position_filters_match_every_position_layout() {
+    let (current, outdated) = (Pubkey::new_unique(), Pubkey::new_unique());
+    // v0 positions are already full size, so they are collected before migrating
+    let current_data = position_data(current, UserPosition::LEN);
+    // Stands in for a position not yet migrated to a layout that grew the account
+    let outdated_data = position_data(outdated, UserPosition::LEN - 8);
+    assert!(matches_position_filters(&current_data));
+    assert!(matches_position_filters(&outdated_data));
+    let mut config_data = ProtocolConfig::DISCRIMINATOR.to_vec();
+    config_data.resize(ProtocolConfig::LEN, 0);
+    assert!(!matches_position_filters(&config_data));
+
+    let scan = PositionScan::from_accounts([(current, current_data), (outdated, outdated_data)]).unwrap();
+    assert_eq!(scan.positions.len(), 1);
+    assert_eq!((scan.positions[0].0, scan.positions[0].1.owner), (current, current));
+    assert_eq!(scan.unmigrated, vec![outdated]);
+}
+
+#[test]
+fn reports_unmigrated_positions_instead_of_dropping_them() {
+    let mut cluster = BankCluster::start(2);
+    cluster.warp_past_fee_interval();
+    let owner = Pubkey::new_unique();
+    let outdated = pda::user_position(&owner);
+    let data = position_data(owner, UserPosition::LEN - 8);
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: grid_vault::ID,
+        ..Account::default()
+    };
+    cluster.ctx.set_account(&outdated, &account.into());
+    cluster.scanned_accounts.extend([outdated, pda::protocol_config()]);
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits::default());
+    let summary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.positions_scanned, 2);
+    assert_eq!(summary.positions_unmigrated, 1);
+    assert_eq!(summary.positions_collected, 2);
+    assert_eq!(metric(&metrics, "positions_unmigrated_total"), 1);
+}
+
+#[test]
+fn collects_every_eligible_position_in_batches() {
+    let mut cluster = BankCluster::start(12);
+    cluster.warp_past_fee_interval();
+    // Opened after the warp, so still inside its interval
+    let late = keypair_from_seed(&[200; 32]).unwrap();
+    let admin = BankCluster::admin();
+    cluster.execute(&[system_instruction::transfer(&admin.pubkey(), &late.pubkey(), 1_000_000_000)], &[&admin]);
+    cluster.add_depositor(&late);
+    let late_collection = cluster.position(&late.pubkey()).last_fee_collection;
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits { max_positions: 5, ..BatchLimits::default() });
+    let summary: RunSummary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.positions_scanned, 13);
+    assert_eq!(summary.positions_eligible, 12);
+    assert_eq!(summary.positions_collected, 12);
+    assert_eq!(summary.positions_failed, 0);
+    assert_eq!(summary.signatures.len(), 3);
+    let owners = keeper.cluster.owners.clone();
+    for owner in &owners[..12] {
+        assert_eq!(keeper.cluster.position(owner).last_fee_collection, summary.timestamp);
+    }
+    assert_eq!(keeper.cluster.position(&late.pubkey()).last_fee_collection, late_collection);
+    assert_eq!(metric(&metrics, "batches_sent_total"), 3);
+    assert_eq!(metric(&metrics, "positions_collected_total"), 12);
+
+    // Nothing is due on the next pass
+    let summary = keeper.run_once().unwrap();
+    assert_eq!(summary.positions_eligible, 0);
+    assert!(summary.signatures.is_empty());
+}
+
+#[test]
+fn retries_transient_send_failures() {
+    let mut cluster = BankCluster::start(3);
+    cluster.warp_past_fee_interval();
+    cluster.failing_sends = 2;
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits::default());
+    let summary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.positions_collected, 3);
+    assert_eq!(summary.signatures.len(), 1);
+    assert_eq!(metric(&metrics, "retries_total"), 2);
+    assert_eq!(metric(&metrics, "batches_failed_total"), 0);
+}
+
+#[test]
+fn gives_up_after_max_retries() {
+    let mut cluster = BankCluster::start(2);
+    cluster.warp_past_fee_interval();
+    cluster.failing_sends = 3;
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits::default());
+    let summary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.positions_collected, 0);
+    assert_eq!(summary.positions_failed, 2);
+    assert_eq!(metric(&metrics, "retries_total"), 2);
+    assert_eq!(metric(&metrics, "batches_failed_total"), 1);
+}
+
+#[test]
+fn splits_rejected_batch_to_isolate_bad_position() {
+    let mut cluster = BankCluster::start(5);
+    cluster.warp_past_fee_interval();
+    cluster.phantom_owners.push(Pubkey::new_unique());
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits::default());
+    let summary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.positions_eligible, 6);
+    assert_eq!(summary.positions_collected, 5);
+    assert_eq!(summary.positions_failed, 1);
+    assert!(metric(&metrics, "batch_splits_total") >= 1);
+    assert_eq!(metric(&metrics, "retries_total"), 0);
+}
+
+#[test]
+fn skips_pass_while_fee_collection_paused() {
+    let mut cluster = BankCluster::start(2);
+    cluster.warp_past_fee_interval();
+    let admin = BankCluster::admin();
+    cluster.execute(&[instructions::pause_protocol(&admin.pubkey(), PAUSE_FEE_COLLECTION, 1)], &[&admin]);
+
+    let (mut keeper, metrics) = keeper(cluster, BatchLimits::default());
+    let summary = keeper.run_once().unwrap();
+
+    assert_eq!(summary.skipped, Some("fee collection paused"));
+    assert!(summary.signatures.is_empty());
+    assert_eq!(metric(&metrics, "runs_skipped_total"), 1);
+}
@@ -0,0 +1,14 @@
+//! Backtests grid-vault parameters by replaying scripted timelines through
+//! `grid_vault::accounting`, the same code the instructions run.
+//!
+//! - [`scenario`]: timeline steps, read from CSV
+//! - [`simulator`]: vault state and the instruction each step stands for
+//! - [`report`]: per-step vault and per-user rows, written as CSV
+
+pub mod report;
+pub mod scenario;
+pub mod simulator;
+
+pub use report::{Report, UserRow, VaultRow};
+pub use scenario::{Action, Step};
+pub use simulator::{Simulator, EPOCH};
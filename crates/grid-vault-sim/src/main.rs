@@ -0,0 +1,45 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use grid_vault_client::grid_vault::PERFORMANCE_FEE_BPS;
+use grid_vault_sim::{scenario, Simulator};
+
+#[derive(Parser)]
+#[command(name = "grid-vault-sim", version, about = "Replay a grid-vault timeline and report balances and fees as CSV")]
+struct Args {
+    /// Timeline CSV with a `time,action,user,amount,cost` header
+    scenario: PathBuf,
+
+    /// Directory for vault.csv and users.csv
+    #[arg(long, short = 'o', default_value = ".")]
+    out_dir: PathBuf,
+
+    /// Performance fee in basis points
+    #[arg(long, default_value_t = PERFORMANCE_FEE_BPS)]
+    fee_bps: u16,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let file = File::open(&args.scenario).with_context(|| format!("failed to open {}", args.scenario.display()))?;
+    let steps = scenario::read(file).with_context(|| format!("invalid scenario {}", args.scenario.display()))?;
+
+    let report = Simulator::new(args.fee_bps).run(&steps)?;
+
+    std::fs::create_dir_all(&args.out_dir)?;
+    report.write_vault(BufWriter::new(File::create(args.out_dir.join("vault.csv"))?))?;
+    report.write_users(BufWriter::new(File::create(args.out_dir.join("users.csv"))?))?;
+
+    for row in report.rejected() {
+        eprintln!("step {} at {}s: {} rejected with {}", row.step, row.time, row.action, row.outcome);
+    }
+    println!(
+        "{} steps, {} rejected, {} in fees; wrote {}",
+        report.vault.len(),
+        report.rejected().count(),
+        report.fees_earned(),
+        args.out_dir.display()
+    );
+    Ok(())
+}
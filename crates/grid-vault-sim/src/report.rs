@@ -0,0 +1,76 @@
+//! Replay results, one vault row per step and one user row per position per step.
+
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Vault totals after a step
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VaultRow {
+    pub step: usize,
+    pub time: i64,
+    pub action: String,
+    /// `ok`, or the program error the step was rejected with
+    pub outcome: String,
+    pub treasury: u64,
+    pub deployed_value: u64,
+    pub total_value: u64,
+    pub user_value_pool: u64,
+    pub total_shares: u64,
+    /// Treasury as a share of total value
+    pub buffer_bps: u64,
+    /// Whether `check_buffer` has flagged a deficit that still blocks deployment
+    pub buffer_deficit: bool,
+    pub accumulated_fees: u64,
+    pub pending_unrealized_fees: u64,
+    pub fees_swept: u64,
+}
+
+/// One position after a step
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UserRow {
+    pub step: usize,
+    pub time: i64,
+    pub user: String,
+    pub shares: u64,
+    pub balance: u64,
+    pub cost_basis: u64,
+    pub high_water_mark: u64,
+    pub fees_paid: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub vault: Vec<VaultRow>,
+    pub users: Vec<UserRow>,
+}
+
+impl Report {
+    /// Fees the vault has earned: still held plus already swept
+    pub fn fees_earned(&self) -> u64 {
+        self.vault.last().map_or(0, |row| row.accumulated_fees + row.fees_swept)
+    }
+
+    /// Steps the program would have rejected
+    pub fn rejected(&self) -> impl Iterator<Item = &VaultRow> {
+        self.vault.iter().filter(|row| row.outcome != "ok")
+    }
+
+    pub fn write_vault(&self, out: impl Write) -> Result<()> {
+        write_csv(out, &self.vault)
+    }
+
+    pub fn write_users(&self, out: impl Write) -> Result<()> {
+        write_csv(out, &self.users)
+    }
+}
+
+fn write_csv<T: Serialize>(out: impl Write, rows: &[T]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
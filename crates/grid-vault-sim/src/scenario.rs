@@ -0,0 +1,141 @@
+//! Timelines as CSV with a `time,action,user,amount,cost` header.
+//!
+//! `time` is seconds since the start of the scenario and must not go backwards.
+//! Unused columns are left empty:
+//!
+//! | action         | user     | amount                 | cost                              |
+//! |----------------|----------|------------------------|-----------------------------------|
+//! | `deposit`      | required | deposited              |                                   |
+//! | `withdraw`     | required | withdrawn              |                                   |
+//! | `redeem_all`   | required |                        |                                   |
+//! | `deploy`       |          | sent to trading        |                                   |
+//! | `return`       |          | returned from trading  | deployed cost closed; default all |
+//! | `valuation`    |          | marked deployed value  |                                   |
+//! | `check_buffer` |          |                        |                                   |
+//! | `collect_fees` | optional |                        |                                   |
+//! | `sweep_fees`   |          |                        |                                   |
+
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+/// What happens at a step; each maps to one instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Deposit {
+        user: String,
+        amount: u64,
+    },
+    Withdraw {
+        user: String,
+        amount: u64,
+    },
+    RedeemAll {
+        user: String,
+    },
+    Deploy {
+        amount: u64,
+    },
+    /// `cost` of `None` closes out everything deployed
+    Return {
+        amount: u64,
+        cost: Option<u64>,
+    },
+    /// Unrealized PnL is the marked value less the deployed cost
+    Valuation {
+        value: u64,
+    },
+    CheckBuffer,
+    /// One user, or every position past the fee interval as the keeper does
+    CollectFees {
+        user: Option<String>,
+    },
+    SweepFees,
+}
+
+impl Action {
+    /// Name as written in the scenario
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Deposit { .. } => "deposit",
+            Action::Withdraw { .. } => "withdraw",
+            Action::RedeemAll { .. } => "redeem_all",
+            Action::Deploy { .. } => "deploy",
+            Action::Return { .. } => "return",
+            Action::Valuation { .. } => "valuation",
+            Action::CheckBuffer => "check_buffer",
+            Action::CollectFees { .. } => "collect_fees",
+            Action::SweepFees => "sweep_fees",
+        }
+    }
+
+    /// The user the step acts for, if any
+    pub fn user(&self) -> Option<&str> {
+        match self {
+            Action::Deposit { user, .. } | Action::Withdraw { user, .. } | Action::RedeemAll { user } => Some(user),
+            Action::CollectFees { user } => user.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub time: i64,
+    pub action: Action,
+}
+
+#[derive(Deserialize)]
+struct Row {
+    time: i64,
+    action: String,
+    user: Option<String>,
+    amount: Option<u64>,
+    cost: Option<u64>,
+}
+
+impl TryFrom<Row> for Step {
+    type Error = anyhow::Error;
+
+    fn try_from(row: Row) -> Result<Self> {
+        let user = || row.user.clone().filter(|user| !user.is_empty()).ok_or_else(|| anyhow!("missing user"));
+        let amount = || row.amount.ok_or_else(|| anyhow!("missing amount"));
+        let action = match row.action.as_str() {
+            "deposit" => Action::Deposit { user: user()?, amount: amount()? },
+            "withdraw" => Action::Withdraw { user: user()?, amount: amount()? },
+            "redeem_all" => Action::RedeemAll { user: user()? },
+            "deploy" => Action::Deploy { amount: amount()? },
+            "return" => Action::Return { amount: amount()?, cost: row.cost },
+            "valuation" => Action::Valuation { value: amount()? },
+            "check_buffer" => Action::CheckBuffer,
+            "collect_fees" => Action::CollectFees { user: user().ok() },
+            "sweep_fees" => Action::SweepFees,
+            other => bail!("unknown action `{other}`"),
+        };
+        Ok(Step { time: row.time, action })
+    }
+}
+
+/// Reads a timeline, rejecting unknown actions, missing fields and time going backwards
+pub fn read(source: impl Read) -> Result<Vec<Step>> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).comment(Some(b'#')).from_reader(source);
+    let headers = reader.headers()?.clone();
+    let mut record = csv::StringRecord::new();
+    let mut steps: Vec<Step> = Vec::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |position| position.line());
+        let step = record
+            .deserialize::<Row>(Some(&headers))
+            .map_err(anyhow::Error::from)
+            .and_then(Step::try_from)
+            .with_context(|| format!("line {line}"))?;
+        if let Some(last) = steps.last() {
+            if step.time < last.time {
+                bail!("line {line}: time {} is before {}", step.time, last.time);
+            }
+        }
+        steps.push(step);
+    }
+    Ok(steps)
+}
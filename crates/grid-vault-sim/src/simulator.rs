@@ -0,0 +1,258 @@
+//! Vault state as the program holds it, advanced one step at a time.
+//!
+//! Steps run the instruction's checks and state changes through `grid_vault::accounting`;
+//! only account loading, access control and token transfers are left out. Token balances
+//! are tracked as the treasury balance the instruction would see.
+
+use std::collections::BTreeMap;
+
+use anchor_lang::{error::Error, prelude::*};
+use grid_vault_client::grid_vault::{
+    accounting::{self, DepositTarget, Rounding},
+    AccessMode, ProtocolConfig, UserPosition, VaultError, VaultLifecycle, FEE_COLLECTION_INTERVAL, PERFORMANCE_FEE_BPS,
+    PROTOCOL_CONFIG_VERSION,
+};
+
+use crate::{
+    report::{Report, UserRow, VaultRow},
+    scenario::{Action, Step},
+};
+
+/// Unix time the scenario starts at; step times are offsets from here
+pub const EPOCH: i64 = 1_700_000_000;
+
+#[derive(Clone)]
+pub struct Simulator {
+    pub config: ProtocolConfig,
+    pub positions: BTreeMap<String, UserPosition>,
+    pub treasury: u64,
+    /// Fees moved out of the treasury by `sweep_fees`
+    pub fees_swept: u64,
+    pub now: i64,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new(PERFORMANCE_FEE_BPS)
+    }
+}
+
+impl Simulator {
+    /// A freshly initialized vault charging `performance_fee_bps`
+    pub fn new(performance_fee_bps: u16) -> Self {
+        let config = ProtocolConfig {
+            admin: Pubkey::default(),
+            trading_bot: Pubkey::default(),
+            treasury: Pubkey::default(),
+            total_shares: 0,
+            total_trading_deployed: 0,
+            accumulated_fees: 0,
+            performance_fee_bps,
+            pause_flags: 0,
+            bump: 0,
+            last_fee_sweep: 0,
+            deployed_current_value: 0,
+            last_valuation_timestamp: 0,
+            pending_unrealized_fees: 0,
+            version: PROTOCOL_CONFIG_VERSION,
+            guardian: Pubkey::default(),
+            emergency_mode: false,
+            lifecycle: VaultLifecycle::Active,
+            wind_down_started_at: 0,
+            max_total_deposits: 0,
+            max_position_size: 0,
+            min_deposit: 0,
+            access_mode: AccessMode::Open,
+            merkle_root: [0; 32],
+            asset_holdings_value: 0,
+            buffer_deficit_since: 0,
+            asset_prices_updated_at: 0,
+        };
+        Self { config, positions: BTreeMap::new(), treasury: 0, fees_swept: 0, now: EPOCH }
+    }
+
+    /// Runs `step` at its time; a rejected step leaves the vault as it was
+    pub fn step(&mut self, step: &Step) -> Result<()> {
+        let mut next = self.clone();
+        next.now = EPOCH + step.time;
+        next.apply(&step.action)?;
+        *self = next;
+        Ok(())
+    }
+
+    fn apply(&mut self, action: &Action) -> Result<()> {
+        match action {
+            Action::Deposit { user, amount } => self.deposit(user, *amount),
+            Action::Withdraw { user, amount } => {
+                let (pool, _) = accounting::vault_value_pool(&self.config, self.treasury, self.now)?;
+                let shares = accounting::convert_to_shares(&self.config, pool, *amount, Rounding::Up)?;
+                self.withdraw(user, *amount, shares)
+            }
+            Action::RedeemAll { user } => {
+                let shares = self.positions.get(user).map_or(0, |position| position.user_shares);
+                let (pool, _) = accounting::vault_value_pool(&self.config, self.treasury, self.now)?;
+                let amount = accounting::convert_to_assets(&self.config, pool, shares, Rounding::Down)?;
+                self.withdraw(user, amount, shares)
+            }
+            Action::Deploy { amount } => {
+                accounting::apply_deploy(&mut self.config, self.treasury, *amount, self.now)?;
+                self.treasury -= amount;
+                Ok(())
+            }
+            Action::Return { amount, cost } => {
+                let cost = cost.unwrap_or(self.config.total_trading_deployed);
+                accounting::apply_return(&mut self.config, *amount, cost)?;
+                self.treasury = self.treasury.checked_add(*amount).ok_or(VaultError::MathOverflow)?;
+                if self.config.buffer_deficit_since != 0 {
+                    accounting::apply_buffer_check(&mut self.config, self.treasury, self.now)?;
+                }
+                Ok(())
+            }
+            Action::CheckBuffer => {
+                accounting::apply_buffer_check(&mut self.config, self.treasury, self.now)?;
+                Ok(())
+            }
+            Action::Valuation { value } => {
+                let unrealized_pnl: i64 = (*value as i128 - self.config.total_trading_deployed as i128)
+                    .try_into()
+                    .map_err(|_| VaultError::MathOverflow)?;
+                accounting::apply_valuation(&mut self.config, *value, unrealized_pnl, self.now)
+            }
+            Action::CollectFees { user: Some(user) } => {
+                let position = self.positions.get(user).ok_or(ErrorCode::AccountNotInitialized)?;
+                require!(
+                    self.now >= position.last_fee_collection + FEE_COLLECTION_INTERVAL,
+                    VaultError::FeeCollectionTooSoon
+                );
+                self.collect_fees(user)
+            }
+            Action::CollectFees { user: None } => {
+                let eligible: Vec<String> = self
+                    .positions
+                    .iter()
+                    .filter(|(_, position)| self.now >= position.last_fee_collection + FEE_COLLECTION_INTERVAL)
+                    .map(|(user, _)| user.clone())
+                    .collect();
+                eligible.iter().try_for_each(|user| self.collect_fees(user))
+            }
+            Action::SweepFees => {
+                let fees = self.config.accumulated_fees;
+                require!(fees > 0, VaultError::NoFeesToCollect);
+                self.treasury = self.treasury.checked_sub(fees).ok_or(VaultError::InsufficientLiquidity)?;
+                self.fees_swept += fees;
+                self.config.accumulated_fees = 0;
+                self.config.last_fee_sweep = self.now;
+                Ok(())
+            }
+        }
+    }
+
+    fn deposit(&mut self, user: &str, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        let now = self.now;
+        // The first deposit creates the position, which starts the fee interval
+        let position = self
+            .positions
+            .entry(user.to_owned())
+            .or_insert_with(|| UserPosition { last_fee_collection: now, ..bytemuck::Zeroable::zeroed() });
+        let (balance, pool, total) = accounting::user_balance(&self.config, position, self.treasury, now)?;
+        accounting::apply_deposit(
+            &mut self.config,
+            position,
+            balance,
+            pool,
+            total,
+            amount,
+            DepositTarget::MinShares(0),
+        )?;
+        self.treasury = self.treasury.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    fn withdraw(&mut self, user: &str, amount: u64, shares: u64) -> Result<()> {
+        require!(self.treasury >= amount, VaultError::InsufficientLiquidity);
+        let position = self.positions.get_mut(user).ok_or(ErrorCode::AccountNotInitialized)?;
+        accounting::apply_withdraw(&mut self.config, position, amount, shares)?;
+        self.treasury -= amount;
+        Ok(())
+    }
+
+    fn collect_fees(&mut self, user: &str) -> Result<()> {
+        let position = self.positions.get_mut(user).ok_or(ErrorCode::AccountNotInitialized)?;
+        let (balance, pool, _) = accounting::user_balance(&self.config, position, self.treasury, self.now)?;
+        accounting::charge_performance_fee(&mut self.config, position, balance, pool)?;
+        position.last_fee_collection = self.now;
+        Ok(())
+    }
+
+    /// Vault totals at the current time
+    pub fn vault_row(&self, step: usize, time: i64, action: &str, outcome: &str) -> Result<VaultRow> {
+        let (user_value_pool, total_value) = accounting::vault_value_pool(&self.config, self.treasury, self.now)?;
+        let buffer_bps = if total_value == 0 {
+            10000
+        } else {
+            accounting::mul_div(self.treasury, 10000, total_value, Rounding::Down)?
+        };
+        Ok(VaultRow {
+            step,
+            time,
+            action: action.to_owned(),
+            outcome: outcome.to_owned(),
+            treasury: self.treasury,
+            deployed_value: accounting::deployed_value(&self.config, self.now),
+            total_value,
+            user_value_pool,
+            total_shares: self.config.total_shares,
+            buffer_bps,
+            buffer_deficit: self.config.buffer_deficit_since != 0,
+            accumulated_fees: self.config.accumulated_fees,
+            pending_unrealized_fees: self.config.pending_unrealized_fees,
+            fees_swept: self.fees_swept,
+        })
+    }
+
+    /// Every position's balance at the current time
+    pub fn user_rows(&self, step: usize, time: i64) -> Result<Vec<UserRow>> {
+        self.positions
+            .iter()
+            .map(|(user, position)| {
+                let (balance, _, _) = accounting::user_balance(&self.config, position, self.treasury, self.now)?;
+                Ok(UserRow {
+                    step,
+                    time,
+                    user: user.clone(),
+                    shares: position.user_shares,
+                    balance,
+                    cost_basis: position.cost_basis,
+                    high_water_mark: position.high_water_mark,
+                    fees_paid: position.lifetime_fees_paid,
+                })
+            })
+            .collect()
+    }
+
+    /// Replays `steps` in order, recording the vault and every position after each one.
+    /// Rejected steps are recorded with the program error they would fail with.
+    pub fn run(&mut self, steps: &[Step]) -> Result<Report> {
+        let mut report = Report::default();
+        for (index, step) in steps.iter().enumerate() {
+            let outcome = match self.step(step) {
+                Ok(()) => "ok".to_owned(),
+                Err(err) => error_name(&err),
+            };
+            // Rejected steps still move the clock, so staleness shows up in the row
+            self.now = EPOCH + step.time;
+            report.vault.push(self.vault_row(index, step.time, step.action.name(), &outcome)?);
+            report.users.extend(self.user_rows(index, step.time)?);
+        }
+        Ok(report)
+    }
+}
+
+/// Program error name, e.g. `InsufficientLiquidity`
+fn error_name(err: &Error) -> String {
+    match err {
+        Error::AnchorError(err) => err.error_name.clone(),
+        Error::ProgramError(err) => err.program_error.to_string(),
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::File;
+
+use grid_vault_sim::{scenario, Action, Simulator, Step};
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn steps(csv: &str) -> Vec<Step> {
+    scenario::read(csv.as_bytes()).unwrap()
+}
+
+#[test]
+fn replays_the_example_scenario() {
+    let steps = scenario::read(File::open("scenarios/grid-two-months.csv").unwrap()).unwrap();
+    let report = Simulator::default().run(&steps).unwrap();
+    assert_eq!(report.vault.len(), steps.len());
+
+    // Only the withdrawal larger than the buffer is rejected
+    let rejected: Vec<_> = report.rejected().map(|row| (row.action.as_str(), row.outcome.as_str())).collect();
+    assert_eq!(rejected, [("withdraw", "InsufficientLiquidity")]);
+
+    for vault in &report.vault {
+        let users: Vec<_> = report.users.iter().filter(|user| user.step == vault.step).collect();
+        // Every share belongs to a position and claims fit in the pool
+        assert_eq!(users.iter().map(|user| user.shares).sum::<u64>(), vault.total_shares);
+        assert!(users.iter().map(|user| user.balance).sum::<u64>() <= vault.user_value_pool);
+        assert!(vault.accumulated_fees <= vault.treasury);
+    }
+
+    // Trading profit and the monthly collections are all charged; the sweep moved some out
+    let last = report.vault.last().unwrap();
+    assert!(last.fees_swept > 0);
+    assert_eq!(report.fees_earned(), last.accumulated_fees + last.fees_swept);
+    let paid: u64 = report.users.iter().filter(|user| user.step == last.step).map(|user| user.fees_paid).sum();
+    assert!(paid > 0 && paid < report.fees_earned());
+}
+
+#[test]
+fn charges_fees_as_the_program_does() {
+    let report = Simulator::default()
+        .run(&steps(&format!(
+            "time,action,user,amount,cost
+             0,deposit,alice,100000,
+             0,deposit,bob,100000,
+             0,deploy,,180000,
+             {d1},valuation,,200000,
+             {d3},collect_fees,alice,,
+             {d3},return,,240000,
+             {d31},collect_fees,alice,,",
+            d1 = DAY,
+            d3 = 3 * DAY,
+            d31 = 31 * DAY,
+        )))
+        .unwrap();
+
+    // The fresh mark counts and accrues a pending fee on 20_000 unrealized profit
+    assert_eq!(report.vault[3].deployed_value, 200_000);
+    assert_eq!(report.vault[3].pending_unrealized_fees, 5_000);
+    // Two days on it is stale and deployed capital counts at cost
+    assert_eq!(report.vault[4].deployed_value, 180_000);
+    assert_eq!(report.vault[4].outcome, "FeeCollectionTooSoon");
+
+    // 60_000 realized profit accrues 15_000; alice's 22_500 gain costs 5_625, shares rounded up
+    let alice = report.users.iter().rfind(|user| user.user == "alice").unwrap();
+    assert_eq!(report.vault[6].accumulated_fees, 15_000 + 5_625);
+    assert_eq!((alice.shares, alice.fees_paid, alice.high_water_mark), (100_000 - 4_592, 5_625, 116_875));
+}
+
+#[test]
+fn rejected_steps_leave_the_vault_untouched() {
+    let mut simulator = Simulator::default();
+    let report = simulator
+        .run(&steps(
+            "time,action,user,amount,cost
+             0,deposit,alice,1000,
+             0,deploy,,901,
+             0,redeem_all,bob,,
+             0,sweep_fees,,,",
+        ))
+        .unwrap();
+
+    let outcomes: Vec<_> = report.vault.iter().map(|row| row.outcome.as_str()).collect();
+    assert_eq!(outcomes, ["ok", "ExceedsMaxDeployment", "AccountNotInitialized", "NoFeesToCollect"]);
+    assert_eq!((simulator.treasury, simulator.config.total_trading_deployed), (1000, 0));
+    assert_eq!(report.vault[1].buffer_bps, 10000);
+}
+
+#[test]
+fn buffer_deficit_blocks_deployment_until_capital_returns() {
+    let report = Simulator::default()
+        .run(&steps(&format!(
+            "time,action,user,amount,cost
+             0,deposit,alice,100000,
+             0,deploy,,85000,
+             0,deploy,,5001,
+             0,withdraw,alice,6000,
+             0,deploy,,100,
+             {d1},check_buffer,,,
+             {d1},deposit,alice,1000,
+             {d1},deploy,,100,
+             {d1},return,,20000,20000
+             {d1},deploy,,100,",
+            d1 = DAY,
+        )))
+        .unwrap();
+
+    let outcomes: Vec<_> = report.vault.iter().map(|row| row.outcome.as_str()).collect();
+    assert_eq!(
+        outcomes,
+        [
+            "ok",
+            "ok",
+            // 9_999 would be left against a 10_000 target
+            "LiquidityBufferDeficit",
+            "ok",
+            "LiquidityBufferDeficit",
+            "ok",
+            "ok",
+            // Deposits restore the ratio, but the flag holds until a return or the next check
+            "LiquidityBufferDeficit",
+            "ok",
+            "ok",
+        ]
+    );
+    let flagged: Vec<_> = report.vault.iter().map(|row| row.buffer_deficit).collect();
+    assert_eq!(flagged, [false, false, false, false, false, true, true, true, false, false]);
+}
+
+#[test]
+fn reads_actions_and_reports_bad_lines() {
+    let parsed = steps(
+        "time,action,user,amount,cost
+         5,return,,10,
+         5,collect_fees,,,",
+    );
+    assert_eq!(parsed[0].action, Action::Return { amount: 10, cost: None });
+    assert_eq!(parsed[1].action, Action::CollectFees { user: None });
+
+    for (csv, message) in [
+        ("time,action,user,amount,cost\n0,deposit,,5,", "line 2: missing user"),
+        ("time,action,user,amount,cost\n0,mint,alice,5,", "line 2: unknown action `mint`"),
+        ("time,action,user,amount,cost\n9,sweep_fees,,,\n3,sweep_fees,,,", "line 3: time 3 is before 9"),
+    ] {
+        let err = scenario::read(csv.as_bytes()).unwrap_err();
+        assert!(format!("{err:#}").starts_with(message), "{err:#}");
+    }
+}
+
+#[test]
+fn writes_one_csv_row_per_position_per_step() {
+    let report = Simulator::default()
+        .run(&steps(
+            "time,action,user,amount,cost
+             0,deposit,alice,1000,
+             60,deposit,bob,500,",
+        ))
+        .unwrap();
+
+    let mut users = Vec::new();
+    report.write_users(&mut users).unwrap();
+    assert_eq!(
+        String::from_utf8(users).unwrap(),
+        "step,time,user,shares,balance,cost_basis,high_water_mark,fees_paid\n\
+         0,0,alice,1000,1000,1000,1000,0\n\
+         1,60,alice,1000,1000,1000,1000,0\n\
+         1,60,bob,500,500,500,500,0\n"
+    );
+
+    let mut vault = Vec::new();
+    report.write_vault(&mut vault).unwrap();
+    let vault = String::from_utf8(vault).unwrap();
+    assert!(vault.starts_with(
+        "step,time,action,outcome,treasury,deployed_value,total_value,user_value_pool,total_shares,buffer_bps,\
+         buffer_deficit,accumulated_fees,pending_unrealized_fees,fees_swept\n"
+    ));
+    assert_eq!(vault.lines().count(), 3);
+}
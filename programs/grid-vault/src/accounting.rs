@@ -0,0 +1,527 @@
+//! Share accounting without account or sysvar access.
+//!
+//! Instructions load accounts, read the clock and move tokens; every share and
+//! fee calculation they make goes through here, so it can be exercised off-chain.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    PositionSnapshot, ProtocolConfig, SharePriceHistory, UserPosition, VaultError, VaultSnapshot, WindowReturn,
+    FEE_COLLECTION_INTERVAL, LIQUIDITY_BUFFER_BPS, MAX_PRICE_AGE, SECONDS_PER_YEAR, SHARE_PRICE_SCALE, STALE_VALUATION_THRESHOLD,
+    TRADING_ALLOCATION_BPS,
+};
+
+/// Fixed-point scale for compounding growth factors
+const GROWTH_SCALE: u128 = 1_000_000_000_000;
+/// Binary digits of the fractional exponent applied when compounding
+const FRACTION_BITS: u32 = 20;
+
+/// Rounding direction for share/asset conversions; callers pick the one favoring the vault
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// How a deposit is sized against the received amount
+#[derive(Clone, Copy, Debug)]
+pub enum DepositTarget {
+    /// Mint whatever the received amount buys, and at least this many shares
+    MinShares(u64),
+    /// Mint exactly this many shares; the received amount must cover them
+    ExactShares(u64),
+}
+
+/// `value * numerator / denominator` in u128 with explicit rounding
+pub fn mul_div(value: u64, numerator: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    require!(denominator > 0, VaultError::MathOverflow);
+    let product = (value as u128)
+        .checked_mul(numerator as u128)
+        .ok_or(VaultError::MathOverflow)?;
+    let mut result = product / denominator as u128;
+    if rounding == Rounding::Up && product % denominator as u128 != 0 {
+        result += 1;
+    }
+    Ok(u64::try_from(result).map_err(|_| VaultError::MathOverflow)?)
+}
+
+/// Performance fee owed on `profit` at `fee_bps`, rounded down
+pub fn performance_fee(profit: u64, fee_bps: u16) -> Result<u64> {
+    mul_div(profit, fee_bps as u64, 10000, Rounding::Down)
+}
+
+/// Whether the last valuation still counts at `now`
+pub fn valuation_is_fresh(config: &ProtocolConfig, now: i64) -> bool {
+    !config.emergency_mode
+        && config.last_valuation_timestamp > 0
+        && now - config.last_valuation_timestamp <= STALE_VALUATION_THRESHOLD
+}
+
+/// Value of deployed capital at `now`: the marked value while fresh, otherwise its cost
+pub fn deployed_value(config: &ProtocolConfig, now: i64) -> u64 {
+    if config.emergency_mode {
+        // Emergency mode: only what the treasury actually holds counts
+        0
+    } else if valuation_is_fresh(config, now) {
+        config.deployed_current_value
+    } else {
+        // No valuation yet, or it is stale: use original amount
+        config.total_trading_deployed
+    }
+}
+
+/// Value of accepted-asset holdings: the last marked value, or nothing in emergency mode
+/// (holdings are paid out in kind then)
+pub fn asset_holdings_value(config: &ProtocolConfig) -> u64 {
+    if config.emergency_mode {
+        0
+    } else {
+        config.asset_holdings_value
+    }
+}
+
+/// Whether every price behind the asset holdings is fresh enough to issue shares against at `now`
+pub fn asset_prices_fresh(config: &ProtocolConfig, now: i64) -> bool {
+    config.asset_holdings_value == 0 || now - config.asset_prices_updated_at <= MAX_PRICE_AGE
+}
+
+/// Values the vault at `now`: returns (user_value_pool, total_value)
+pub fn vault_value_pool(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Result<(u64, u64)> {
+    let deployed_value = deployed_value(config, now);
+    let asset_value = asset_holdings_value(config);
+
+    let total_value = treasury_balance
+        .checked_add(deployed_value)
+        .ok_or(VaultError::MathOverflow)?
+        .checked_add(asset_value)
+        .ok_or(VaultError::MathOverflow)?;
+    // Subtract both realized and unrealized fees from user value pool
+    let pending_unrealized_fees = if config.emergency_mode { 0 } else { config.pending_unrealized_fees };
+    let total_fees = config.accumulated_fees
+        .checked_add(pending_unrealized_fees)
+        .ok_or(VaultError::MathOverflow)?;
+    let user_value_pool = total_value.checked_sub(total_fees).ok_or(VaultError::MathOverflow)?;
+
+    Ok((user_value_pool, total_value))
+}
+
+/// Converts assets to shares at the current pool (1:1 for an empty vault)
+pub fn convert_to_shares(config: &ProtocolConfig, user_value_pool: u64, assets: u64, rounding: Rounding) -> Result<u64> {
+    if config.total_shares == 0 {
+        return Ok(assets);
+    }
+    mul_div(assets, config.total_shares, user_value_pool, rounding)
+}
+
+/// Converts shares to assets at the current pool (1:1 for an empty vault)
+pub fn convert_to_assets(config: &ProtocolConfig, user_value_pool: u64, shares: u64, rounding: Rounding) -> Result<u64> {
+    if config.total_shares == 0 {
+        return Ok(shares);
+    }
+    mul_div(shares, user_value_pool, config.total_shares, rounding)
+}
+
+/// Position value at `now`: returns (user_balance, user_value_pool, total_value)
+pub fn user_balance(
+    config: &ProtocolConfig,
+    position: &UserPosition,
+    treasury_balance: u64,
+    now: i64,
+) -> Result<(u64, u64, u64)> {
+    let (user_value_pool, total_value) = vault_value_pool(config, treasury_balance, now)?;
+
+    if config.total_shares == 0 {
+        return Ok((0, user_value_pool, total_value));
+    }
+
+    let user_balance = convert_to_assets(config, user_value_pool, position.user_shares, Rounding::Down)?;
+
+    Ok((user_balance, user_value_pool, total_value))
+}
+
+/// Credits a deposit worth `value`, priced at the pre-deposit pool. Enforces the deposit
+/// limits against `user_balance` and `total_value` and returns the shares minted.
+pub fn apply_deposit(
+    config: &mut ProtocolConfig,
+    position: &mut UserPosition,
+    user_balance: u64,
+    user_value_pool: u64,
+    total_value: u64,
+    value: u64,
+    target: DepositTarget,
+) -> Result<u64> {
+    // Enforce deposit limits against the pre-deposit valuation
+    require!(value >= config.min_deposit, VaultError::DepositBelowMinimum);
+    if config.max_total_deposits > 0 {
+        let new_total = total_value.checked_add(value).ok_or(VaultError::MathOverflow)?;
+        require!(new_total <= config.max_total_deposits, VaultError::VaultCapExceeded);
+    }
+    if config.max_position_size > 0 {
+        let new_balance = user_balance.checked_add(value).ok_or(VaultError::MathOverflow)?;
+        require!(new_balance <= config.max_position_size, VaultError::PositionLimitExceeded);
+    }
+
+    let shares_to_mint = match target {
+        DepositTarget::MinShares(min_shares) => {
+            let shares = convert_to_shares(config, user_value_pool, value, Rounding::Down)?;
+            require!(shares >= min_shares, VaultError::SlippageExceeded);
+            shares
+        }
+        DepositTarget::ExactShares(shares) => {
+            let cost = convert_to_assets(config, user_value_pool, shares, Rounding::Up)?;
+            require!(value >= cost, VaultError::SlippageExceeded);
+            shares
+        }
+    };
+    require!(shares_to_mint > 0, VaultError::InvalidAmount);
+
+    position.cost_basis = position.cost_basis
+        .checked_add(value)
+        .ok_or(VaultError::MathOverflow)?;
+    position.user_shares = position.user_shares
+        .checked_add(shares_to_mint)
+        .ok_or(VaultError::MathOverflow)?;
+    // HWM increase by deposited amount (exact value added)
+    position.high_water_mark = position.high_water_mark
+        .checked_add(value)
+        .ok_or(VaultError::MathOverflow)?;
+
+    config.total_shares = config.total_shares
+        .checked_add(shares_to_mint)
+        .ok_or(VaultError::MathOverflow)?;
+
+    Ok(shares_to_mint)
+}
+
+/// Burns `shares_to_burn` for a payout of `amount`. Returns (cost_basis_released, realized_pnl)
+pub fn apply_withdraw(
+    config: &mut ProtocolConfig,
+    position: &mut UserPosition,
+    amount: u64,
+    shares_to_burn: u64,
+) -> Result<(u64, i64)> {
+    require!(amount > 0 && shares_to_burn > 0, VaultError::InvalidAmount);
+    require!(position.user_shares >= shares_to_burn, VaultError::InsufficientBalance);
+
+    let original_shares = position.user_shares;
+    position.user_shares = original_shares - shares_to_burn;
+    // Average-cost basis: release the cost attributable to the burned shares
+    let cost_basis_released = mul_div(position.cost_basis, shares_to_burn, original_shares, Rounding::Down)?;
+    position.cost_basis = position.cost_basis
+        .checked_sub(cost_basis_released)
+        .ok_or(VaultError::MathOverflow)?;
+    let realized_pnl: i64 = (amount as i128 - cost_basis_released as i128)
+        .try_into()
+        .map_err(|_| VaultError::MathOverflow)?;
+    // Proportional HWM reduction
+    position.high_water_mark = mul_div(position.high_water_mark, position.user_shares, original_shares, Rounding::Down)?;
+
+    config.total_shares = config.total_shares
+        .checked_sub(shares_to_burn)
+        .ok_or(VaultError::MathOverflow)?;
+
+    Ok((cost_basis_released, realized_pnl))
+}
+
+/// Charges the performance fee on `current_balance` above the high-water mark.
+/// Returns (fee, shares_reduced); both are zero when there is no new profit.
+pub fn charge_performance_fee(
+    config: &mut ProtocolConfig,
+    position: &mut UserPosition,
+    current_balance: u64,
+    user_value_pool: u64,
+) -> Result<(u64, u64)> {
+    let profit = current_balance.saturating_sub(position.high_water_mark);
+    let fee = performance_fee(profit, config.performance_fee_bps)?;
+    if fee == 0 {
+        return Ok((0, 0));
+    }
+
+    // Round the shares taken up so the fee never dilutes other holders
+    let shares_to_reduce = mul_div(fee, config.total_shares, user_value_pool, Rounding::Up)?
+        .min(position.user_shares);
+
+    position.user_shares -= shares_to_reduce;
+    position.high_water_mark = current_balance - fee;
+    position.lifetime_fees_paid = position.lifetime_fees_paid
+        .checked_add(fee)
+        .ok_or(VaultError::MathOverflow)?;
+
+    config.total_shares = config.total_shares
+        .checked_sub(shares_to_reduce)
+        .ok_or(VaultError::MathOverflow)?;
+    config.accumulated_fees = config.accumulated_fees
+        .checked_add(fee)
+        .ok_or(VaultError::MathOverflow)?;
+
+    Ok((fee, shares_to_reduce))
+}
+
+/// Largest amount the trading bot may deploy out of `treasury_balance`
+pub fn max_deployable(treasury_balance: u64) -> Result<u64> {
+    mul_div(treasury_balance, TRADING_ALLOCATION_BPS as u64, 10000, Rounding::Down)
+}
+
+/// Treasury balance the liquidity buffer calls for at `now`: returns (target, shortfall)
+pub fn buffer_shortfall(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Result<(u64, u64)> {
+    let (_, total_value) = vault_value_pool(config, treasury_balance, now)?;
+    let target = mul_div(total_value, LIQUIDITY_BUFFER_BPS as u64, 10000, Rounding::Up)?;
+    Ok((target, target.saturating_sub(treasury_balance)))
+}
+
+/// Flags a buffer shortfall from `now`, keeping the original start while it lasts, or
+/// clears the flag once the treasury is back at target. Returns (target, shortfall).
+pub fn apply_buffer_check(config: &mut ProtocolConfig, treasury_balance: u64, now: i64) -> Result<(u64, u64)> {
+    let (target, shortfall) = buffer_shortfall(config, treasury_balance, now)?;
+    if shortfall == 0 {
+        config.buffer_deficit_since = 0;
+    } else if config.buffer_deficit_since == 0 {
+        config.buffer_deficit_since = now;
+    }
+    Ok((target, shortfall))
+}
+
+/// Records `amount` leaving a treasury holding `treasury_balance` for trading. Refused
+/// while a buffer deficit is flagged, or if it would take the treasury below target.
+pub fn apply_deploy(config: &mut ProtocolConfig, treasury_balance: u64, amount: u64, now: i64) -> Result<()> {
+    require!(config.buffer_deficit_since == 0, VaultError::LiquidityBufferDeficit);
+    require!(amount <= max_deployable(treasury_balance)?, VaultError::ExceedsMaxDeployment);
+    let (target, _) = buffer_shortfall(config, treasury_balance, now)?;
+    require!(treasury_balance - amount >= target, VaultError::LiquidityBufferDeficit);
+    config.total_trading_deployed = config.total_trading_deployed
+        .checked_add(amount)
+        .ok_or(VaultError::MathOverflow)?;
+    Ok(())
+}
+
+/// Records `returned_amount` coming back for `original_deployed` of cost and accrues the
+/// fee on any profit. Returns (profit_or_loss, fee).
+pub fn apply_return(config: &mut ProtocolConfig, returned_amount: u64, original_deployed: u64) -> Result<(i64, u64)> {
+    require!(original_deployed <= config.total_trading_deployed, VaultError::InvalidAmount);
+
+    let profit_or_loss = if returned_amount > original_deployed {
+        (returned_amount - original_deployed) as i64
+    } else {
+        -((original_deployed - returned_amount) as i64)
+    };
+
+    config.total_trading_deployed = config.total_trading_deployed
+        .checked_sub(original_deployed)
+        .ok_or(VaultError::MathOverflow)?;
+
+    // Clear valuation data if all capital is returned
+    if config.total_trading_deployed == 0 {
+        config.deployed_current_value = 0;
+        config.last_valuation_timestamp = 0;
+        config.pending_unrealized_fees = 0;
+    } else if config.deployed_current_value > 0 {
+        // Proportionally reduce deployed current value
+        let remaining_ratio = ((config.total_trading_deployed as u128)
+            .checked_mul(10000)
+            .ok_or(VaultError::MathOverflow)?
+            .checked_div((config.total_trading_deployed + original_deployed) as u128)
+            .ok_or(VaultError::MathOverflow)?) as u64;
+
+        config.deployed_current_value = ((config.deployed_current_value as u128)
+            .checked_mul(remaining_ratio as u128)
+            .ok_or(VaultError::MathOverflow)?
+            .checked_div(10000)
+            .ok_or(VaultError::MathOverflow)?) as u64;
+    }
+
+    let mut fee = 0;
+    if profit_or_loss > 0 {
+        fee = performance_fee(profit_or_loss as u64, config.performance_fee_bps)?;
+        config.accumulated_fees = config.accumulated_fees
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+        // Reduce pending unrealized fees by the realized portion
+        config.pending_unrealized_fees = config.pending_unrealized_fees.saturating_sub(fee);
+    }
+
+    Ok((profit_or_loss, fee))
+}
+
+/// Marks deployed capital at `current_value` as of `timestamp`; positive `unrealized_pnl`
+/// accrues a pending fee, anything else clears it
+pub fn apply_valuation(
+    config: &mut ProtocolConfig,
+    current_value: u64,
+    unrealized_pnl: i64,
+    timestamp: i64,
+) -> Result<()> {
+    config.deployed_current_value = current_value;
+    config.last_valuation_timestamp = timestamp;
+    config.pending_unrealized_fees = if unrealized_pnl > 0 {
+        performance_fee(unrealized_pnl as u64, config.performance_fee_bps)?
+    } else {
+        // No fees on losses
+        0
+    };
+    Ok(())
+}
+
+/// Assets `SHARE_PRICE_SCALE` shares redeem for out of `user_value_pool`
+pub fn price_per_share(config: &ProtocolConfig, user_value_pool: u64) -> Result<u64> {
+    convert_to_assets(config, user_value_pool, SHARE_PRICE_SCALE, Rounding::Down)
+}
+
+/// Vault state at `now` as `get_vault_snapshot` reports it
+pub fn vault_snapshot(config: &ProtocolConfig, treasury_balance: u64, now: i64) -> Result<VaultSnapshot> {
+    let (user_value_pool, total_value) = vault_value_pool(config, treasury_balance, now)?;
+    let (buffer_target, _) = buffer_shortfall(config, treasury_balance, now)?;
+    Ok(VaultSnapshot {
+        nav: user_value_pool,
+        total_value,
+        total_shares: config.total_shares,
+        price_per_share: price_per_share(config, user_value_pool)?,
+        liquid: treasury_balance,
+        buffer_target,
+        buffer_deficit_since: (config.buffer_deficit_since > 0).then_some(config.buffer_deficit_since),
+        deployed_value: deployed_value(config, now),
+        deployed_cost: config.total_trading_deployed,
+        asset_holdings_value: asset_holdings_value(config),
+        accumulated_fees: config.accumulated_fees,
+        pending_unrealized_fees: config.pending_unrealized_fees,
+        valuation_age: (config.last_valuation_timestamp > 0).then(|| now - config.last_valuation_timestamp),
+        valuation_is_fresh: valuation_is_fresh(config, now),
+        pause_flags: config.pause_flags,
+        emergency_mode: config.emergency_mode,
+        lifecycle: config.lifecycle,
+        timestamp: now,
+    })
+}
+
+/// Position state at `now` as `get_position_snapshot` reports it
+pub fn position_snapshot(
+    config: &ProtocolConfig,
+    position: &UserPosition,
+    treasury_balance: u64,
+    now: i64,
+) -> Result<PositionSnapshot> {
+    let (value, _, _) = user_balance(config, position, treasury_balance, now)?;
+    let unrealized_pnl: i64 = (value as i128 - position.cost_basis as i128)
+        .try_into()
+        .map_err(|_| VaultError::MathOverflow)?;
+    let next_fee_collection = position.last_fee_collection
+        .checked_add(FEE_COLLECTION_INTERVAL)
+        .ok_or(VaultError::MathOverflow)?;
+    let fee_due = if now >= next_fee_collection {
+        performance_fee(value.saturating_sub(position.high_water_mark), config.performance_fee_bps)?
+    } else {
+        0
+    };
+
+    Ok(PositionSnapshot {
+        owner: position.owner,
+        shares: position.user_shares,
+        value,
+        high_water_mark: position.high_water_mark,
+        cost_basis: position.cost_basis,
+        unrealized_pnl,
+        fee_due,
+        next_fee_collection,
+        lifetime_fees_paid: position.lifetime_fees_paid,
+        timestamp: now,
+    })
+}
+
+/// Return between the latest snapshot and the newest one at least `window` seconds older.
+/// `None` while the history does not reach back that far.
+pub fn window_return(history: &SharePriceHistory, window: i64) -> Result<Option<WindowReturn>> {
+    require!(window > 0, VaultError::InvalidReturnWindow);
+    let Some(end) = history.latest() else {
+        return Ok(None);
+    };
+    let Some(start) = history
+        .newest_first()
+        .find(|entry| end.timestamp - entry.timestamp >= window)
+    else {
+        return Ok(None);
+    };
+    if start.price_per_share == 0 {
+        return Ok(None);
+    }
+
+    let period_return_bps = i64::try_from(
+        (end.price_per_share as i128 - start.price_per_share as i128) * 10000 / start.price_per_share as i128,
+    )
+    .map_err(|_| VaultError::MathOverflow)?;
+    let apy_bps = annualized_return_bps(start.price_per_share, end.price_per_share, end.timestamp - start.timestamp)?;
+
+    Ok(Some(WindowReturn {
+        window,
+        start_timestamp: start.timestamp,
+        end_timestamp: end.timestamp,
+        start_price: start.price_per_share,
+        end_price: end.price_per_share,
+        period_return_bps,
+        apy_bps,
+    }))
+}
+
+/// Growth from `start_price` to `end_price` over `elapsed` seconds, compounded to a year, in bps.
+/// Saturates at `i64::MAX` when the annualized figure does not fit.
+pub fn annualized_return_bps(start_price: u64, end_price: u64, elapsed: i64) -> Result<i64> {
+    require!(start_price > 0 && elapsed > 0, VaultError::InvalidReturnWindow);
+    let growth = (end_price as u128)
+        .checked_mul(GROWTH_SCALE)
+        .ok_or(VaultError::MathOverflow)?
+        / start_price as u128;
+
+    let Some(annual) = compound(growth, SECONDS_PER_YEAR as u128, elapsed as u128) else {
+        return Ok(i64::MAX);
+    };
+    let bps = (annual as i128 - GROWTH_SCALE as i128) * 10000 / GROWTH_SCALE as i128;
+    Ok(i64::try_from(bps).unwrap_or(i64::MAX))
+}
+
+/// `growth^(numerator / denominator)` at `GROWTH_SCALE`: whole powers by squaring, the
+/// fractional part by repeated square roots. `None` on overflow.
+fn compound(growth: u128, numerator: u128, denominator: u128) -> Option<u128> {
+    let scaled_mul = |a: u128, b: u128| a.checked_mul(b).map(|product| product / GROWTH_SCALE);
+
+    let mut result = GROWTH_SCALE;
+    let mut base = growth;
+    let mut exponent = numerator / denominator;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = scaled_mul(result, base)?;
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            base = scaled_mul(base, base)?;
+        }
+    }
+
+    let mut remainder = numerator % denominator;
+    let mut root = growth;
+    for _ in 0..FRACTION_BITS {
+        if remainder == 0 {
+            break;
+        }
+        root = isqrt(root.checked_mul(GROWTH_SCALE)?);
+        remainder *= 2;
+        if remainder >= denominator {
+            remainder -= denominator;
+            result = scaled_mul(result, root)?;
+        }
+    }
+    Some(result)
+}
+
+/// Integer square root, rounded down
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // Start above the root so Newton's iteration descends onto it
+    let mut x = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let next = (x + value / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
@@ -1,14 +1,61 @@
 use anchor_lang::prelude::*;
 use anchor_lang::accounts::account_loader::AccountLoader;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
+use anchor_lang::solana_program::keccak;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::{
+    self,
+    spl_token_2022::{
+        self,
+        extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+    },
+    CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface, TransferChecked,
+};
+
+pub mod accounting;
+
+use accounting::{
+    apply_deploy, apply_deposit, apply_return, apply_valuation, apply_withdraw, charge_performance_fee,
+    convert_to_assets, convert_to_shares, mul_div, performance_fee, DepositTarget, Rounding,
+};
 
 declare_id!("521NYDkSEV1htFy6iAkwCfkZrAvaaw7YYDd4dhtfnXQ7");
 
 // Configuration constants
-const PERFORMANCE_FEE_BPS: u16 = 2500; // 25%
-const FEE_COLLECTION_INTERVAL: i64 = 30 * 24 * 60 * 60; // 30 days in seconds
-const TRADING_ALLOCATION_BPS: u16 = 9000; // 90% can be used for trading
-const STALE_VALUATION_THRESHOLD: i64 = 24 * 60 * 60; // 24 hours in seconds
+pub const PERFORMANCE_FEE_BPS: u16 = 2500; // 25%
+pub const FEE_COLLECTION_INTERVAL: i64 = 30 * 24 * 60 * 60; // 30 days in seconds
+pub const TRADING_ALLOCATION_BPS: u16 = 9000; // 90% can be used for trading
+pub const LIQUIDITY_BUFFER_BPS: u16 = 1000; // Treasury kept liquid as a share of total value
+pub const STALE_VALUATION_THRESHOLD: i64 = 24 * 60 * 60; // 24 hours in seconds
+pub const SHARE_PRICE_SCALE: u64 = 1_000_000_000; // Shares priced per `VaultSnapshot::price_per_share`
+pub const SHARE_PRICE_SNAPSHOT_INTERVAL: i64 = 24 * 60 * 60; // 24 hours in seconds
+pub const SHARE_PRICE_HISTORY_CAPACITY: usize = 512; // Daily snapshots for about 17 months
+pub const MAX_RETURN_WINDOWS: usize = 4; // Windows per `get_share_price_returns` call
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+pub const WIND_DOWN_GRACE_PERIOD: i64 = 30 * 24 * 60 * 60; // 30 days in seconds
+pub const MAX_PRICE_AGE: i64 = 60 * 60; // 1 hour in seconds
+
+// Account layout versions
+pub const PROTOCOL_CONFIG_VERSION: u8 = 9;
+pub const USER_POSITION_VERSION: u8 = 1;
+pub const CLAIMS_ESCROW_VERSION: u8 = 1;
+pub const ATTESTATION_VERSION: u8 = 1;
+pub const WITHDRAWAL_DELEGATE_VERSION: u8 = 1;
+pub const ACCEPTED_ASSET_VERSION: u8 = 1;
+pub const SHARE_PRICE_HISTORY_VERSION: u8 = 1;
+
+// Pause flags, one bit per operation class
+pub const PAUSE_DEPOSITS: u8 = 1 << 0;
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 1;
+pub const PAUSE_DEPLOYMENTS: u8 = 1 << 2; // Deploying and returning trading capital
+pub const PAUSE_VALUATIONS: u8 = 1 << 3;
+pub const PAUSE_FEE_COLLECTION: u8 = 1 << 4;
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSITS
+    | PAUSE_WITHDRAWALS
+    | PAUSE_DEPLOYMENTS
+    | PAUSE_VALUATIONS
+    | PAUSE_FEE_COLLECTION;
 
 /// Deployment valuation data from the trading bot
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -21,52 +68,483 @@ pub struct DeploymentValuation {
     pub timestamp: i64,
 }
 
+/// Vault state at the time of the call, returned by `get_vault_snapshot`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultSnapshot {
+    /// User value pool: total value less realized and pending fees
+    pub nav: u64,
+    pub total_value: u64,
+    pub total_shares: u64,
+    /// Base-asset units per `SHARE_PRICE_SCALE` shares, rounded down
+    pub price_per_share: u64,
+    /// Treasury balance, available for withdrawals
+    pub liquid: u64,
+    /// Treasury balance the liquidity buffer calls for
+    pub buffer_target: u64,
+    /// When `check_buffer` flagged the current deficit; deployment is blocked until cleared
+    pub buffer_deficit_since: Option<i64>,
+    /// Deployed capital as counted in `total_value`
+    pub deployed_value: u64,
+    /// Original amount of capital currently deployed
+    pub deployed_cost: u64,
+    /// Accepted-asset holdings as counted in `total_value`; zero once a price behind them is stale
+    pub asset_holdings_value: u64,
+    pub accumulated_fees: u64,
+    pub pending_unrealized_fees: u64,
+    /// Seconds since the last valuation; `None` until one is recorded
+    pub valuation_age: Option<i64>,
+    /// Whether `deployed_value` is the valuation rather than the original amount
+    pub valuation_is_fresh: bool,
+    pub pause_flags: u8,
+    pub emergency_mode: bool,
+    pub lifecycle: VaultLifecycle,
+    pub timestamp: i64,
+}
+
+/// Position state at the time of the call, returned by `get_position_snapshot`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PositionSnapshot {
+    pub owner: Pubkey,
+    pub shares: u64,
+    /// Current value of the shares, rounded down
+    pub value: u64,
+    pub high_water_mark: u64,
+    /// Average-cost basis of the shares held
+    pub cost_basis: u64,
+    pub unrealized_pnl: i64,
+    /// Fee `collect_user_fees` would charge now; zero until `next_fee_collection`
+    pub fee_due: u64,
+    pub next_fee_collection: i64,
+    pub lifetime_fees_paid: u64,
+    pub timestamp: i64,
+}
+
+/// What `deposit(assets)` would do now, returned by `preview_deposit`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DepositPreview {
+    /// Shares minted; an exact `min_shares` bound
+    pub shares: u64,
+    /// Amount credited to the treasury
+    pub received: u64,
+    /// Withheld by a Token-2022 transfer fee
+    pub transfer_fee: u64,
+}
+
+/// What `withdraw(assets)` would do now, returned by `preview_withdraw`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawPreview {
+    /// Shares burned; an exact `max_shares` bound
+    pub shares: u64,
+    /// Amount landing in the destination account
+    pub received: u64,
+    /// Withheld by a Token-2022 transfer fee
+    pub transfer_fee: u64,
+    pub cost_basis_released: u64,
+    pub realized_pnl: i64,
+}
+
+/// What `collect_user_fees` would charge at the current NAV, returned by `preview_fee`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FeePreview {
+    /// Whether collection is accepted now rather than failing with `FeeCollectionTooSoon`
+    pub eligible: bool,
+    pub next_fee_collection: i64,
+    pub fee: u64,
+    pub shares_reduced: u64,
+    /// High-water mark after collection
+    pub high_water_mark: u64,
+}
+
+/// Share price growth over one requested window, returned by `get_share_price_returns`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WindowReturn {
+    /// Requested window in seconds; the measured span is at least this long
+    pub window: i64,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub start_price: u64,
+    pub end_price: u64,
+    /// Return over the measured span
+    pub period_return_bps: i64,
+    /// `period_return_bps` compounded over a year
+    pub apy_bps: i64,
+}
+
+/// Helper to resize a program-owned account, topping up or refunding rent via `payer`
+fn resize_account<'info>(
+    account: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let required_lamports = Rent::get()?.minimum_balance(new_len);
+    let current_lamports = account.lamports();
+
+    if required_lamports > current_lamports {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.clone(), cpi_accounts);
+        system_program::transfer(cpi_ctx, required_lamports - current_lamports)?;
+    } else if current_lamports > required_lamports {
+        let excess = current_lamports - required_lamports;
+        **account.try_borrow_mut_lamports()? -= excess;
+        **payer.try_borrow_mut_lamports()? += excess;
+    }
+
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Helper to verify a sorted-pair keccak Merkle proof for `user`
+fn verify_allowlist_proof(proof: &[[u8; 32]], root: &[u8; 32], user: &Pubkey) -> bool {
+    let mut node = keccak::hashv(&[user.as_ref()]).to_bytes();
+    for sibling in proof {
+        node = if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).to_bytes()
+        } else {
+            keccak::hashv(&[sibling, &node]).to_bytes()
+        };
+    }
+    node == *root
+}
+
+/// Helper to gate entry in restricted vaults: a live attestation or an allowlist proof.
+/// `attestation` is always the user's PDA, so a revocation can't be skipped by omitting it.
+fn check_access(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    attestation: &AccountInfo,
+    proof: &[[u8; 32]],
+) -> Result<()> {
+    if config.access_mode == AccessMode::Open {
+        return Ok(());
+    }
+
+    // A PDA that was never issued or revoked is still owned by the system program
+    if *attestation.owner == crate::ID {
+        let attestation = Attestation::try_deserialize(&mut &attestation.try_borrow_data()?[..])?;
+        // A revocation also overrides any Merkle proof
+        require!(!attestation.revoked, VaultError::AccessRevoked);
+        if attestation.expires_at == 0 || Clock::get()?.unix_timestamp < attestation.expires_at {
+            return Ok(());
+        }
+    }
+
+    require!(
+        config.merkle_root != [0; 32] && verify_allowlist_proof(proof, &config.merkle_root, user),
+        VaultError::AccessDenied
+    );
+    Ok(())
+}
+
+/// Helper to read a mint's Token-2022 transfer fee config, if it has one
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Helper for the amount that lands in the destination after any transfer fee
+fn amount_after_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint)? else {
+        return Ok(amount);
+    };
+    let fee = fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(VaultError::MathOverflow)?;
+    Ok(amount.checked_sub(fee).ok_or(VaultError::MathOverflow)?)
+}
+
+/// Helper for the amount to send so that `net_amount` lands after any transfer fee
+fn amount_before_fee(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint)? else {
+        return Ok(net_amount);
+    };
+    let fee = fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+        .ok_or(VaultError::MathOverflow)?;
+    Ok(net_amount.checked_add(fee).ok_or(VaultError::MathOverflow)?)
+}
+
+/// Helper for the checks every deposit flavour shares
+fn check_deposits_open(config: &ProtocolConfig) -> Result<()> {
+    require!(!config.is_paused(PAUSE_DEPOSITS), VaultError::ProtocolPaused);
+    require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+    require!(config.lifecycle == VaultLifecycle::Active, VaultError::DepositsClosed);
+    Ok(())
+}
+
+/// Helper rejecting share issuance while the asset holdings rest on a stale price
+fn check_asset_prices_fresh(config: &ProtocolConfig) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(accounting::asset_prices_fresh(config, now), VaultError::AssetPriceStale);
+    Ok(())
+}
+
+/// Helper for the checks every withdrawal flavour shares, including treasury liquidity for `amount`
+fn check_withdrawal(config: &ProtocolConfig, treasury_balance: u64, amount: u64) -> Result<()> {
+    require!(!config.is_paused(PAUSE_WITHDRAWALS), VaultError::ProtocolPaused);
+    require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+    // Winding down: exits go through claim_final_balance so final fees apply
+    require!(config.lifecycle != VaultLifecycle::WindingDown, VaultError::VaultWindingDown);
+    require!(treasury_balance >= amount, VaultError::InsufficientLiquidity);
+    Ok(())
+}
+
+/// Helper for the checks fee collection shares with its preview
+fn check_fee_collection_open(config: &ProtocolConfig) -> Result<()> {
+    require!(!config.is_paused(PAUSE_FEE_COLLECTION), VaultError::ProtocolPaused);
+    require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+    Ok(())
+}
+
+/// Helper for `check_buffer` and returns: records the buffer state and emits it
+fn check_liquidity_buffer(config: &mut ProtocolConfig, treasury_balance: u64) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let (target, shortfall) = accounting::apply_buffer_check(config, treasury_balance, now)?;
+    emit!(LiquidityBufferCheckedEvent {
+        treasury_balance,
+        target,
+        shortfall,
+        deficit_since: config.buffer_deficit_since,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+/// Shared crediting step: enforces limits on `value` and mints shares at the pre-deposit pool.
+/// Returns (shares_minted, user_shares)
+fn credit_deposit(
+    config: &mut ProtocolConfig,
+    user_position: &AccountLoader<UserPosition>,
+    user_balance: u64,
+    user_value_pool: u64,
+    total_value: u64,
+    value: u64,
+    target: DepositTarget,
+) -> Result<(u64, u64)> {
+    let mut position = user_position.load_mut()?;
+    let shares_to_mint = apply_deposit(
+        config,
+        &mut position,
+        user_balance,
+        user_value_pool,
+        total_value,
+        value,
+        target,
+    )?;
+    Ok((shares_to_mint, position.user_shares))
+}
+
+/// Shared deposit path; shares are priced from the measured balance delta at the pre-deposit NAV
+#[allow(clippy::too_many_arguments)]
+fn process_deposit<'info>(
+    config: &mut Account<'info, ProtocolConfig>,
+    user_position: &AccountLoader<'info, UserPosition>,
+    treasury_account: &mut InterfaceAccount<'info, TokenAccount>,
+    source_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    payer: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    beneficiary: Pubkey,
+    attestation: &AccountInfo<'info>,
+    amount: u64,
+    target: DepositTarget,
+    proof: &[[u8; 32]],
+) -> Result<()> {
+    check_deposits_open(config)?;
+    check_asset_prices_fresh(config)?;
+    require!(amount > 0, VaultError::InvalidAmount);
+
+    // Access is checked against the position owner, not the payer
+    check_access(config, &beneficiary, attestation, proof)?;
+
+    // Value the vault before any tokens move
+    let treasury_before = treasury_account.amount;
+    let position = user_position.load()?;
+    let (user_balance, user_value_pool, total_value) = calculate_user_balance_internal(
+        config,
+        &position,
+        treasury_before,
+    )?;
+    drop(position);
+
+    // Transfer from payer to TREASURY
+    let cpi_accounts = TransferChecked {
+        from: source_token_account.to_account_info(),
+        mint: mint.to_account_info(),
+        to: treasury_account.to_account_info(),
+        authority: payer.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
+
+    // Credit what actually arrived; transfer-fee mints deliver less than `amount`
+    treasury_account.reload()?;
+    let received = treasury_account.amount
+        .checked_sub(treasury_before)
+        .ok_or(VaultError::MathOverflow)?;
+
+    let (shares_to_mint, user_shares) = credit_deposit(
+        config,
+        user_position,
+        user_balance,
+        user_value_pool,
+        total_value,
+        received,
+        target,
+    )?;
+
+    emit!(DepositEvent {
+        user: beneficiary,
+        payer: payer.key(),
+        amount: received,
+        shares_minted: shares_to_mint,
+        treasury_balance: treasury_account.amount,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Deposited {} to treasury. Minted {} shares. User shares: {}",
+        received, shares_to_mint, user_shares);
+    Ok(())
+}
+
+/// Shared withdraw path; callers price `amount` and `shares_to_burn` against the current pool
+#[allow(clippy::too_many_arguments)]
+fn process_withdraw<'info>(
+    config: &mut Account<'info, ProtocolConfig>,
+    user_position: &AccountLoader<'info, UserPosition>,
+    treasury_account: &InterfaceAccount<'info, TokenAccount>,
+    destination_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    owner: Pubkey,
+    amount: u64,
+    shares_to_burn: u64,
+) -> Result<()> {
+    check_withdrawal(config, treasury_account.amount, amount)?;
+
+    let mut position = user_position.load_mut()?;
+    let (cost_basis_released, realized_pnl) = apply_withdraw(config, &mut position, amount, shares_to_burn)?;
+
+    // Transfer from TREASURY to destination
+    let config_seeds: &[&[&[u8]]] = &[&[
+        b"protocol_config",
+        &[config.bump],
+    ]];
+
+    let cpi_accounts = TransferChecked {
+        from: treasury_account.to_account_info(),
+        mint: mint.to_account_info(),
+        to: destination_token_account.to_account_info(),
+        authority: config.to_account_info(),
+    };
+    let cpi_program = token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
+    token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
+
+    emit!(WithdrawEvent {
+        user: owner,
+        destination: destination_token_account.key(),
+        amount,
+        shares_burned: shares_to_burn,
+        remaining_shares: position.user_shares,
+        cost_basis_released,
+        realized_pnl,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Withdrew {} from treasury. Burned {} shares. Realized PnL: {}",
+        amount, shares_to_burn, realized_pnl);
+    Ok(())
+}
+
+/// Helper to value the vault at the cluster clock: returns (user_value_pool, total_value)
+fn vault_value_pool(config: &ProtocolConfig, treasury_balance: u64) -> Result<(u64, u64)> {
+    let now = Clock::get().map_or(0, |clock| clock.unix_timestamp);
+    accounting::vault_value_pool(config, treasury_balance, now)
+}
+
+/// Helper to value `amount` of an accepted asset in base-asset units at its last price
+fn asset_value(asset: &AcceptedAsset, amount: u64) -> Result<u64> {
+    let unit = 10u64.checked_pow(asset.decimals as u32).ok_or(VaultError::MathOverflow)?;
+    mul_div(amount, asset.price, unit, Rounding::Down)
+}
+
+/// Helper for the shares withdrawing `amount` burns at the current pool (rounded up)
+fn withdraw_shares(config: &ProtocolConfig, treasury_balance: u64, amount: u64) -> Result<u64> {
+    let (pool, _) = vault_value_pool(config, treasury_balance)?;
+    convert_to_shares(config, pool, amount, Rounding::Up)
+}
+
+/// Helper to re-mark an asset's holdings and keep the config aggregate in sync
+fn revalue_asset(config: &mut ProtocolConfig, asset: &mut AcceptedAsset, holdings: u64) -> Result<()> {
+    let value = asset_value(asset, holdings)?;
+    let other_holdings = config.asset_holdings_value
+        .checked_sub(asset.holdings_value)
+        .ok_or(VaultError::MathOverflow)?;
+    config.asset_holdings_value = other_holdings
+        .checked_add(value)
+        .ok_or(VaultError::MathOverflow)?;
+    asset.holdings_value = value;
+
+    // Other assets' prices aren't loaded here, so only ever move the oldest price back
+    config.asset_prices_updated_at = if other_holdings == 0 {
+        asset.price_updated_at
+    } else {
+        config.asset_prices_updated_at.min(asset.price_updated_at)
+    };
+    Ok(())
+}
+
 /// Helper to calculate user balance (internal)
 fn calculate_user_balance_internal(
     config: &ProtocolConfig,
     position: &UserPosition,
     treasury_balance: u64,
 ) -> Result<(u64, u64, u64)> {
-    // Use current valuation if available and fresh, otherwise fall back to original deployed amount
-    let deployed_value = if config.last_valuation_timestamp > 0 {
-        let now = Clock::get().unwrap_or(Clock {
-            slot: 0,
-            epoch_start_timestamp: 0,
-            epoch: 0,
-            leader_schedule_epoch: 0,
-            unix_timestamp: 0,
-        }).unix_timestamp;
-        
-        // Check if valuation is fresh (within 24 hours)
-        if now - config.last_valuation_timestamp <= STALE_VALUATION_THRESHOLD {
-            config.deployed_current_value
-        } else {
-            // Valuation is stale, use original amount
-            config.total_trading_deployed
-        }
-    } else {
-        // No valuation yet, use original amount
-        config.total_trading_deployed
-    };
-    
-    let total_value = treasury_balance.checked_add(deployed_value).ok_or(VaultError::MathOverflow)?;
-    // Subtract both realized and unrealized fees from user value pool
-    let total_fees = config.accumulated_fees
-        .checked_add(config.pending_unrealized_fees)
-        .ok_or(VaultError::MathOverflow)?;
-    let user_value_pool = total_value.checked_sub(total_fees).ok_or(VaultError::MathOverflow)?;
+    let now = Clock::get().map_or(0, |clock| clock.unix_timestamp);
+    accounting::user_balance(config, position, treasury_balance, now)
+}
 
-    if config.total_shares == 0 {
-        return Ok((0, user_value_pool, total_value));
+/// Helper for the largest deposit the vault would accept from `position` right now
+fn max_deposit_internal(config: &ProtocolConfig, position: &UserPosition, treasury_balance: u64) -> Result<u64> {
+    if config.is_paused(PAUSE_DEPOSITS) || config.emergency_mode || config.lifecycle != VaultLifecycle::Active {
+        return Ok(0);
+    }
+    if !accounting::asset_prices_fresh(config, Clock::get()?.unix_timestamp) {
+        return Ok(0);
+    }
+
+    let (user_balance, _, total_value) = calculate_user_balance_internal(config, position, treasury_balance)?;
+    let mut max_assets = u64::MAX;
+    if config.max_total_deposits > 0 {
+        max_assets = max_assets.min(config.max_total_deposits.saturating_sub(total_value));
+    }
+    if config.max_position_size > 0 {
+        max_assets = max_assets.min(config.max_position_size.saturating_sub(user_balance));
     }
+    if max_assets < config.min_deposit {
+        return Ok(0);
+    }
+    Ok(max_assets)
+}
 
-    let user_balance = ((position.user_shares as u128)
-        .checked_mul(user_value_pool as u128)
-        .ok_or(VaultError::MathOverflow)?
-        .checked_div(config.total_shares as u128)
-        .ok_or(VaultError::MathOverflow)?) as u64;
+/// Helper for the largest amount `position` could withdraw right now (bounded by treasury liquidity)
+fn max_withdraw_internal(config: &ProtocolConfig, position: &UserPosition, treasury_balance: u64) -> Result<u64> {
+    if config.is_paused(PAUSE_WITHDRAWALS) || config.emergency_mode || config.lifecycle == VaultLifecycle::WindingDown {
+        return Ok(0);
+    }
 
-    Ok((user_balance, user_value_pool, total_value))
+    let (user_balance, _, _) = calculate_user_balance_internal(config, position, treasury_balance)?;
+    Ok(user_balance.min(treasury_balance))
 }
 
 #[program]
@@ -80,6 +558,7 @@ pub mod vault_with_treasury {
         trading_bot: Pubkey,
     ) -> Result<()> {
         let config = &mut ctx.accounts.protocol_config;
+        config.version = PROTOCOL_CONFIG_VERSION;
         config.admin = admin;
         config.trading_bot = trading_bot;
         config.treasury = ctx.accounts.treasury_account.key();
@@ -87,7 +566,19 @@ pub mod vault_with_treasury {
         config.total_trading_deployed = 0;
         config.accumulated_fees = 0;
         config.performance_fee_bps = PERFORMANCE_FEE_BPS;
-        config.is_paused = false;
+        config.pause_flags = 0;
+        config.guardian = Pubkey::default();
+        config.emergency_mode = false;
+        config.lifecycle = VaultLifecycle::Active;
+        config.wind_down_started_at = 0;
+        config.max_total_deposits = 0;
+        config.max_position_size = 0;
+        config.min_deposit = 0;
+        config.access_mode = AccessMode::Open;
+        config.merkle_root = [0; 32];
+        config.asset_holdings_value = 0;
+        config.buffer_deficit_since = 0;
+        config.asset_prices_updated_at = 0;
         config.bump = ctx.bumps.protocol_config;
         config.last_fee_sweep = 0;
         config.deployed_current_value = 0;
@@ -99,157 +590,481 @@ pub mod vault_with_treasury {
     }
 
     /// User creates their position in the vault
-    pub fn create_user_position(ctx: Context<CreateUserPosition>) -> Result<()> {
+    pub fn create_user_position(ctx: Context<CreateUserPosition>, proof: Vec<[u8; 32]>) -> Result<()> {
+        check_access(
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.owner.key(),
+            &ctx.accounts.attestation,
+            &proof,
+        )?;
+
         let position = &mut ctx.accounts.user_position.load_init()?;
         position.owner = ctx.accounts.owner.key();
-        position.deposited_amount = 0;
+        position.cost_basis = 0;
         position.user_shares = 0;
         position.high_water_mark = 0;
         position.last_fee_collection = Clock::get()?.unix_timestamp;
         position.lifetime_fees_paid = 0;
         position.is_active = 1; // true
-        position._padding = [0; 7];
+        position.version = USER_POSITION_VERSION;
+        position._padding = [0; 6];
 
         msg!("User position created for: {}", position.owner);
         Ok(())
     }
 
+    /// User closes an empty position and reclaims its rent
+    pub fn close_user_position(ctx: Context<CloseUserPosition>) -> Result<()> {
+        let position = ctx.accounts.user_position.load()?;
+        // Positions only hold shares; there is no withdrawal queue to drain first
+        require!(position.user_shares == 0, VaultError::PositionNotEmpty);
+        drop(position);
+
+        let lamports_reclaimed = ctx.accounts.user_position.to_account_info().lamports();
+
+        emit!(UserPositionClosedEvent {
+            user: ctx.accounts.owner.key(),
+            position: ctx.accounts.user_position.key(),
+            lamports_reclaimed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("User position closed for: {}. Reclaimed {} lamports",
+            ctx.accounts.owner.key(), lamports_reclaimed);
+        Ok(())
+    }
+
     /// User deposits funds - goes to TREASURY
-    pub fn deposit(ctx: Context<Deposit>, amount: u64, min_shares: u64) -> Result<()> {
-        require!(!ctx.accounts.protocol_config.is_paused, VaultError::ProtocolPaused);
+    pub fn deposit(ctx: Context<Deposit>, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        let beneficiary = ctx.accounts.owner.key();
+        process_deposit(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &mut ctx.accounts.treasury_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            beneficiary,
+            &ctx.accounts.attestation,
+            amount,
+            DepositTarget::MinShares(min_shares),
+            &proof,
+        )
+    }
+
+    /// Payer deposits funds into another owner's position
+    pub fn deposit_for(ctx: Context<DepositFor>, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        let beneficiary = ctx.accounts.beneficiary.key();
+        process_deposit(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &mut ctx.accounts.treasury_account,
+            &ctx.accounts.payer_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.payer,
+            &ctx.accounts.token_program,
+            beneficiary,
+            &ctx.accounts.attestation,
+            amount,
+            DepositTarget::MinShares(min_shares),
+            &proof,
+        )
+    }
+
+    /// User withdraws their share from the treasury, always in the base asset
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64, max_shares: u64) -> Result<()> {
+        let shares_to_burn = withdraw_shares(&ctx.accounts.protocol_config, ctx.accounts.treasury_account.amount, amount)?;
+        require!(shares_to_burn <= max_shares, VaultError::SlippageExceeded);
+
+        let owner = ctx.accounts.owner.key();
+        process_withdraw(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &ctx.accounts.treasury_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            owner,
+            amount,
+            shares_to_burn,
+        )
+    }
+
+    /// User mints an exact number of shares, paying at most `max_assets`
+    pub fn mint(ctx: Context<Deposit>, shares: u64, max_assets: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        let assets = convert_to_assets(config, pool, shares, Rounding::Up)?;
+        let amount = amount_before_fee(&ctx.accounts.token_mint, assets)?;
+        require!(amount <= max_assets, VaultError::SlippageExceeded);
+
+        let beneficiary = ctx.accounts.owner.key();
+        process_deposit(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &mut ctx.accounts.treasury_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            beneficiary,
+            &ctx.accounts.attestation,
+            amount,
+            DepositTarget::ExactShares(shares),
+            &proof,
+        )
+    }
+
+    /// User burns an exact number of shares, receiving at least `min_assets`
+    pub fn redeem(ctx: Context<Withdraw>, shares: u64, min_assets: u64) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        let amount = convert_to_assets(config, pool, shares, Rounding::Down)?;
+        require!(amount >= min_assets, VaultError::SlippageExceeded);
+
+        let owner = ctx.accounts.owner.key();
+        process_withdraw(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &ctx.accounts.treasury_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            owner,
+            amount,
+            shares,
+        )
+    }
+
+    /// User burns every share they hold, receiving at least `min_assets`
+    pub fn redeem_all(ctx: Context<Withdraw>, min_assets: u64) -> Result<()> {
+        let shares = ctx.accounts.user_position.load()?.user_shares;
+        let config = &ctx.accounts.protocol_config;
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        let amount = convert_to_assets(config, pool, shares, Rounding::Down)?;
+        require!(amount >= min_assets, VaultError::SlippageExceeded);
+
+        let owner = ctx.accounts.owner.key();
+        process_withdraw(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &ctx.accounts.treasury_account,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            owner,
+            amount,
+            shares,
+        )
+    }
+
+    /// User deposits native SOL into a wSOL vault; lamports are wrapped on the way in
+    pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        // Wrap lamports into the owner's transient wSOL account
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.wsol_account.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        token_interface::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            SyncNative {
+                account: ctx.accounts.wsol_account.to_account_info(),
+            },
+        ))?;
+
+        let beneficiary = ctx.accounts.owner.key();
+        process_deposit(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &mut ctx.accounts.treasury_account,
+            &ctx.accounts.wsol_account,
+            &ctx.accounts.native_mint,
+            &ctx.accounts.owner,
+            &ctx.accounts.token_program,
+            beneficiary,
+            &ctx.accounts.attestation,
+            amount,
+            DepositTarget::MinShares(min_shares),
+            &proof,
+        )?;
+
+        // Close the emptied wSOL account, returning its rent to the owner
+        token_interface::close_account(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.wsol_account.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ))
+    }
+
+    /// User withdraws from a wSOL vault as native SOL; tokens are unwrapped on the way out
+    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64, max_shares: u64) -> Result<()> {
+        let shares_to_burn = withdraw_shares(&ctx.accounts.protocol_config, ctx.accounts.treasury_account.amount, amount)?;
+        require!(shares_to_burn <= max_shares, VaultError::SlippageExceeded);
+
+        let owner = ctx.accounts.owner.key();
+        process_withdraw(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &ctx.accounts.treasury_account,
+            &ctx.accounts.wsol_account,
+            &ctx.accounts.native_mint,
+            &ctx.accounts.token_program,
+            owner,
+            amount,
+            shares_to_burn,
+        )?;
+
+        // Closing the wSOL account pays out the withdrawn lamports plus its rent
+        token_interface::close_account(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.wsol_account.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        ))
+    }
+
+    /// User deposits an accepted non-base asset, credited at its price less the haircut.
+    /// Exits pay the base asset; holdings reach the treasury once the bot sweeps and converts them.
+    pub fn deposit_asset(ctx: Context<DepositAsset>, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let asset = &ctx.accounts.accepted_asset;
+        check_deposits_open(config)?;
+        check_asset_prices_fresh(config)?;
         require!(amount > 0, VaultError::InvalidAmount);
+        require!(asset.enabled, VaultError::AssetDisabled);
+        let now = Clock::get()?.unix_timestamp;
+        require!(now - asset.price_updated_at <= MAX_PRICE_AGE, VaultError::AssetPriceStale);
 
-        // Transfer from user to TREASURY
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.treasury_account.to_account_info(),
+        let owner = ctx.accounts.owner.key();
+        check_access(config, &owner, &ctx.accounts.attestation, &proof)?;
+
+        // Value the vault before any tokens move
+        let holdings_before = ctx.accounts.asset_treasury.amount;
+        let position = ctx.accounts.user_position.load()?;
+        let (user_balance, user_value_pool, total_value) = calculate_user_balance_internal(
+            config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?;
+        drop(position);
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.user_asset_account.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.asset_treasury.to_account_info(),
             authority: ctx.accounts.owner.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
-
-        // Calculate current total value and user value pool
-        let config = &mut ctx.accounts.protocol_config;
-        let treasury_balance = ctx.accounts.treasury_account.amount;
-        let deployed_capital = config.total_trading_deployed;
-        let total_value = treasury_balance.checked_add(deployed_capital).ok_or(VaultError::MathOverflow)?;
-        let user_value_pool = total_value.checked_sub(config.accumulated_fees).ok_or(VaultError::MathOverflow)?;
-
-        // Calculate shares to mint
-        let shares_to_mint: u64 = if config.total_shares == 0 {
-            amount
-        } else {
-            ((amount as u128)
-                .checked_mul(config.total_shares as u128)
-                .ok_or(VaultError::MathOverflow)?
-                .checked_div(user_value_pool as u128)
-                .ok_or(VaultError::MathOverflow)?) as u64
-        };
-        require!(shares_to_mint >= min_shares, VaultError::SlippageExceeded);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.asset_mint.decimals)?;
 
-        // Update user position
-        let mut position = ctx.accounts.user_position.load_mut()?;
-        position.deposited_amount = position.deposited_amount
-            .checked_add(amount)
-            .ok_or(VaultError::MathOverflow)?;
-        position.user_shares = position.user_shares
-            .checked_add(shares_to_mint)
-            .ok_or(VaultError::MathOverflow)?;
-        // HWM increase by deposited amount (exact value added)
-        position.high_water_mark = position.high_water_mark
-            .checked_add(amount)
+        ctx.accounts.asset_treasury.reload()?;
+        let received = ctx.accounts.asset_treasury.amount
+            .checked_sub(holdings_before)
             .ok_or(VaultError::MathOverflow)?;
+        let value = mul_div(
+            asset_value(asset, received)?,
+            10000 - asset.haircut_bps as u64,
+            10000,
+            Rounding::Down,
+        )?;
 
-        // Update protocol totals
-        config.total_shares = config.total_shares
-            .checked_add(shares_to_mint)
-            .ok_or(VaultError::MathOverflow)?;
+        let (shares_to_mint, user_shares) = credit_deposit(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            user_balance,
+            user_value_pool,
+            total_value,
+            value,
+            DepositTarget::MinShares(min_shares),
+        )?;
+        revalue_asset(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.accepted_asset,
+            ctx.accounts.asset_treasury.amount,
+        )?;
 
-        emit!(DepositEvent {
-            user: ctx.accounts.owner.key(),
-            amount,
+        emit!(AssetDepositEvent {
+            user: owner,
+            mint: ctx.accounts.asset_mint.key(),
+            amount: received,
+            value,
             shares_minted: shares_to_mint,
-            treasury_balance: ctx.accounts.treasury_account.amount,
-            timestamp: Clock::get()?.unix_timestamp,
+            timestamp: now,
         });
 
-        msg!("Deposited {} to treasury. Minted {} shares. User shares: {}",
-            amount, shares_to_mint, position.user_shares);
+        msg!("Deposited {} of {} valued at {}. Minted {} shares. User shares: {}",
+            received, ctx.accounts.asset_mint.key(), value, shares_to_mint, user_shares);
         Ok(())
     }
 
-    /// User withdraws their share from the treasury
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64, max_shares: u64) -> Result<()> {
-        require!(!ctx.accounts.protocol_config.is_paused, VaultError::ProtocolPaused);
+    /// Owner registers a delegate allowed to redeem into a fixed destination
+    pub fn set_withdrawal_delegate(ctx: Context<SetWithdrawalDelegate>, delegate: Pubkey) -> Result<()> {
+        let delegation = &mut ctx.accounts.withdrawal_delegate;
+        delegation.version = WITHDRAWAL_DELEGATE_VERSION;
+        delegation.owner = ctx.accounts.owner.key();
+        delegation.delegate = delegate;
+        delegation.destination = ctx.accounts.destination.key();
+        delegation.bump = ctx.bumps.withdrawal_delegate;
+
+        emit!(WithdrawalDelegateSetEvent {
+            owner: delegation.owner,
+            delegate,
+            destination: delegation.destination,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // Calculate current user balance
-        let position = ctx.accounts.user_position.load()?;
-        let (user_balance, user_value_pool, _total_value) = calculate_user_balance_internal(
-            &ctx.accounts.protocol_config,
-            &position,
-            ctx.accounts.treasury_account.amount,
-        )?;
-        drop(position); // Release the immutable borrow
-        require!(user_balance >= amount, VaultError::InsufficientBalance);
+        msg!("Withdrawal delegate {} set for {}", delegate, delegation.owner);
+        Ok(())
+    }
 
-        // Check liquidity
-        let treasury_balance = ctx.accounts.treasury_account.amount;
-        require!(treasury_balance >= amount, VaultError::InsufficientLiquidity);
+    /// Owner removes their withdrawal delegate and reclaims its rent
+    pub fn revoke_withdrawal_delegate(ctx: Context<RevokeWithdrawalDelegate>) -> Result<()> {
+        emit!(WithdrawalDelegateRevokedEvent {
+            owner: ctx.accounts.owner.key(),
+            delegate: ctx.accounts.withdrawal_delegate.delegate,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // Calculate shares to burn
-        let config = &mut ctx.accounts.protocol_config;
-        let shares_to_burn = ((amount as u128)
-            .checked_mul(config.total_shares as u128)
-            .ok_or(VaultError::MathOverflow)?
-            .checked_div(user_value_pool as u128)
-            .ok_or(VaultError::MathOverflow)?) as u64;
+        msg!("Withdrawal delegate revoked for {}", ctx.accounts.owner.key());
+        Ok(())
+    }
+
+    /// Registered delegate withdraws on the owner's behalf into the approved destination
+    pub fn withdraw_as_delegate(ctx: Context<WithdrawAsDelegate>, amount: u64, max_shares: u64) -> Result<()> {
+        let shares_to_burn = withdraw_shares(&ctx.accounts.protocol_config, ctx.accounts.treasury_account.amount, amount)?;
         require!(shares_to_burn <= max_shares, VaultError::SlippageExceeded);
 
-        // Update user position
-        let mut position = ctx.accounts.user_position.load_mut()?;
-        let original_shares = position.user_shares;
-        position.user_shares = position.user_shares
-            .checked_sub(shares_to_burn)
-            .ok_or(VaultError::MathOverflow)?;
-        // Proportional HWM reduction
-        if original_shares > 0 {
-            position.high_water_mark = ((position.high_water_mark as u128)
-                * (position.user_shares as u128)
-                / (original_shares as u128)) as u64;
-        } else {
-            position.high_water_mark = 0;
-        }
+        let delegation = &ctx.accounts.withdrawal_delegate;
+        require!(
+            ctx.accounts.delegate.key() == delegation.delegate,
+            VaultError::UnauthorizedDelegate
+        );
+        require!(
+            ctx.accounts.destination.key() == delegation.destination,
+            VaultError::InvalidDestination
+        );
 
-        // Update protocol totals
-        config.total_shares = config.total_shares
-            .checked_sub(shares_to_burn)
+        let owner = ctx.accounts.owner.key();
+        process_withdraw(
+            &mut ctx.accounts.protocol_config,
+            &ctx.accounts.user_position,
+            &ctx.accounts.treasury_account,
+            &ctx.accounts.destination,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            owner,
+            amount,
+            shares_to_burn,
+        )
+    }
+
+    /// User redeems shares pro rata against the treasury while in emergency mode.
+    /// remaining_accounts: for every accepted asset with holdings, (accepted_asset, asset_treasury,
+    /// asset_mint, user_asset_account, token_program); the same share of each is paid out in kind.
+    pub fn emergency_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        shares: u64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        require!(config.emergency_mode, VaultError::NotInEmergencyMode);
+        require!(ctx.remaining_accounts.len() % 5 == 0, VaultError::InvalidAccounts);
+
+        // Deployed capital and valuations are ignored: redeem against what the vault holds
+        let total_shares = config.total_shares;
+        let liquid_value = ctx.accounts.treasury_account.amount
+            .checked_sub(config.accumulated_fees)
             .ok_or(VaultError::MathOverflow)?;
+        let amount = mul_div(liquid_value, shares, total_shares, Rounding::Down)?;
 
-        // Transfer from TREASURY to user
+        let owner = ctx.accounts.owner.key();
         let config_seeds: &[&[&[u8]]] = &[&[
             b"protocol_config",
             &[config.bump],
         ]];
+        let holdings_value = config.asset_holdings_value;
+        let mut holdings_covered = 0u64;
+        let mut asset_value_paid = 0u64;
+        let mut paid_mints: Vec<Pubkey> = Vec::new();
+        for accounts in ctx.remaining_accounts.chunks(5) {
+            let mut asset: Account<AcceptedAsset> = Account::try_from(&accounts[0])?;
+            let asset_treasury: InterfaceAccount<TokenAccount> = InterfaceAccount::try_from(&accounts[1])?;
+            let asset_mint: InterfaceAccount<Mint> = InterfaceAccount::try_from(&accounts[2])?;
+            let user_asset_account: InterfaceAccount<TokenAccount> = InterfaceAccount::try_from(&accounts[3])?;
+            let token_program: Interface<TokenInterface> = Interface::try_from(&accounts[4])?;
+            require!(
+                asset_treasury.key() == asset.treasury
+                    && asset_mint.key() == asset.mint
+                    && user_asset_account.owner == owner
+                    && !paid_mints.contains(&asset.mint),
+                VaultError::InvalidAccounts
+            );
+            paid_mints.push(asset.mint);
+            holdings_covered = holdings_covered
+                .checked_add(asset.holdings_value)
+                .ok_or(VaultError::MathOverflow)?;
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.treasury_account.to_account_info(),
-            to: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.protocol_config.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
-        token::transfer(cpi_ctx, amount)?;
+            let payout = mul_div(asset_treasury.amount, shares, total_shares, Rounding::Down)?;
+            if payout > 0 {
+                let cpi_accounts = TransferChecked {
+                    from: asset_treasury.to_account_info(),
+                    mint: asset_mint.to_account_info(),
+                    to: user_asset_account.to_account_info(),
+                    authority: ctx.accounts.protocol_config.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, config_seeds);
+                token_interface::transfer_checked(cpi_ctx, payout, asset_mint.decimals)?;
+            }
+            asset_value_paid = asset_value_paid
+                .checked_add(asset_value(&asset, payout)?)
+                .ok_or(VaultError::MathOverflow)?;
+            revalue_asset(&mut ctx.accounts.protocol_config, &mut asset, asset_treasury.amount - payout)?;
+            asset.exit(&crate::ID)?;
+        }
+        // Leaving an asset out would forfeit the holder's share of it
+        require!(holdings_covered == holdings_value, VaultError::InvalidAccounts);
 
-        emit!(WithdrawEvent {
-            user: ctx.accounts.owner.key(),
+        let value_paid = amount.checked_add(asset_value_paid).ok_or(VaultError::MathOverflow)?;
+        let mut position = ctx.accounts.user_position.load_mut()?;
+        let (cost_basis_released, realized_pnl) =
+            apply_withdraw(&mut ctx.accounts.protocol_config, &mut position, value_paid, shares)?;
+
+        if amount > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.treasury_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: ctx.accounts.protocol_config.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
+            token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.token_mint.decimals)?;
+        }
+
+        emit!(EmergencyWithdrawEvent {
+            user: owner,
             amount,
-            shares_burned: shares_to_burn,
+            asset_value: asset_value_paid,
+            shares_burned: shares,
             remaining_shares: position.user_shares,
+            cost_basis_released,
+            realized_pnl,
             timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("Withdrew {} from treasury. Burned {} shares", amount, shares_to_burn);
+        msg!("Emergency withdrew {} and {} in assets for {} shares", amount, asset_value_paid, shares);
         Ok(())
     }
 
@@ -264,38 +1079,33 @@ pub mod vault_with_treasury {
             ctx.accounts.trading_bot.key() == config.trading_bot,
             VaultError::UnauthorizedTradingBot
         );
+        require!(!config.is_paused(PAUSE_DEPLOYMENTS), VaultError::ProtocolPaused);
+        require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+        require!(config.lifecycle != VaultLifecycle::WindingDown, VaultError::VaultWindingDown);
 
         let treasury_balance = ctx.accounts.treasury_account.amount;
-        let max_deployable = ((treasury_balance as u128)
-            .checked_mul(TRADING_ALLOCATION_BPS as u128)
-            .ok_or(VaultError::MathOverflow)?
-            .checked_div(10000)
-            .ok_or(VaultError::MathOverflow)?) as u64;
-
-        require!(amount <= max_deployable, VaultError::ExceedsMaxDeployment);
+        let now = Clock::get()?.unix_timestamp;
+        apply_deploy(&mut ctx.accounts.protocol_config, treasury_balance, amount, now)?;
 
+        let config = &ctx.accounts.protocol_config;
         let config_seeds: &[&[&[u8]]] = &[&[
             b"protocol_config",
             &[config.bump],
         ]];
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.treasury_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
             to: ctx.accounts.trading_account.to_account_info(),
             authority: ctx.accounts.protocol_config.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
-        token::transfer(cpi_ctx, amount)?;
-
-        let config = &mut ctx.accounts.protocol_config;
-        config.total_trading_deployed = config.total_trading_deployed
-            .checked_add(amount)
-            .ok_or(VaultError::MathOverflow)?;
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.token_mint.decimals)?;
 
         emit!(CapitalDeployedEvent {
             amount,
-            total_deployed: config.total_trading_deployed,
+            total_deployed: ctx.accounts.protocol_config.total_trading_deployed,
             treasury_remaining: treasury_balance - amount,
             timestamp: Clock::get()?.unix_timestamp,
         });
@@ -315,71 +1125,34 @@ pub mod vault_with_treasury {
             ctx.accounts.trading_bot.key() == config.trading_bot,
             VaultError::UnauthorizedTradingBot
         );
+        // Returns stay open in emergency mode so the bot can bring capital home
+        require!(
+            config.emergency_mode || !config.is_paused(PAUSE_DEPLOYMENTS),
+            VaultError::ProtocolPaused
+        );
 
-        require!(original_deployed <= config.total_trading_deployed, VaultError::InvalidAmount);
+        let (profit_or_loss, fee) = apply_return(config, returned_amount, original_deployed)?;
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.trading_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
             to: ctx.accounts.treasury_account.to_account_info(),
             authority: ctx.accounts.trading_bot.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, returned_amount)?;
-
-        let profit_or_loss = if returned_amount > original_deployed {
-            (returned_amount - original_deployed) as i64
-        } else {
-            -((original_deployed - returned_amount) as i64)
-        };
+        token_interface::transfer_checked(cpi_ctx, returned_amount, ctx.accounts.token_mint.decimals)?;
+        ctx.accounts.treasury_account.reload()?;
 
-        config.total_trading_deployed = config.total_trading_deployed
-            .checked_sub(original_deployed)
-            .ok_or(VaultError::MathOverflow)?;
-        
-        // Clear valuation data if all capital is returned
-        if config.total_trading_deployed == 0 {
-            config.deployed_current_value = 0;
-            config.last_valuation_timestamp = 0;
-            config.pending_unrealized_fees = 0;
-        } else {
-            // Proportionally reduce deployed current value
-            if config.deployed_current_value > 0 && config.total_trading_deployed > 0 {
-                let remaining_ratio = ((config.total_trading_deployed as u128)
-                    .checked_mul(10000)
-                    .ok_or(VaultError::MathOverflow)?
-                    .checked_div((config.total_trading_deployed + original_deployed) as u128)
-                    .ok_or(VaultError::MathOverflow)?) as u64;
-                
-                config.deployed_current_value = ((config.deployed_current_value as u128)
-                    .checked_mul(remaining_ratio as u128)
-                    .ok_or(VaultError::MathOverflow)?
-                    .checked_div(10000)
-                    .ok_or(VaultError::MathOverflow)?) as u64;
-            }
+        // Deployment reopens once the returned capital restores the buffer
+        if ctx.accounts.protocol_config.buffer_deficit_since != 0 {
+            check_liquidity_buffer(&mut ctx.accounts.protocol_config, ctx.accounts.treasury_account.amount)?;
         }
 
         if profit_or_loss > 0 {
-            let profit = profit_or_loss as u64;
-            let fee = ((profit as u128)
-                .checked_mul(config.performance_fee_bps as u128)
-                .ok_or(VaultError::MathOverflow)?
-                .checked_div(10000)
-                .ok_or(VaultError::MathOverflow)?) as u64;
-
-            config.accumulated_fees = config.accumulated_fees
-                .checked_add(fee)
-                .ok_or(VaultError::MathOverflow)?;
-            
-            // Reduce pending unrealized fees by the realized portion
-            if config.pending_unrealized_fees > 0 {
-                config.pending_unrealized_fees = config.pending_unrealized_fees.saturating_sub(fee);
-            }
-
-            msg!("Partial profit: {}, Fee accrued: {}", profit, fee);
+            msg!("Partial profit: {}, Fee accrued: {}", profit_or_loss, fee);
         } else if profit_or_loss < 0 {
-            let loss = (-profit_or_loss) as u64;
-            msg!("Partial loss: {}", loss);
+            msg!("Partial loss: {}", -profit_or_loss);
         }
 
         emit!(CapitalReturnedEvent {
@@ -392,70 +1165,52 @@ pub mod vault_with_treasury {
         Ok(())
     }
 
-    /// Collect monthly performance fees for a single user
-    pub fn collect_user_fees(ctx: Context<CollectUserFees>) -> Result<()> {
+    /// Anyone checks the treasury against the liquidity buffer target. A shortfall blocks
+    /// deployment until returned capital (or deposits, on the next check) restore it.
+    pub fn check_buffer(ctx: Context<CheckBuffer>) -> Result<()> {
+        let treasury_balance = ctx.accounts.treasury_account.amount;
+        check_liquidity_buffer(&mut ctx.accounts.protocol_config, treasury_balance)?;
+
+        let config = &ctx.accounts.protocol_config;
+        if config.buffer_deficit_since != 0 {
+            msg!("Liquidity buffer deficit since {}", config.buffer_deficit_since);
+        }
+        Ok(())
+    }
+
+    /// Collect monthly performance fees for a single user
+    pub fn collect_user_fees(ctx: Context<CollectUserFees>) -> Result<()> {
         // Read-only first
         let cfg_ref = &ctx.accounts.protocol_config;
         require!(
             ctx.accounts.caller.key() == cfg_ref.admin || ctx.accounts.caller.key() == cfg_ref.trading_bot,
             VaultError::UnauthorizedCaller
         );
+        check_fee_collection_open(cfg_ref)?;
 
         let now = Clock::get()?.unix_timestamp;
-        // First, use immutable borrow for checks and calculations
-        let (current_balance, user_value_pool, high_water_mark, perf_bps) = {
-            let position = ctx.accounts.user_position.load()?; // immutable borrow
-            require!(
-                now >= position.last_fee_collection + FEE_COLLECTION_INTERVAL,
-                VaultError::FeeCollectionTooSoon
-            );
-            let (cb, uvp, _) = calculate_user_balance_internal(
-                cfg_ref,
-                &position,
-                ctx.accounts.treasury_account.amount,
-            )?;
-            (cb, uvp, position.high_water_mark, cfg_ref.performance_fee_bps)
-        }; // immutable borrows end here
-
-        // Now use mutable borrow for mutations
         let mut position = ctx.accounts.user_position.load_mut()?;
-        let profit = current_balance.saturating_sub(high_water_mark);
-
-        // Compute fee from the copied perf_bps to avoid holding cfg_ref
-        let fee = ((profit as u128)
-            .checked_mul(perf_bps as u128)
-            .ok_or(VaultError::MathOverflow)?
-            .checked_div(10000)
-            .ok_or(VaultError::MathOverflow)?) as u64;
+        require!(
+            now >= position.last_fee_collection + FEE_COLLECTION_INTERVAL,
+            VaultError::FeeCollectionTooSoon
+        );
+        let (current_balance, user_value_pool, _) = calculate_user_balance_internal(
+            cfg_ref,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?;
 
+        let (fee, shares_reduced) = charge_performance_fee(
+            &mut ctx.accounts.protocol_config,
+            &mut position,
+            current_balance,
+            user_value_pool,
+        )?;
         if fee > 0 {
-            // Borrow ProtocolConfig mutably only when needed for writes and reading total_shares
-            let config = &mut ctx.accounts.protocol_config;
-            let shares_to_reduce = ((fee as u128)
-                .checked_mul(config.total_shares as u128)
-                .ok_or(VaultError::MathOverflow)?
-                .checked_div(user_value_pool as u128)
-                .ok_or(VaultError::MathOverflow)?) as u64;
-
-            position.user_shares = position.user_shares
-                .checked_sub(shares_to_reduce)
-                .ok_or(VaultError::MathOverflow)?;
-            position.high_water_mark = current_balance - fee;
-            position.lifetime_fees_paid = position.lifetime_fees_paid
-                .checked_add(fee)
-                .ok_or(VaultError::MathOverflow)?;
-
-            config.total_shares = config.total_shares
-                .checked_sub(shares_to_reduce)
-                .ok_or(VaultError::MathOverflow)?;
-            config.accumulated_fees = config.accumulated_fees
-                .checked_add(fee)
-                .ok_or(VaultError::MathOverflow)?;
-
             emit!(FeeCollectedEvent {
                 user: position.owner,
                 fee,
-                shares_reduced: shares_to_reduce,
+                shares_reduced,
                 timestamp: now,
             });
         }
@@ -475,73 +1230,41 @@ pub mod vault_with_treasury {
                 ctx.accounts.caller.key() == cfg_ref.admin || ctx.accounts.caller.key() == cfg_ref.trading_bot,
                 VaultError::UnauthorizedCaller
             );
+            check_fee_collection_open(cfg_ref)?;
         }
 
         let now = Clock::get()?.unix_timestamp;
         let treasury_balance = ctx.accounts.treasury_account.amount;
         let mut total_fees = 0u64;
-        let mut _total_shares_reduced = 0u64;
 
-        // Process each account separately to avoid lifetime issues
-        let num_accounts = ctx.remaining_accounts.len();
-        for i in 0..num_accounts {
-            let account_info = &ctx.remaining_accounts[i];
+        for account_info in ctx.remaining_accounts {
             let loader: AccountLoader<UserPosition> = AccountLoader::try_from(account_info)?;
-            // First, check eligibility and calculate with immutable borrow
-            let (current_balance, user_value_pool, high_water_mark, owner, perf_bps) = {
-                let position = loader.load()?; // immutable borrow
-                if now < position.last_fee_collection + FEE_COLLECTION_INTERVAL {
-                    continue;
-                }
-                let cfg_ref = &ctx.accounts.protocol_config;
-                let (cb, uvp, _) = calculate_user_balance_internal(
-                    cfg_ref,
-                    &position,
-                    treasury_balance,
-                )?;
-                (cb, uvp, position.high_water_mark, position.owner, cfg_ref.performance_fee_bps)
-            }; // immutable borrows end here
-
-            // Now use mutable borrow for mutations
-            let profit = current_balance.saturating_sub(high_water_mark);
-            // Compute fee from the copied perf_bps to avoid holding cfg_ref while mut-borrowing
-            let fee = ((profit as u128)
-                .checked_mul(perf_bps as u128)
-                .ok_or(VaultError::MathOverflow)?
-                .checked_div(10000)
-                .ok_or(VaultError::MathOverflow)?) as u64;
-
-            let mut position = loader.load_mut()?; // mutable borrow
+            if now < loader.load()?.last_fee_collection + FEE_COLLECTION_INTERVAL {
+                continue;
+            }
+            let mut position = loader.load_mut()?;
+            let (current_balance, user_value_pool, _) = calculate_user_balance_internal(
+                &ctx.accounts.protocol_config,
+                &position,
+                treasury_balance,
+            )?;
 
+            let (fee, shares_reduced) = charge_performance_fee(
+                &mut ctx.accounts.protocol_config,
+                &mut position,
+                current_balance,
+                user_value_pool,
+            )?;
             if fee > 0 {
-                // Borrow ProtocolConfig mutably only now (for current total_shares and to write)
-                let config = &mut ctx.accounts.protocol_config;
-                let shares_to_reduce = ((fee as u128)
-                    .checked_mul(config.total_shares as u128)
-                    .ok_or(VaultError::MathOverflow)?
-                    .checked_div(user_value_pool as u128)
-                    .ok_or(VaultError::MathOverflow)?) as u64;
-
-                position.user_shares = position.user_shares.checked_sub(shares_to_reduce).ok_or(VaultError::MathOverflow)?;
-                position.high_water_mark = current_balance - fee;
-                position.lifetime_fees_paid = position.lifetime_fees_paid.checked_add(fee).ok_or(VaultError::MathOverflow)?;
-                position.last_fee_collection = now;
-
-                config.total_shares = config.total_shares.checked_sub(shares_to_reduce).ok_or(VaultError::MathOverflow)?;
-                config.accumulated_fees = config.accumulated_fees.checked_add(fee).ok_or(VaultError::MathOverflow)?;
-
-                total_fees += fee;
-                _total_shares_reduced += shares_to_reduce;
-
+                total_fees = total_fees.checked_add(fee).ok_or(VaultError::MathOverflow)?;
                 emit!(FeeCollectedEvent {
-                    user: owner,
+                    user: position.owner,
                     fee,
-                    shares_reduced: shares_to_reduce,
+                    shares_reduced,
                     timestamp: now,
                 });
-            } else {
-                position.last_fee_collection = now;
             }
+            position.last_fee_collection = now;
             // Loader auto-stores on drop
         }
 
@@ -561,6 +1284,8 @@ pub mod vault_with_treasury {
             ctx.accounts.trading_bot.key() == config.trading_bot,
             VaultError::UnauthorizedTradingBot
         );
+        require!(!config.is_paused(PAUSE_VALUATIONS), VaultError::ProtocolPaused);
+        require!(!config.emergency_mode, VaultError::EmergencyModeActive);
         
         // Verify timestamp is recent
         let now = Clock::get()?.unix_timestamp;
@@ -576,24 +1301,7 @@ pub mod vault_with_treasury {
             .checked_add(valuation.uncollected_fees)
             .ok_or(VaultError::MathOverflow)?;
         
-        // Update deployed current value
-        config.deployed_current_value = total_current_value;
-        config.last_valuation_timestamp = valuation.timestamp;
-        
-        // Calculate and update pending unrealized fees if there's profit
-        if valuation.unrealized_pnl > 0 {
-            let unrealized_profit = valuation.unrealized_pnl as u64;
-            let pending_fee = ((unrealized_profit as u128)
-                .checked_mul(config.performance_fee_bps as u128)
-                .ok_or(VaultError::MathOverflow)?
-                .checked_div(10000)
-                .ok_or(VaultError::MathOverflow)?) as u64;
-            
-            config.pending_unrealized_fees = pending_fee;
-        } else {
-            // No fees on losses
-            config.pending_unrealized_fees = 0;
-        }
+        apply_valuation(config, total_current_value, valuation.unrealized_pnl, valuation.timestamp)?;
         
         emit!(ValuationUpdateEvent {
             total_deployed_original: config.total_trading_deployed,
@@ -622,6 +1330,7 @@ pub mod vault_with_treasury {
             ctx.accounts.admin.key() == config.admin,
             VaultError::UnauthorizedAdmin
         );
+        require!(!config.is_paused(PAUSE_FEE_COLLECTION), VaultError::ProtocolPaused);
 
         let fees = config.accumulated_fees;
         require!(fees > 0, VaultError::NoFeesToCollect);
@@ -632,14 +1341,15 @@ pub mod vault_with_treasury {
                  ]];
 
 
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.treasury_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
             to: ctx.accounts.admin_token_account.to_account_info(),
             authority: ctx.accounts.protocol_config.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
-        token::transfer(cpi_ctx, fees)?;
+        token_interface::transfer_checked(cpi_ctx, fees, ctx.accounts.token_mint.decimals)?;
 
         let config = &mut ctx.accounts.protocol_config;
         config.accumulated_fees = 0;
@@ -654,6 +1364,55 @@ pub mod vault_with_treasury {
         Ok(())
     }
 
+    /// Admin creates the share price history ring buffer
+    pub fn initialize_share_price_history(ctx: Context<InitializeSharePriceHistory>) -> Result<()> {
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.protocol_config.admin,
+            VaultError::UnauthorizedAdmin
+        );
+
+        let history = &mut ctx.accounts.share_price_history.load_init()?;
+        history.head = 0;
+        history.count = 0;
+        history.version = SHARE_PRICE_HISTORY_VERSION;
+        history.bump = ctx.bumps.share_price_history;
+
+        msg!("Share price history created with {} slots", SHARE_PRICE_HISTORY_CAPACITY);
+        Ok(())
+    }
+
+    /// Anyone records the current price per share, at most once per snapshot interval
+    pub fn snapshot(ctx: Context<Snapshot>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        // Emergency NAV leaves out deployed capital and would read as a loss
+        require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+        // A stale asset mark isn't a price to record
+        check_asset_prices_fresh(config)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut history = ctx.accounts.share_price_history.load_mut()?;
+        if let Some(latest) = history.latest() {
+            require!(
+                now >= latest.timestamp + SHARE_PRICE_SNAPSHOT_INTERVAL,
+                VaultError::SnapshotTooSoon
+            );
+        }
+
+        let (user_value_pool, _) = accounting::vault_value_pool(config, ctx.accounts.treasury_account.amount, now)?;
+        let price_per_share = accounting::price_per_share(config, user_value_pool)?;
+        history.push(now, price_per_share);
+
+        emit!(SharePriceSnapshotEvent {
+            price_per_share,
+            total_shares: config.total_shares,
+            nav: user_value_pool,
+            timestamp: now,
+        });
+
+        msg!("Share price snapshot: {} per {} shares", price_per_share, SHARE_PRICE_SCALE);
+        Ok(())
+    }
+
     /// View function for user balance
     pub fn calculate_user_balance(ctx: Context<CalculateBalance>) -> Result<u64> {
         let position = ctx.accounts.user_position.load()?;
@@ -684,6 +1443,181 @@ pub mod vault_with_treasury {
         Ok((balance, position.lifetime_fees_paid, position.last_fee_collection))
     }
 
+    /// View: Vault NAV, liquidity, fees and status in one typed struct
+    pub fn get_vault_snapshot(ctx: Context<GetProtocolStats>) -> Result<VaultSnapshot> {
+        let now = Clock::get()?.unix_timestamp;
+        accounting::vault_snapshot(&ctx.accounts.protocol_config, ctx.accounts.treasury_account.amount, now)
+    }
+
+    /// View: Position value, basis and fee due in one typed struct
+    pub fn get_position_snapshot(ctx: Context<GetUserStats>) -> Result<PositionSnapshot> {
+        let now = Clock::get()?.unix_timestamp;
+        let position = ctx.accounts.user_position.load()?;
+        accounting::position_snapshot(
+            &ctx.accounts.protocol_config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+            now,
+        )
+    }
+
+    /// View: Annualized share price returns over each of `windows` seconds, from the
+    /// snapshot history; `None` for windows the history does not yet cover
+    pub fn get_share_price_returns(
+        ctx: Context<GetSharePriceReturns>,
+        windows: Vec<i64>,
+    ) -> Result<Vec<Option<WindowReturn>>> {
+        require!(windows.len() <= MAX_RETURN_WINDOWS, VaultError::InvalidReturnWindow);
+        let history = ctx.accounts.share_price_history.load()?;
+        windows
+            .iter()
+            .map(|window| accounting::window_return(&history, *window))
+            .collect()
+    }
+
+    /// View: Unrealized PnL of a position against its average-cost basis
+    pub fn get_unrealized_pnl(ctx: Context<GetUnrealizedPnl>) -> Result<i64> {
+        let position = ctx.accounts.user_position.load()?;
+        let balance = calculate_user_balance_internal(
+            &ctx.accounts.protocol_config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?.0;
+        let unrealized_pnl: i64 = (balance as i128 - position.cost_basis as i128)
+            .try_into()
+            .map_err(|_| VaultError::MathOverflow)?;
+        Ok(unrealized_pnl)
+    }
+
+    /// View: What `deposit(assets)` would do now. Runs the deposit's checks, limits and share
+    /// math on copies of the accounts; access proofs are not checked. Zero shares while a
+    /// stale asset price blocks deposits.
+    pub fn preview_deposit(ctx: Context<PreviewPosition>, assets: u64) -> Result<DepositPreview> {
+        let mut config = ProtocolConfig::clone(&ctx.accounts.protocol_config);
+        let mut position = *ctx.accounts.user_position.load()?;
+        check_deposits_open(&config)?;
+        require!(assets > 0, VaultError::InvalidAmount);
+        if !accounting::asset_prices_fresh(&config, Clock::get()?.unix_timestamp) {
+            return Ok(DepositPreview { shares: 0, received: 0, transfer_fee: 0 });
+        }
+
+        let (user_balance, user_value_pool, total_value) = calculate_user_balance_internal(
+            &config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?;
+        let received = amount_after_fee(&ctx.accounts.token_mint, assets)?;
+        let shares = apply_deposit(
+            &mut config,
+            &mut position,
+            user_balance,
+            user_value_pool,
+            total_value,
+            received,
+            DepositTarget::MinShares(0),
+        )?;
+
+        Ok(DepositPreview { shares, received, transfer_fee: assets - received })
+    }
+
+    /// View: Assets `mint(shares)` would charge (rounded up, including transfer fees);
+    /// 0 while a stale asset price blocks minting
+    pub fn preview_mint(ctx: Context<Preview>, shares: u64) -> Result<u64> {
+        let config = &ctx.accounts.protocol_config;
+        if !accounting::asset_prices_fresh(config, Clock::get()?.unix_timestamp) {
+            return Ok(0);
+        }
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        let assets = convert_to_assets(config, pool, shares, Rounding::Up)?;
+        amount_before_fee(&ctx.accounts.token_mint, assets)
+    }
+
+    /// View: What `withdraw(assets)` would do now. Runs the withdrawal's checks and share
+    /// math on copies of the accounts.
+    pub fn preview_withdraw(ctx: Context<PreviewPosition>, assets: u64) -> Result<WithdrawPreview> {
+        let mut config = ProtocolConfig::clone(&ctx.accounts.protocol_config);
+        let mut position = *ctx.accounts.user_position.load()?;
+        let treasury_balance = ctx.accounts.treasury_account.amount;
+
+        let shares = withdraw_shares(&config, treasury_balance, assets)?;
+        check_withdrawal(&config, treasury_balance, assets)?;
+        let (cost_basis_released, realized_pnl) = apply_withdraw(&mut config, &mut position, assets, shares)?;
+        let received = amount_after_fee(&ctx.accounts.token_mint, assets)?;
+
+        Ok(WithdrawPreview { shares, received, transfer_fee: assets - received, cost_basis_released, realized_pnl })
+    }
+
+    /// View: What `collect_user_fees` would charge at the current NAV, whether or not the
+    /// fee interval has passed yet
+    pub fn preview_fee(ctx: Context<PreviewLimits>) -> Result<FeePreview> {
+        let mut config = ProtocolConfig::clone(&ctx.accounts.protocol_config);
+        let mut position = *ctx.accounts.user_position.load()?;
+        check_fee_collection_open(&config)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let next_fee_collection = position.last_fee_collection + FEE_COLLECTION_INTERVAL;
+        let (current_balance, user_value_pool, _) = calculate_user_balance_internal(
+            &config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?;
+        let (fee, shares_reduced) = charge_performance_fee(
+            &mut config,
+            &mut position,
+            current_balance,
+            user_value_pool,
+        )?;
+
+        Ok(FeePreview {
+            eligible: now >= next_fee_collection,
+            next_fee_collection,
+            fee,
+            shares_reduced,
+            high_water_mark: position.high_water_mark,
+        })
+    }
+
+    /// View: Assets `redeem(shares)` would pay out (rounded down)
+    pub fn preview_redeem(ctx: Context<Preview>, shares: u64) -> Result<u64> {
+        let config = &ctx.accounts.protocol_config;
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        convert_to_assets(config, pool, shares, Rounding::Down)
+    }
+
+    /// View: Largest `deposit` the owner can make now (ignores access proofs)
+    pub fn max_deposit(ctx: Context<PreviewLimits>) -> Result<u64> {
+        let position = ctx.accounts.user_position.load()?;
+        max_deposit_internal(&ctx.accounts.protocol_config, &position, ctx.accounts.treasury_account.amount)
+    }
+
+    /// View: Largest `mint` the owner can make now (ignores access proofs)
+    pub fn max_mint(ctx: Context<PreviewLimits>) -> Result<u64> {
+        let config = &ctx.accounts.protocol_config;
+        let position = ctx.accounts.user_position.load()?;
+        let max_assets = max_deposit_internal(config, &position, ctx.accounts.treasury_account.amount)?;
+        if max_assets == u64::MAX {
+            return Ok(u64::MAX);
+        }
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        convert_to_shares(config, pool, max_assets, Rounding::Down)
+    }
+
+    /// View: Largest `withdraw` the owner can make now
+    pub fn max_withdraw(ctx: Context<PreviewLimits>) -> Result<u64> {
+        let position = ctx.accounts.user_position.load()?;
+        max_withdraw_internal(&ctx.accounts.protocol_config, &position, ctx.accounts.treasury_account.amount)
+    }
+
+    /// View: Largest `redeem` the owner can make now
+    pub fn max_redeem(ctx: Context<PreviewLimits>) -> Result<u64> {
+        let config = &ctx.accounts.protocol_config;
+        let position = ctx.accounts.user_position.load()?;
+        let max_assets = max_withdraw_internal(config, &position, ctx.accounts.treasury_account.amount)?;
+        let (pool, _) = vault_value_pool(config, ctx.accounts.treasury_account.amount)?;
+        let max_shares = convert_to_shares(config, pool, max_assets, Rounding::Down)?;
+        Ok(max_shares.min(position.user_shares))
+    }
+
     /// View: Check fee eligibility
     pub fn check_fee_eligibility(ctx: Context<CheckFeeEligibility>) -> Result<bool> {
         let now = Clock::get()?.unix_timestamp;
@@ -692,104 +1626,1136 @@ pub mod vault_with_treasury {
     }
 
 
+    /// Admin upgrades the protocol config account to the current layout
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config_info = ctx.accounts.protocol_config.to_account_info();
+
+        // Read the header straight from raw data; older layouts may not deserialize
+        let from_version = {
+            let data = config_info.try_borrow_data()?;
+            require!(
+                data.len() > ProtocolConfig::VERSION_OFFSET && data[..8] == ProtocolConfig::DISCRIMINATOR,
+                VaultError::InvalidAccounts
+            );
+            let admin = Pubkey::try_from(&data[8..40]).map_err(|_| VaultError::InvalidAccounts)?;
+            require!(ctx.accounts.admin.key() == admin, VaultError::UnauthorizedAdmin);
+            data[ProtocolConfig::VERSION_OFFSET]
+        };
+        require!(from_version < PROTOCOL_CONFIG_VERSION, VaultError::AlreadyMigrated);
+
+        resize_account(
+            &config_info,
+            ProtocolConfig::LEN,
+            &ctx.accounts.admin.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        // Fields added after `from_version` read as zero from the resized tail
+        let mut config = ProtocolConfig::try_deserialize(&mut &config_info.try_borrow_data()?[..])?;
+        // v2: the `is_paused` bool became the `pause_flags` bitset in the same byte
+        if from_version < 2 && config.pause_flags != 0 {
+            config.pause_flags = PAUSE_DEPOSITS | PAUSE_WITHDRAWALS;
+        }
+        config.version = PROTOCOL_CONFIG_VERSION;
+        config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
+
+        emit!(AccountMigratedEvent {
+            account: config_info.key(),
+            from_version,
+            to_version: PROTOCOL_CONFIG_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Protocol config migrated from v{} to v{}", from_version, PROTOCOL_CONFIG_VERSION);
+        Ok(())
+    }
+
+    /// Owner upgrades their position account to the current layout
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        let position_info = ctx.accounts.user_position.to_account_info();
+
+        let from_version = {
+            let data = position_info.try_borrow_data()?;
+            require!(
+                data.len() > UserPosition::VERSION_OFFSET && data[..8] == UserPosition::DISCRIMINATOR,
+                VaultError::InvalidAccounts
+            );
+            data[UserPosition::VERSION_OFFSET]
+        };
+        require!(from_version < USER_POSITION_VERSION, VaultError::AlreadyMigrated);
+
+        resize_account(
+            &position_info,
+            UserPosition::LEN,
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        {
+            let mut data = position_info.try_borrow_mut_data()?;
+            let position: &mut UserPosition = bytemuck::from_bytes_mut(&mut data[8..UserPosition::LEN]);
+            require!(position.owner == ctx.accounts.owner.key(), VaultError::InvalidAccounts);
+            if from_version == 0 {
+                // v0 kept total deposits here, never reduced by withdrawals. Its high-water
+                // mark was scaled down pro rata on every withdrawal and only raised by fee
+                // collection, so the smaller of the two is the average-cost basis, or above
+                // it only by fee-crystallized gains
+                position.cost_basis = position.cost_basis.min(position.high_water_mark);
+            }
+            position.version = USER_POSITION_VERSION;
+        }
+
+        emit!(AccountMigratedEvent {
+            account: position_info.key(),
+            from_version,
+            to_version: USER_POSITION_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("User position migrated from v{} to v{}", from_version, USER_POSITION_VERSION);
+        Ok(())
+    }
+
     // Emergency functions
-    pub fn pause_protocol(ctx: Context<AdminAction>) -> Result<()> {
+
+    /// Admin or guardian pauses the given operations (`PAUSE_*` flags)
+    pub fn pause_protocol(ctx: Context<PauseAction>, operations: u8, reason_code: u16) -> Result<()> {
         let config = &mut ctx.accounts.protocol_config;
-        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
-        config.is_paused = true;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == config.admin || (config.guardian != Pubkey::default() && authority == config.guardian),
+            VaultError::UnauthorizedCaller
+        );
+        require!(operations != 0 && operations & !PAUSE_ALL == 0, VaultError::InvalidPauseFlags);
+
+        config.pause_flags |= operations;
+
+        emit!(ProtocolPausedEvent {
+            authority,
+            operations,
+            pause_flags: config.pause_flags,
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Paused operations {:#07b}, reason {}", operations, reason_code);
         Ok(())
     }
 
-    pub fn unpause_protocol(ctx: Context<AdminAction>) -> Result<()> {
+    /// Admin resumes the given operations; the guardian cannot unpause
+    pub fn unpause_protocol(ctx: Context<AdminAction>, operations: u8, reason_code: u16) -> Result<()> {
         let config = &mut ctx.accounts.protocol_config;
         require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
-        config.is_paused = false;
+        require!(operations != 0 && operations & !PAUSE_ALL == 0, VaultError::InvalidPauseFlags);
+
+        config.pause_flags &= !operations;
+
+        emit!(ProtocolUnpausedEvent {
+            authority: ctx.accounts.admin.key(),
+            operations,
+            pause_flags: config.pause_flags,
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Unpaused operations {:#07b}, reason {}", operations, reason_code);
         Ok(())
     }
-}
 
-// ============ CONTEXTS ============
+    /// Admin or guardian triggers emergency shutdown
+    pub fn enter_emergency_mode(ctx: Context<PauseAction>, reason_code: u16) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == config.admin || (config.guardian != Pubkey::default() && authority == config.guardian),
+            VaultError::UnauthorizedCaller
+        );
+        require!(!config.emergency_mode, VaultError::EmergencyModeActive);
 
-#[derive(Accounts)]
-pub struct InitializeProtocol<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    #[account(
-        init,
-        payer = authority,
-        space = ProtocolConfig::LEN,
-        seeds = [b"protocol_config"],
-        bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
-    #[account(
-        init,
-        payer = authority,
-        token::mint = token_mint,
-        token::authority = protocol_config,
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury_account: Account<'info, TokenAccount>,
-    pub token_mint: Account<'info, Mint>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        config.emergency_mode = true;
 
-#[derive(Accounts)]
-pub struct CreateUserPosition<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    #[account(
-        init,
-        payer = owner,
-        space = UserPosition::LEN,
-        seeds = [b"user_position", owner.key().as_ref()],
-        bump
-    )]
-    pub user_position: AccountLoader<'info, UserPosition>,
-    pub system_program: Program<'info, System>,
-}
+        emit!(EmergencyModeEnteredEvent {
+            authority,
+            reason_code,
+            total_deployed: config.total_trading_deployed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"user_position", owner.key().as_ref()],
-        bump
-    )]
-    pub user_position: AccountLoader<'info, UserPosition>,
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury_account: Account<'info, TokenAccount>,
-    #[account(mut, token::authority = owner)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-}
+        msg!("Emergency mode entered, reason {}. Outstanding deployment: {}",
+            reason_code, config.total_trading_deployed);
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"user_position", owner.key().as_ref()],
-        bump
-    )]
-    pub user_position: AccountLoader<'info, UserPosition>,
-    #[account(
+    /// Admin leaves emergency mode; the guardian cannot
+    pub fn exit_emergency_mode(ctx: Context<AdminAction>, reason_code: u16) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        require!(config.emergency_mode, VaultError::NotInEmergencyMode);
+
+        config.emergency_mode = false;
+
+        emit!(EmergencyModeExitedEvent {
+            authority: ctx.accounts.admin.key(),
+            reason_code,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Emergency mode exited, reason {}", reason_code);
+        Ok(())
+    }
+
+    /// Admin sets deposit limits; zero disables the vault cap and the per-user cap
+    pub fn set_deposit_limits(
+        ctx: Context<AdminAction>,
+        max_total_deposits: u64,
+        max_position_size: u64,
+        min_deposit: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        require!(
+            max_position_size == 0 || min_deposit <= max_position_size,
+            VaultError::InvalidAmount
+        );
+
+        config.max_total_deposits = max_total_deposits;
+        config.max_position_size = max_position_size;
+        config.min_deposit = min_deposit;
+
+        emit!(DepositLimitsUpdatedEvent {
+            max_total_deposits,
+            max_position_size,
+            min_deposit,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Deposit limits set. Vault cap: {}, position cap: {}, minimum: {}",
+            max_total_deposits, max_position_size, min_deposit);
+        Ok(())
+    }
+
+    /// Admin switches between open and allowlist-gated entry
+    pub fn set_access_mode(
+        ctx: Context<AdminAction>,
+        access_mode: AccessMode,
+        merkle_root: [u8; 32],
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+
+        config.access_mode = access_mode;
+        config.merkle_root = merkle_root;
+
+        emit!(AccessModeUpdatedEvent {
+            access_mode,
+            merkle_root,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Access mode set to {:?}", access_mode);
+        Ok(())
+    }
+
+    /// Admin accepts an additional deposit mint with its own treasury sub-account
+    pub fn add_accepted_asset(
+        ctx: Context<AddAcceptedAsset>,
+        oracle: Pubkey,
+        haircut_bps: u16,
+        price: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.protocol_config.admin,
+            VaultError::UnauthorizedAdmin
+        );
+        require!(haircut_bps < 10000, VaultError::InvalidHaircut);
+        require!(price > 0, VaultError::InvalidPrice);
+
+        let now = Clock::get()?.unix_timestamp;
+        let asset = &mut ctx.accounts.accepted_asset;
+        asset.version = ACCEPTED_ASSET_VERSION;
+        asset.mint = ctx.accounts.asset_mint.key();
+        asset.treasury = ctx.accounts.asset_treasury.key();
+        asset.decimals = ctx.accounts.asset_mint.decimals;
+        asset.oracle = oracle;
+        asset.price = price;
+        asset.price_updated_at = now;
+        asset.haircut_bps = haircut_bps;
+        asset.holdings_value = 0;
+        asset.enabled = true;
+        asset.bump = ctx.bumps.accepted_asset;
+
+        emit!(AcceptedAssetUpdatedEvent {
+            mint: asset.mint,
+            oracle,
+            haircut_bps,
+            enabled: true,
+            timestamp: now,
+        });
+
+        msg!("Accepted asset {} at price {} with {} bps haircut", asset.mint, price, haircut_bps);
+        Ok(())
+    }
+
+    /// Admin changes an accepted asset's oracle, haircut, or disables new deposits of it
+    pub fn configure_accepted_asset(
+        ctx: Context<ConfigureAcceptedAsset>,
+        oracle: Pubkey,
+        haircut_bps: u16,
+        enabled: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.protocol_config.admin,
+            VaultError::UnauthorizedAdmin
+        );
+        require!(haircut_bps < 10000, VaultError::InvalidHaircut);
+
+        let asset = &mut ctx.accounts.accepted_asset;
+        asset.oracle = oracle;
+        asset.haircut_bps = haircut_bps;
+        asset.enabled = enabled;
+
+        emit!(AcceptedAssetUpdatedEvent {
+            mint: asset.mint,
+            oracle,
+            haircut_bps,
+            enabled,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Accepted asset {} updated: haircut {} bps, enabled {}", asset.mint, haircut_bps, enabled);
+        Ok(())
+    }
+
+    /// Oracle or admin publishes a new price and re-marks the asset's holdings. Prices are
+    /// trusted as pushed; there is no on-chain feed behind them.
+    /// remaining_accounts: optionally every other accepted asset with holdings, so the config
+    /// can move `asset_prices_updated_at` forward to their oldest price.
+    pub fn update_asset_price<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateAssetPrice<'info>>,
+        price: u64,
+    ) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let asset = &ctx.accounts.accepted_asset;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == config.admin || (asset.oracle != Pubkey::default() && authority == asset.oracle),
+            VaultError::UnauthorizedOracle
+        );
+        require!(!config.is_paused(PAUSE_VALUATIONS), VaultError::ProtocolPaused);
+        require!(price > 0, VaultError::InvalidPrice);
+
+        let now = Clock::get()?.unix_timestamp;
+        let asset = &mut ctx.accounts.accepted_asset;
+        asset.price = price;
+        asset.price_updated_at = now;
+        revalue_asset(&mut ctx.accounts.protocol_config, asset, ctx.accounts.asset_treasury.amount)?;
+
+        if !ctx.remaining_accounts.is_empty() {
+            let mut holdings_covered = asset.holdings_value;
+            let mut oldest_price = asset.price_updated_at;
+            let mut seen_mints = vec![asset.mint];
+            for account_info in ctx.remaining_accounts {
+                let other: Account<AcceptedAsset> = Account::try_from(account_info)?;
+                require!(!seen_mints.contains(&other.mint), VaultError::InvalidAccounts);
+                seen_mints.push(other.mint);
+                if other.holdings_value > 0 {
+                    holdings_covered = holdings_covered
+                        .checked_add(other.holdings_value)
+                        .ok_or(VaultError::MathOverflow)?;
+                    oldest_price = oldest_price.min(other.price_updated_at);
+                }
+            }
+            let config = &mut ctx.accounts.protocol_config;
+            require!(holdings_covered == config.asset_holdings_value, VaultError::InvalidAccounts);
+            config.asset_prices_updated_at = oldest_price;
+        }
+
+        emit!(AssetPriceUpdatedEvent {
+            mint: asset.mint,
+            price,
+            holdings_value: asset.holdings_value,
+            timestamp: now,
+        });
+
+        msg!("Price for {} set to {}. Holdings value: {}", asset.mint, price, asset.holdings_value);
+        Ok(())
+    }
+
+    /// Trading bot takes accepted-asset holdings for conversion; their value moves to deployed capital
+    pub fn sweep_asset_to_trading(ctx: Context<SweepAsset>, amount: u64) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        require!(
+            ctx.accounts.trading_bot.key() == config.trading_bot,
+            VaultError::UnauthorizedTradingBot
+        );
+        require!(!config.is_paused(PAUSE_DEPLOYMENTS), VaultError::ProtocolPaused);
+        require!(!config.emergency_mode, VaultError::EmergencyModeActive);
+        require!(
+            !matches!(config.lifecycle, VaultLifecycle::WindingDown | VaultLifecycle::Closed),
+            VaultError::VaultWindingDown
+        );
+        require!(amount > 0, VaultError::InvalidAmount);
+
+        let config_seeds: &[&[&[u8]]] = &[&[
+            b"protocol_config",
+            &[config.bump],
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.asset_treasury.to_account_info(),
+            mint: ctx.accounts.asset_mint.to_account_info(),
+            to: ctx.accounts.trading_account.to_account_info(),
+            authority: ctx.accounts.protocol_config.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.asset_mint.decimals)?;
+
+        // The marked value that left the sub-account is now deployed capital
+        ctx.accounts.asset_treasury.reload()?;
+        let value_before = ctx.accounts.accepted_asset.holdings_value;
+        revalue_asset(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.accepted_asset,
+            ctx.accounts.asset_treasury.amount,
+        )?;
+        let value = value_before
+            .checked_sub(ctx.accounts.accepted_asset.holdings_value)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let config = &mut ctx.accounts.protocol_config;
+        config.total_trading_deployed = config.total_trading_deployed
+            .checked_add(value)
+            .ok_or(VaultError::MathOverflow)?;
+        if config.last_valuation_timestamp > 0 {
+            config.deployed_current_value = config.deployed_current_value
+                .checked_add(value)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+
+        emit!(AssetSweptEvent {
+            mint: ctx.accounts.asset_mint.key(),
+            amount,
+            value,
+            total_deployed: config.total_trading_deployed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Swept {} of {} (value {}) to trading", amount, ctx.accounts.asset_mint.key(), value);
+        Ok(())
+    }
+
+    /// Admin issues (or renews) an attestation granting `user` entry until `expires_at` (0 = no expiry)
+    pub fn issue_attestation(ctx: Context<ManageAttestation>, user: Pubkey, expires_at: i64) -> Result<()> {
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.protocol_config.admin,
+            VaultError::UnauthorizedAdmin
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(expires_at == 0 || expires_at > now, VaultError::InvalidAmount);
+
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.version = ATTESTATION_VERSION;
+        attestation.user = user;
+        attestation.issued_at = now;
+        attestation.expires_at = expires_at;
+        attestation.revoked = false;
+        attestation.bump = ctx.bumps.attestation;
+
+        emit!(AttestationIssuedEvent {
+            user,
+            expires_at,
+            timestamp: now,
+        });
+
+        msg!("Attestation issued for {} until {}", user, expires_at);
+        Ok(())
+    }
+
+    /// Admin revokes `user`'s access; overrides attestations and allowlist proofs, withdrawals unaffected
+    pub fn revoke_access(ctx: Context<ManageAttestation>, user: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.admin.key() == ctx.accounts.protocol_config.admin,
+            VaultError::UnauthorizedAdmin
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let attestation = &mut ctx.accounts.attestation;
+        attestation.version = ATTESTATION_VERSION;
+        attestation.user = user;
+        attestation.revoked = true;
+        attestation.bump = ctx.bumps.attestation;
+
+        emit!(AccessRevokedEvent {
+            user,
+            timestamp: now,
+        });
+
+        msg!("Access revoked for {}", user);
+        Ok(())
+    }
+
+    /// Admin sets the guardian key (Pubkey::default() disables it)
+    pub fn set_guardian(ctx: Context<AdminAction>, guardian: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        let old_guardian = config.guardian;
+        config.guardian = guardian;
+
+        emit!(GuardianUpdatedEvent {
+            old_guardian,
+            new_guardian: guardian,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Guardian set to: {}", guardian);
+        Ok(())
+    }
+
+    // Lifecycle functions
+
+    /// Admin stops new deposits (Active -> DepositsClosed)
+    pub fn close_deposits(ctx: Context<AdminAction>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        require!(config.lifecycle == VaultLifecycle::Active, VaultError::InvalidLifecycleTransition);
+
+        config.lifecycle = VaultLifecycle::DepositsClosed;
+
+        emit!(LifecycleChangedEvent {
+            from: VaultLifecycle::Active,
+            to: VaultLifecycle::DepositsClosed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Deposits closed");
+        Ok(())
+    }
+
+    /// Admin starts the wind-down (DepositsClosed -> WindingDown); no new deployments after this
+    pub fn begin_wind_down(ctx: Context<AdminAction>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        require!(
+            config.lifecycle == VaultLifecycle::DepositsClosed,
+            VaultError::InvalidLifecycleTransition
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        config.lifecycle = VaultLifecycle::WindingDown;
+        config.wind_down_started_at = now;
+
+        emit!(LifecycleChangedEvent {
+            from: VaultLifecycle::DepositsClosed,
+            to: VaultLifecycle::WindingDown,
+            timestamp: now,
+        });
+
+        msg!("Wind-down started. Capital still deployed: {}", config.total_trading_deployed);
+        Ok(())
+    }
+
+    /// User claims their final balance once all capital is back; final fee is crystallized
+    pub fn claim_final_balance(ctx: Context<Withdraw>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        require!(config.lifecycle == VaultLifecycle::WindingDown, VaultError::InvalidLifecycleTransition);
+        require!(
+            config.total_trading_deployed == 0 && config.asset_holdings_value == 0,
+            VaultError::CapitalStillDeployed
+        );
+
+        let position = ctx.accounts.user_position.load()?;
+        let (user_balance, _, _) = calculate_user_balance_internal(
+            config,
+            &position,
+            ctx.accounts.treasury_account.amount,
+        )?;
+        let shares_to_burn = position.user_shares;
+        let profit = user_balance.saturating_sub(position.high_water_mark);
+        drop(position);
+        require!(shares_to_burn > 0, VaultError::InsufficientBalance);
+
+        let fee = performance_fee(profit, config.performance_fee_bps)?;
+        let payout = user_balance - fee;
+
+        // Update user position
+        let mut position = ctx.accounts.user_position.load_mut()?;
+        position.user_shares = 0;
+        position.high_water_mark = 0;
+        position.cost_basis = 0;
+        position.lifetime_fees_paid = position.lifetime_fees_paid
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        // Update protocol totals
+        let config = &mut ctx.accounts.protocol_config;
+        config.total_shares = config.total_shares
+            .checked_sub(shares_to_burn)
+            .ok_or(VaultError::MathOverflow)?;
+        config.accumulated_fees = config.accumulated_fees
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let config_seeds: &[&[&[u8]]] = &[&[
+            b"protocol_config",
+            &[config.bump],
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.treasury_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.protocol_config.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
+        token_interface::transfer_checked(cpi_ctx, payout, ctx.accounts.token_mint.decimals)?;
+
+        emit!(FinalBalanceClaimedEvent {
+            user: ctx.accounts.owner.key(),
+            amount: payout,
+            fee,
+            shares_burned: shares_to_burn,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Final balance claimed: {}. Fee crystallized: {}", payout, fee);
+        Ok(())
+    }
+
+    /// Admin retires the vault after the grace period, sweeping unclaimed balances to escrow
+    pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        require!(ctx.accounts.admin.key() == config.admin, VaultError::UnauthorizedAdmin);
+        require!(config.lifecycle == VaultLifecycle::WindingDown, VaultError::InvalidLifecycleTransition);
+        require!(
+            config.total_trading_deployed == 0 && config.asset_holdings_value == 0,
+            VaultError::CapitalStillDeployed
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= config.wind_down_started_at + WIND_DOWN_GRACE_PERIOD,
+            VaultError::GracePeriodActive
+        );
+
+        let fees = config.accumulated_fees;
+        let unclaimed = ctx.accounts.treasury_account.amount
+            .checked_sub(fees)
+            .ok_or(VaultError::MathOverflow)?;
+        let unclaimed_shares = config.total_shares;
+
+        let config_seeds: &[&[&[u8]]] = &[&[
+            b"protocol_config",
+            &[config.bump],
+        ]];
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+
+        if fees > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.treasury_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.admin_token_account.to_account_info(),
+                authority: ctx.accounts.protocol_config.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, config_seeds);
+            token_interface::transfer_checked(cpi_ctx, fees, ctx.accounts.token_mint.decimals)?;
+        }
+
+        if unclaimed > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.treasury_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.escrow_token_account.to_account_info(),
+                authority: ctx.accounts.protocol_config.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, config_seeds);
+            token_interface::transfer_checked(cpi_ctx, unclaimed, ctx.accounts.token_mint.decimals)?;
+        }
+
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.treasury_account.to_account_info(),
+            destination: ctx.accounts.admin.to_account_info(),
+            authority: ctx.accounts.protocol_config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, config_seeds);
+        token_interface::close_account(cpi_ctx)?;
+
+        let escrow = &mut ctx.accounts.claims_escrow;
+        escrow.version = CLAIMS_ESCROW_VERSION;
+        escrow.remaining_shares = unclaimed_shares;
+        escrow.remaining_amount = unclaimed;
+        escrow.closed_at = now;
+        escrow.bump = ctx.bumps.claims_escrow;
+
+        // The config stays behind as Closed so the vault can't be initialized again
+        let config = &mut ctx.accounts.protocol_config;
+        config.lifecycle = VaultLifecycle::Closed;
+        config.accumulated_fees = 0;
+
+        emit!(LifecycleChangedEvent {
+            from: VaultLifecycle::WindingDown,
+            to: VaultLifecycle::Closed,
+            timestamp: now,
+        });
+        emit!(VaultClosedEvent {
+            fees_collected: fees,
+            unclaimed_amount: unclaimed,
+            unclaimed_shares,
+            timestamp: now,
+        });
+
+        msg!("Vault closed. Swept {} for {} unclaimed shares to escrow", unclaimed, unclaimed_shares);
+        Ok(())
+    }
+
+    /// User redeems leftover shares from the claims escrow of a closed vault; final fee is crystallized
+    pub fn claim_from_escrow(ctx: Context<ClaimFromEscrow>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        require!(config.lifecycle == VaultLifecycle::Closed, VaultError::InvalidLifecycleTransition);
+        let escrow = &ctx.accounts.claims_escrow;
+        let mut position = ctx.accounts.user_position.load_mut()?;
+        let shares = position.user_shares;
+        require!(shares > 0, VaultError::InsufficientBalance);
+
+        let amount = mul_div(escrow.remaining_amount, shares, escrow.remaining_shares, Rounding::Down)?;
+        // Same fee `claim_final_balance` would have charged before the sweep
+        let profit = amount.saturating_sub(position.high_water_mark);
+        let fee = performance_fee(profit, config.performance_fee_bps)?;
+        let payout = amount - fee;
+
+        position.user_shares = 0;
+        position.high_water_mark = 0;
+        position.cost_basis = 0;
+        position.lifetime_fees_paid = position.lifetime_fees_paid
+            .checked_add(fee)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let escrow_seeds: &[&[&[u8]]] = &[&[
+            b"claims_escrow",
+            &[escrow.bump],
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.escrow_token_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.claims_escrow.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program.clone(), cpi_accounts, escrow_seeds);
+        token_interface::transfer_checked(cpi_ctx, payout, ctx.accounts.token_mint.decimals)?;
+
+        if fee > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.escrow_token_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.admin_token_account.to_account_info(),
+                authority: ctx.accounts.claims_escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, escrow_seeds);
+            token_interface::transfer_checked(cpi_ctx, fee, ctx.accounts.token_mint.decimals)?;
+        }
+
+        let escrow = &mut ctx.accounts.claims_escrow;
+        escrow.remaining_shares = escrow.remaining_shares
+            .checked_sub(shares)
+            .ok_or(VaultError::MathOverflow)?;
+        escrow.remaining_amount = escrow.remaining_amount
+            .checked_sub(amount)
+            .ok_or(VaultError::MathOverflow)?;
+
+        emit!(EscrowClaimedEvent {
+            user: ctx.accounts.owner.key(),
+            amount: payout,
+            fee,
+            shares_burned: shares,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Claimed {} from escrow for {} shares. Fee crystallized: {}", payout, shares, fee);
+        Ok(())
+    }
+}
+
+// ============ CONTEXTS ============
+
+#[derive(Accounts)]
+pub struct InitializeProtocol<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = ProtocolConfig::LEN,
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = token_mint,
+        token::authority = protocol_config,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateUserPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = UserPosition::LEN,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    /// CHECK: Owner's attestation PDA, read by `check_access` when it exists
+    #[account(
+        seeds = [b"attestation", owner.key().as_ref()],
+        bump
+    )]
+    pub attestation: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUserPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump,
+        close = owner
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = owner)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Owner's attestation PDA, read by `check_access` when it exists
+    #[account(
+        seeds = [b"attestation", owner.key().as_ref()],
+        bump
+    )]
+    pub attestation: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DepositFor<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Position owner receiving the shares; only used to derive PDAs
+    pub beneficiary: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", beneficiary.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = payer)]
+    pub payer_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Beneficiary's attestation PDA, read by `check_access` when it exists
+    #[account(
+        seeds = [b"attestation", beneficiary.key().as_ref()],
+        bump
+    )]
+    pub attestation: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = owner)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump,
+        constraint = treasury_account.mint == native_mint.key() @ VaultError::NotNativeVault
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    /// Transient wSOL account, created and closed within the instruction
+    #[account(
+        init,
+        payer = owner,
+        token::mint = native_mint,
+        token::authority = owner,
+        seeds = [b"wsol", owner.key().as_ref()],
+        bump
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Owner's attestation PDA, read by `check_access` when it exists
+    #[account(
+        seeds = [b"attestation", owner.key().as_ref()],
+        bump
+    )]
+    pub attestation: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump,
+        constraint = treasury_account.mint == native_mint.key() @ VaultError::NotNativeVault
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    /// Transient wSOL account, created and closed within the instruction
+    #[account(
+        init,
+        payer = owner,
+        token::mint = native_mint,
+        token::authority = owner,
+        seeds = [b"wsol", owner.key().as_ref()],
+        bump
+    )]
+    pub wsol_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = spl_token::native_mint::ID)]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositAsset<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"accepted_asset", asset_mint.key().as_ref()],
+        bump = accepted_asset.bump
+    )]
+    pub accepted_asset: Account<'info, AcceptedAsset>,
+    #[account(
+        mut,
+        seeds = [b"asset_treasury", asset_mint.key().as_ref()],
+        bump
+    )]
+    pub asset_treasury: InterfaceAccount<'info, TokenAccount>,
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = owner)]
+    pub user_asset_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Owner's attestation PDA, read by `check_access` when it exists
+    #[account(
+        seeds = [b"attestation", owner.key().as_ref()],
+        bump
+    )]
+    pub attestation: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AddAcceptedAsset<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = asset_mint.key() != treasury_account.mint @ VaultError::InvalidAccounts)]
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        space = AcceptedAsset::LEN,
+        seeds = [b"accepted_asset", asset_mint.key().as_ref()],
+        bump
+    )]
+    pub accepted_asset: Account<'info, AcceptedAsset>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = asset_mint,
+        token::authority = protocol_config,
+        seeds = [b"asset_treasury", asset_mint.key().as_ref()],
+        bump
+    )]
+    pub asset_treasury: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureAcceptedAsset<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"accepted_asset", accepted_asset.mint.as_ref()],
+        bump = accepted_asset.bump
+    )]
+    pub accepted_asset: Account<'info, AcceptedAsset>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAssetPrice<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"accepted_asset", accepted_asset.mint.as_ref()],
+        bump = accepted_asset.bump
+    )]
+    pub accepted_asset: Account<'info, AcceptedAsset>,
+    #[account(
+        seeds = [b"asset_treasury", accepted_asset.mint.as_ref()],
+        bump
+    )]
+    pub asset_treasury: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SweepAsset<'info> {
+    pub trading_bot: Signer<'info>,
+    #[account(
         mut,
         seeds = [b"protocol_config"],
         bump = protocol_config.bump
@@ -797,13 +2763,95 @@ pub struct Withdraw<'info> {
     pub protocol_config: Account<'info, ProtocolConfig>,
     #[account(
         mut,
+        seeds = [b"accepted_asset", asset_mint.key().as_ref()],
+        bump = accepted_asset.bump
+    )]
+    pub accepted_asset: Account<'info, AcceptedAsset>,
+    #[account(
+        mut,
+        seeds = [b"asset_treasury", asset_mint.key().as_ref()],
+        bump
+    )]
+    pub asset_treasury: InterfaceAccount<'info, TokenAccount>,
+    pub asset_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = trading_bot)]
+    pub trading_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetWithdrawalDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = WithdrawalDelegate::LEN,
+        seeds = [b"withdrawal_delegate", owner.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_delegate: Account<'info, WithdrawalDelegate>,
+    #[account(
         seeds = [b"treasury"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
-    #[account(mut, token::authority = owner)]
-    pub user_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = destination.mint == treasury_account.mint @ VaultError::InvalidDestination)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeWithdrawalDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"withdrawal_delegate", owner.key().as_ref()],
+        bump = withdrawal_delegate.bump,
+        close = owner
+    )]
+    pub withdrawal_delegate: Account<'info, WithdrawalDelegate>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawAsDelegate<'info> {
+    pub delegate: Signer<'info>,
+    /// CHECK: Position owner; only used to derive PDAs
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"withdrawal_delegate", owner.key().as_ref()],
+        bump = withdrawal_delegate.bump
+    )]
+    pub withdrawal_delegate: Account<'info, WithdrawalDelegate>,
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -820,10 +2868,12 @@ pub struct DeployCapital<'info> {
         seeds = [b"treasury"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
     #[account(mut, token::authority = trading_bot)]
-    pub trading_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub trading_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -840,10 +2890,27 @@ pub struct ReturnCapital<'info> {
         seeds = [b"treasury"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
     #[account(mut, token::authority = trading_bot)]
-    pub trading_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub trading_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CheckBuffer<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
@@ -864,7 +2931,7 @@ pub struct CalculateBalance<'info> {
         seeds = [b"treasury"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
@@ -875,22 +2942,223 @@ pub struct CollectUserFees<'info> {
     #[account(mut)]
     pub user_position: AccountLoader<'info, UserPosition>,
     #[account(mut)]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CollectBatchFees<'info> {
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    // remaining_accounts: UserPosition PDAs
+}
+
+#[derive(Accounts)]
+pub struct UpdateValuation<'info> {
+    pub trading_bot: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct GetProtocolStats<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSharePriceHistory<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        init,
+        payer = admin,
+        space = SharePriceHistory::LEN,
+        seeds = [b"share_price_history"],
+        bump
+    )]
+    pub share_price_history: AccountLoader<'info, SharePriceHistory>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Snapshot<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"share_price_history"],
+        bump
+    )]
+    pub share_price_history: AccountLoader<'info, SharePriceHistory>,
+}
+
+#[derive(Accounts)]
+pub struct GetSharePriceReturns<'info> {
+    #[account(
+        seeds = [b"share_price_history"],
+        bump
+    )]
+    pub share_price_history: AccountLoader<'info, SharePriceHistory>,
+}
+
+#[derive(Accounts)]
+pub struct GetUserStats<'info> {
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    /// CHECK: Owner pubkey
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct Preview<'info> {
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewLimits<'info> {
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    /// CHECK: Owner pubkey
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewPosition<'info> {
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    /// CHECK: Owner pubkey
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct GetUnrealizedPnl<'info> {
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    /// CHECK: Owner pubkey
+    pub owner: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-pub struct CollectBatchFees<'info> {
-    pub caller: Signer<'info>,
-    #[account(mut)]
-    pub protocol_config: Account<'info, ProtocolConfig>,
-    #[account(mut)]
-    pub treasury_account: Account<'info, TokenAccount>,
-    // remaining_accounts: UserPosition PDAs
+pub struct CheckFeeEligibility<'info> {
+    #[account(
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: AccountLoader<'info, UserPosition>,
+    /// CHECK: Owner pubkey
+    pub owner: UncheckedAccount<'info>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateValuation<'info> {
-    pub trading_bot: Signer<'info>,
+pub struct PauseAction<'info> {
+    pub authority: Signer<'info>,
     #[account(
         mut,
         seeds = [b"protocol_config"],
@@ -900,73 +3168,145 @@ pub struct UpdateValuation<'info> {
 }
 
 #[derive(Accounts)]
-pub struct CollectFees<'info> {
+pub struct AdminAction<'info> {
     pub admin: Signer<'info>,
     #[account(mut)]
     pub protocol_config: Account<'info, ProtocolConfig>,
-    #[account(mut)]
-    pub treasury_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub admin_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct GetProtocolStats<'info> {
+pub struct CloseVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
     #[account(
+        mut,
         seeds = [b"protocol_config"],
         bump = protocol_config.bump
     )]
     pub protocol_config: Account<'info, ProtocolConfig>,
     #[account(
+        mut,
         seeds = [b"treasury"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub treasury_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = ClaimsEscrow::LEN,
+        seeds = [b"claims_escrow"],
+        bump
+    )]
+    pub claims_escrow: Account<'info, ClaimsEscrow>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = token_mint,
+        token::authority = claims_escrow,
+        seeds = [b"claims_escrow_vault"],
+        bump
+    )]
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = treasury_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct GetUserStats<'info> {
+pub struct ClaimFromEscrow<'info> {
+    pub owner: Signer<'info>,
     #[account(
+        mut,
         seeds = [b"user_position", owner.key().as_ref()],
         bump
     )]
     pub user_position: AccountLoader<'info, UserPosition>,
-    /// CHECK: Owner pubkey
-    pub owner: UncheckedAccount<'info>,
     #[account(
         seeds = [b"protocol_config"],
         bump = protocol_config.bump
     )]
     pub protocol_config: Account<'info, ProtocolConfig>,
     #[account(
-        seeds = [b"treasury"],
+        mut,
+        seeds = [b"claims_escrow"],
+        bump = claims_escrow.bump
+    )]
+    pub claims_escrow: Account<'info, ClaimsEscrow>,
+    #[account(
+        mut,
+        seeds = [b"claims_escrow_vault"],
         bump
     )]
-    pub treasury_account: Account<'info, TokenAccount>,
+    pub escrow_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = escrow_token_account.mint)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::authority = owner)]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the crystallized performance fee
+    #[account(mut, token::mint = token_mint, token::authority = protocol_config.admin)]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct CheckFeeEligibility<'info> {
+#[instruction(user: Pubkey)]
+pub struct ManageAttestation<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
     #[account(
-        seeds = [b"user_position", owner.key().as_ref()],
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = Attestation::LEN,
+        seeds = [b"attestation", user.as_ref()],
         bump
     )]
-    pub user_position: AccountLoader<'info, UserPosition>,
-    /// CHECK: Owner pubkey
-    pub owner: UncheckedAccount<'info>,
+    pub attestation: Account<'info, Attestation>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct AdminAction<'info> {
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
     pub admin: Signer<'info>,
+    /// CHECK: Loaded raw so older layouts can be read; seeds, owner and discriminator are verified
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump,
+        owner = crate::ID
+    )]
+    pub protocol_config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
     #[account(mut)]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub owner: Signer<'info>,
+    /// CHECK: Loaded raw so older layouts can be read; seeds, owner and discriminator are verified
+    #[account(
+        mut,
+        seeds = [b"user_position", owner.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub user_position: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 // ============ STATE STRUCTS ============
 
 #[account]
+#[derive(InitSpace)]
 pub struct ProtocolConfig {
     pub admin: Pubkey,
     pub trading_bot: Pubkey,
@@ -975,16 +3315,119 @@ pub struct ProtocolConfig {
     pub total_trading_deployed: u64,
     pub accumulated_fees: u64,
     pub performance_fee_bps: u16,
-    pub is_paused: bool,
+    pub pause_flags: u8,                  // PAUSE_* bitset
     pub bump: u8,
     pub last_fee_sweep: i64,
     pub deployed_current_value: u64,      // Current market value of deployed capital
     pub last_valuation_timestamp: i64,    // When valuation was last updated
     pub pending_unrealized_fees: u64,     // Performance fees on unrealized gains
+    pub version: u8,                      // Layout version, 0 for pre-versioning accounts
+    // New fields go below `version` so every layout keeps it at VERSION_OFFSET
+    pub guardian: Pubkey,                 // May pause but not unpause (v2)
+    pub emergency_mode: bool,             // Pro-rata exits against the treasury only (v3)
+    pub lifecycle: VaultLifecycle,        // (v4)
+    pub wind_down_started_at: i64,        // Start of the close_vault grace period (v4)
+    pub max_total_deposits: u64,          // Vault-wide TVL cap, 0 = unlimited (v5)
+    pub max_position_size: u64,           // Per-user value cap, 0 = unlimited (v5)
+    pub min_deposit: u64,                 // Minimum deposit amount (v5)
+    pub access_mode: AccessMode,          // (v6)
+    pub merkle_root: [u8; 32],            // Allowlist root for Restricted mode (v6)
+    pub asset_holdings_value: u64,        // Sum of AcceptedAsset holdings_value (v7)
+    pub buffer_deficit_since: i64,        // When check_buffer found the treasury below target, 0 = none (v8)
+    pub asset_prices_updated_at: i64,     // Oldest price behind asset_holdings_value, or older (v9)
 }
 
 impl ProtocolConfig {
-    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 1 + 1 + 8 + 8 + 8 + 8 + 32; // Disc + fields + padding
+    pub const LEN: usize = 8 + ProtocolConfig::INIT_SPACE;
+    /// Offset of `version`; v0 accounts carry zeroed padding here
+    pub const VERSION_OFFSET: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 1 + 1 + 8 + 8 + 8 + 8;
+
+    pub fn is_paused(&self, operations: u8) -> bool {
+        self.pause_flags & operations != 0
+    }
+}
+
+/// Vault lifecycle; transitions only move forward
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum VaultLifecycle {
+    Active,
+    DepositsClosed,
+    WindingDown,
+    Closed,
+}
+
+/// Who may create positions and deposit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum AccessMode {
+    Open,
+    Restricted, // Attestation PDA or Merkle proof against `merkle_root`
+}
+
+/// Admin-issued entry permit for a restricted vault
+#[account]
+#[derive(InitSpace)]
+pub struct Attestation {
+    pub version: u8,
+    pub user: Pubkey,
+    pub issued_at: i64,
+    pub expires_at: i64, // 0 = no expiry
+    pub revoked: bool,
+    pub bump: u8,
+}
+
+impl Attestation {
+    pub const LEN: usize = 8 + Attestation::INIT_SPACE;
+}
+
+/// Additional deposit mint, valued into the base asset by price
+#[account]
+#[derive(InitSpace)]
+pub struct AcceptedAsset {
+    pub version: u8,
+    pub mint: Pubkey,
+    pub treasury: Pubkey,        // Sub-account holding this asset
+    pub decimals: u8,
+    pub oracle: Pubkey,          // Trusted key that may push prices besides the admin; default = admin only
+    pub price: u64,              // Base-asset units per whole token
+    pub price_updated_at: i64,
+    pub haircut_bps: u16,        // Discount on deposited value
+    pub holdings_value: u64,     // Last marked value of `treasury`, included in NAV
+    pub enabled: bool,
+    pub bump: u8,
+}
+
+impl AcceptedAsset {
+    pub const LEN: usize = 8 + AcceptedAsset::INIT_SPACE;
+}
+
+/// Owner-approved third party that may redeem into a fixed destination
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawalDelegate {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub destination: Pubkey, // Token account all delegated withdrawals pay into
+    pub bump: u8,
+}
+
+impl WithdrawalDelegate {
+    pub const LEN: usize = 8 + WithdrawalDelegate::INIT_SPACE;
+}
+
+/// Holds balances left unclaimed when a vault is closed
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimsEscrow {
+    pub version: u8,
+    pub remaining_shares: u64,
+    pub remaining_amount: u64,
+    pub closed_at: i64,
+    pub bump: u8,
+}
+
+impl ClaimsEscrow {
+    pub const LEN: usize = 8 + ClaimsEscrow::INIT_SPACE;
 }
 
 #[account(zero_copy)]
@@ -992,17 +3435,64 @@ impl ProtocolConfig {
 #[derive(Debug)]
 pub struct UserPosition {
     pub owner: Pubkey,
-    pub deposited_amount: u64,
+    /// Average-cost basis of the shares held; deposits add to it, withdrawals release it pro
+    /// rata. Renamed from `deposited_amount`, which held total deposits; same offset.
+    /// `migrate_position` rebuilds it for v0 positions.
+    pub cost_basis: u64,
     pub user_shares: u64,
     pub high_water_mark: u64,
     pub last_fee_collection: i64,
     pub lifetime_fees_paid: u64,
     pub is_active: u8, // 0 = false, 1 = true for zero-copy compatibility
-    pub _padding: [u8; 7], // Padding for alignment
+    pub version: u8, // Layout version, 0 for pre-versioning accounts
+    pub _padding: [u8; 6], // Padding for alignment
 }
 
 impl UserPosition {
-    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 7; // Disc + fields + 7 bytes padding for alignment
+    pub const LEN: usize = 8 + std::mem::size_of::<UserPosition>();
+    /// Offset of `version`; v0 accounts carry zeroed padding here
+    pub const VERSION_OFFSET: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Price per share one snapshot interval apart, oldest overwritten once full
+#[account(zero_copy)]
+#[repr(C)]
+#[derive(Debug)]
+pub struct SharePriceHistory {
+    pub head: u32, // Slot the next snapshot is written to
+    pub count: u32, // Slots filled, up to SHARE_PRICE_HISTORY_CAPACITY
+    pub version: u8,
+    pub bump: u8,
+    pub _padding: [u8; 6],
+    pub entries: [SharePriceEntry; SHARE_PRICE_HISTORY_CAPACITY],
+}
+
+#[zero_copy]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SharePriceEntry {
+    pub timestamp: i64,
+    pub price_per_share: u64, // Per SHARE_PRICE_SCALE shares
+}
+
+impl SharePriceHistory {
+    pub const LEN: usize = 8 + std::mem::size_of::<SharePriceHistory>();
+
+    pub fn push(&mut self, timestamp: i64, price_per_share: u64) {
+        self.entries[self.head as usize] = SharePriceEntry { timestamp, price_per_share };
+        self.head = ((self.head as usize + 1) % SHARE_PRICE_HISTORY_CAPACITY) as u32;
+        self.count = (self.count + 1).min(SHARE_PRICE_HISTORY_CAPACITY as u32);
+    }
+
+    pub fn latest(&self) -> Option<SharePriceEntry> {
+        self.newest_first().next()
+    }
+
+    /// Snapshots from the most recent back to the oldest still held
+    pub fn newest_first(&self) -> impl Iterator<Item = SharePriceEntry> + '_ {
+        (1..=self.count as usize).map(move |age| {
+            self.entries[(self.head as usize + SHARE_PRICE_HISTORY_CAPACITY - age) % SHARE_PRICE_HISTORY_CAPACITY]
+        })
+    }
 }
 
 // ============ EVENTS ============
@@ -1010,6 +3500,7 @@ impl UserPosition {
 #[event]
 pub struct DepositEvent {
     pub user: Pubkey,
+    pub payer: Pubkey,
     pub amount: u64,
     pub shares_minted: u64,
     pub treasury_balance: u64,
@@ -1018,10 +3509,194 @@ pub struct DepositEvent {
 
 #[event]
 pub struct WithdrawEvent {
+    pub user: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub shares_burned: u64,
+    pub remaining_shares: u64,
+    pub cost_basis_released: u64,
+    pub realized_pnl: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AssetDepositEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub value: u64, // Base-asset value credited after the haircut
+    pub shares_minted: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AcceptedAssetUpdatedEvent {
+    pub mint: Pubkey,
+    pub oracle: Pubkey,
+    pub haircut_bps: u16,
+    pub enabled: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AssetPriceUpdatedEvent {
+    pub mint: Pubkey,
+    pub price: u64,
+    pub holdings_value: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AssetSweptEvent {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub value: u64,
+    pub total_deployed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalDelegateSetEvent {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub destination: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalDelegateRevokedEvent {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserPositionClosedEvent {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub lamports_reclaimed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolPausedEvent {
+    pub authority: Pubkey,
+    pub operations: u8,
+    pub pause_flags: u8,
+    pub reason_code: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolUnpausedEvent {
+    pub authority: Pubkey,
+    pub operations: u8,
+    pub pause_flags: u8,
+    pub reason_code: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GuardianUpdatedEvent {
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyModeEnteredEvent {
+    pub authority: Pubkey,
+    pub reason_code: u16,
+    pub total_deployed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyModeExitedEvent {
+    pub authority: Pubkey,
+    pub reason_code: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyWithdrawEvent {
     pub user: Pubkey,
     pub amount: u64,
+    /// Base-asset value of accepted assets paid out in kind
+    pub asset_value: u64,
     pub shares_burned: u64,
     pub remaining_shares: u64,
+    pub cost_basis_released: u64,
+    pub realized_pnl: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LifecycleChangedEvent {
+    pub from: VaultLifecycle,
+    pub to: VaultLifecycle,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FinalBalanceClaimedEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub shares_burned: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VaultClosedEvent {
+    pub fees_collected: u64,
+    pub unclaimed_amount: u64,
+    pub unclaimed_shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowClaimedEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub shares_burned: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositLimitsUpdatedEvent {
+    pub max_total_deposits: u64,
+    pub max_position_size: u64,
+    pub min_deposit: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccessModeUpdatedEvent {
+    pub access_mode: AccessMode,
+    pub merkle_root: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AttestationIssuedEvent {
+    pub user: Pubkey,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccessRevokedEvent {
+    pub user: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccountMigratedEvent {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
     pub timestamp: i64,
 }
 
@@ -1033,6 +3708,15 @@ pub struct CapitalDeployedEvent {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct LiquidityBufferCheckedEvent {
+    pub treasury_balance: u64,
+    pub target: u64,
+    pub shortfall: u64,
+    pub deficit_since: i64, // 0 once the buffer is at target
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct CapitalReturnedEvent {
     pub amount: u64,
@@ -1055,6 +3739,14 @@ pub struct FeesWithdrawnEvent {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct SharePriceSnapshotEvent {
+    pub price_per_share: u64,
+    pub total_shares: u64,
+    pub nav: u64,
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct ValuationUpdateEvent {
     pub total_deployed_original: u64,
@@ -1103,4 +3795,56 @@ pub enum VaultError {
     StaleValuation,
     #[msg("Invalid valuation data")]
     InvalidValuation,
+    #[msg("Position still holds shares")]
+    PositionNotEmpty,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Emergency mode is active")]
+    EmergencyModeActive,
+    #[msg("Protocol is not in emergency mode")]
+    NotInEmergencyMode,
+    #[msg("Vault is not accepting deposits")]
+    DepositsClosed,
+    #[msg("Vault is winding down")]
+    VaultWindingDown,
+    #[msg("Invalid lifecycle transition")]
+    InvalidLifecycleTransition,
+    #[msg("Capital is still deployed")]
+    CapitalStillDeployed,
+    #[msg("Wind-down grace period has not elapsed")]
+    GracePeriodActive,
+    #[msg("Deposit is below the minimum size")]
+    DepositBelowMinimum,
+    #[msg("Deposit would exceed the vault cap")]
+    VaultCapExceeded,
+    #[msg("Deposit would exceed the per-user position limit")]
+    PositionLimitExceeded,
+    #[msg("Access denied: attestation or allowlist proof required")]
+    AccessDenied,
+    #[msg("Access has been revoked")]
+    AccessRevoked,
+    #[msg("Signer is not the registered withdrawal delegate")]
+    UnauthorizedDelegate,
+    #[msg("Destination is not the approved token account")]
+    InvalidDestination,
+    #[msg("Vault treasury is not wrapped SOL")]
+    NotNativeVault,
+    #[msg("Accepted asset is disabled")]
+    AssetDisabled,
+    #[msg("Accepted asset price is stale")]
+    AssetPriceStale,
+    #[msg("Price must be greater than zero")]
+    InvalidPrice,
+    #[msg("Haircut must be below 10000 bps")]
+    InvalidHaircut,
+    #[msg("Signer is not the asset oracle or admin")]
+    UnauthorizedOracle,
+    #[msg("Share price snapshot too soon")]
+    SnapshotTooSoon,
+    #[msg("Return windows must be positive and at most MAX_RETURN_WINDOWS")]
+    InvalidReturnWindow,
+    #[msg("Treasury liquidity buffer is below target")]
+    LiquidityBufferDeficit,
 }
\ No newline at end of file
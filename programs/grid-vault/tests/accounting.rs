@@ -0,0 +1,332 @@
+use anchor_lang::prelude::Pubkey;
+use grid_vault::{
+    accounting::{
+        annualized_return_bps, apply_deposit, apply_withdraw, charge_performance_fee, convert_to_assets,
+        convert_to_shares, mul_div, user_balance, vault_value_pool, window_return, DepositTarget, Rounding,
+    },
+    AccessMode, ProtocolConfig, SharePriceHistory, UserPosition, VaultError, VaultLifecycle, PROTOCOL_CONFIG_VERSION,
+    SECONDS_PER_YEAR, SHARE_PRICE_HISTORY_CAPACITY, SHARE_PRICE_SCALE, STALE_VALUATION_THRESHOLD,
+};
+use proptest::prelude::*;
+
+const USERS: usize = 4;
+const NOW: i64 = 1_700_000_000;
+
+fn config(total_shares: u64, accumulated_fees: u64) -> ProtocolConfig {
+    ProtocolConfig {
+        admin: Pubkey::default(),
+        trading_bot: Pubkey::default(),
+        treasury: Pubkey::default(),
+        total_shares,
+        total_trading_deployed: 0,
+        accumulated_fees,
+        performance_fee_bps: 2500,
+        pause_flags: 0,
+        bump: 255,
+        last_fee_sweep: 0,
+        deployed_current_value: 0,
+        last_valuation_timestamp: 0,
+        pending_unrealized_fees: 0,
+        version: PROTOCOL_CONFIG_VERSION,
+        guardian: Pubkey::default(),
+        emergency_mode: false,
+        lifecycle: VaultLifecycle::Active,
+        wind_down_started_at: 0,
+        max_total_deposits: 0,
+        max_position_size: 0,
+        min_deposit: 0,
+        access_mode: AccessMode::Open,
+        merkle_root: [0; 32],
+        asset_holdings_value: 0,
+        buffer_deficit_since: 0,
+        asset_prices_updated_at: 0,
+    }
+}
+
+/// One holder owning `shares` of the vault, bought for `cost`
+fn position(shares: u64, cost: u64) -> UserPosition {
+    UserPosition {
+        user_shares: shares,
+        cost_basis: cost,
+        high_water_mark: cost,
+        ..bytemuck::Zeroable::zeroed()
+    }
+}
+
+/// `a / b >= c / d` without rounding
+fn ratio_at_least(a: u64, b: u64, c: u64, d: u64) -> bool {
+    a as u128 * d as u128 >= c as u128 * b as u128
+}
+
+/// The vault as the instructions see it: the treasury holds everything, nothing is deployed
+#[derive(Clone)]
+struct Vault {
+    config: ProtocolConfig,
+    positions: [UserPosition; USERS],
+    treasury: u64,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit { user: usize, amount: u64 },
+    Withdraw { user: usize, bps: u64 },
+    Redeem { user: usize, bps: u64 },
+    Gain(u64),
+    Loss(u64),
+    CollectFee { user: usize },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let user = 0..USERS;
+    prop_oneof![
+        (user.clone(), 1..1_000_000_000_000u64).prop_map(|(user, amount)| Op::Deposit { user, amount }),
+        (user.clone(), 1..=10_000u64).prop_map(|(user, bps)| Op::Withdraw { user, bps }),
+        (user.clone(), 1..=10_000u64).prop_map(|(user, bps)| Op::Redeem { user, bps }),
+        (0..1_000_000_000_000u64).prop_map(Op::Gain),
+        (0..1_000_000_000_000u64).prop_map(Op::Loss),
+        user.prop_map(|user| Op::CollectFee { user }),
+    ]
+}
+
+impl Vault {
+    fn balance(&self, user: usize) -> anchor_lang::Result<(u64, u64, u64)> {
+        user_balance(&self.config, &self.positions[user], self.treasury, NOW)
+    }
+
+    /// Applies `op` the way its instruction would; a rejected or impossible op leaves the vault
+    /// untouched. Harness arithmetic is checked so such ops are skipped rather than panicking.
+    fn apply(&mut self, op: &Op) {
+        let mut next = self.clone();
+        if next.try_apply(op).is_ok() {
+            *self = next;
+        }
+    }
+
+    fn try_apply(&mut self, op: &Op) -> anchor_lang::Result<()> {
+        match *op {
+            Op::Deposit { user, amount } => {
+                let (balance, pool, total) = self.balance(user)?;
+                let target = DepositTarget::MinShares(0);
+                apply_deposit(&mut self.config, &mut self.positions[user], balance, pool, total, amount, target)?;
+                self.treasury = self.treasury.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+            }
+            Op::Withdraw { user, bps } => {
+                let (balance, pool, _) = self.balance(user)?;
+                let amount = mul_div(balance, bps, 10_000, Rounding::Down)?;
+                let shares = convert_to_shares(&self.config, pool, amount, Rounding::Up)?;
+                apply_withdraw(&mut self.config, &mut self.positions[user], amount, shares)?;
+                self.treasury = self.treasury.checked_sub(amount).ok_or(VaultError::InsufficientLiquidity)?;
+            }
+            Op::Redeem { user, bps } => {
+                let (_, pool, _) = self.balance(user)?;
+                let shares = mul_div(self.positions[user].user_shares, bps, 10_000, Rounding::Down)?;
+                let amount = convert_to_assets(&self.config, pool, shares, Rounding::Down)?;
+                apply_withdraw(&mut self.config, &mut self.positions[user], amount, shares)?;
+                self.treasury = self.treasury.checked_sub(amount).ok_or(VaultError::InsufficientLiquidity)?;
+            }
+            Op::Gain(amount) => {
+                self.treasury = self.treasury.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+            }
+            Op::Loss(amount) => {
+                // Trading losses can take everything except fees already earned
+                let available =
+                    self.treasury.checked_sub(self.config.accumulated_fees).ok_or(VaultError::MathOverflow)?;
+                self.treasury -= amount.min(available);
+            }
+            Op::CollectFee { user } => {
+                let (balance, pool, _) = self.balance(user)?;
+                charge_performance_fee(&mut self.config, &mut self.positions[user], balance, pool)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+proptest! {
+    #[test]
+    fn share_accounting_holds_across_operation_sequences(ops in prop::collection::vec(op(), 1..60)) {
+        let mut vault = Vault { config: config(0, 0), positions: [position(0, 0); USERS], treasury: 0 };
+        for op in &ops {
+            vault.apply(op);
+
+            // Every share is owned by exactly one position
+            let shares: u64 = vault.positions.iter().map(|p| p.user_shares).sum();
+            prop_assert_eq!(shares, vault.config.total_shares);
+
+            // Earned fees stay in the treasury and holders' claims fit in what is left
+            prop_assert!(vault.config.accumulated_fees <= vault.treasury);
+            let (pool, _) = vault_value_pool(&vault.config, vault.treasury, NOW).unwrap();
+            let claims: u64 = (0..USERS).map(|user| vault.balance(user).unwrap().0).sum();
+            prop_assert!(claims <= pool, "claims {} exceed pool {}", claims, pool);
+        }
+    }
+
+    #[test]
+    fn deposit_then_exit_never_profits(
+        total_shares in 1..1_000_000_000_000_000u64,
+        treasury in 1..1_000_000_000_000_000u64,
+        amount in 1..1_000_000_000_000u64,
+    ) {
+        let mut config = config(total_shares, 0);
+        let mut user = position(0, 0);
+        let (pool, total) = vault_value_pool(&config, treasury, NOW).unwrap();
+        let Ok(minted) = apply_deposit(&mut config, &mut user, 0, pool, total, amount, DepositTarget::MinShares(0)) else {
+            return Ok(());
+        };
+        let treasury = treasury + amount;
+
+        // Redeeming every minted share pays back at most the deposit
+        let (pool_after, _) = vault_value_pool(&config, treasury, NOW).unwrap();
+        let payout = convert_to_assets(&config, pool_after, minted, Rounding::Down).unwrap();
+        prop_assert!(payout <= amount);
+
+        // Withdrawing the full deposit back must burn at least what was minted
+        let burn = convert_to_shares(&config, pool_after, amount, Rounding::Up).unwrap();
+        prop_assert!(burn >= minted);
+
+        // The round trip leaves the remaining holders' share price no lower than before
+        if payout > 0 {
+            apply_withdraw(&mut config, &mut user, payout, minted).unwrap();
+            prop_assert_eq!(config.total_shares, total_shares);
+            prop_assert!(ratio_at_least(treasury - payout, total_shares, pool, total_shares));
+        }
+    }
+
+    #[test]
+    fn depositor_gets_at_most_deposit_plus_share_of_pnl(
+        total_shares in 1..1_000_000_000_000_000u64,
+        treasury in 1..1_000_000_000_000_000u64,
+        amount in 1..1_000_000_000_000u64,
+        pnl in -1_000_000_000_000i64..1_000_000_000_000i64,
+    ) {
+        let mut config = config(total_shares, 0);
+        let mut user = position(0, 0);
+        let (pool, total) = vault_value_pool(&config, treasury, NOW).unwrap();
+        prop_assume!(apply_deposit(&mut config, &mut user, 0, pool, total, amount, DepositTarget::MinShares(0)).is_ok());
+
+        let pool_after_deposit = treasury + amount;
+        let marked = (pool_after_deposit as i128 + pnl as i128).max(0) as u64;
+        let (balance, _, _) = user_balance(&config, &user, marked, NOW).unwrap();
+
+        // balance <= amount * marked / pool_after_deposit: the user's slice of the pool never grows
+        prop_assert!(balance as u128 * pool_after_deposit as u128 <= amount as u128 * marked as u128);
+    }
+
+    #[test]
+    fn performance_fee_does_not_dilute_other_holders(
+        user_shares in 1..1_000_000_000_000u64,
+        other_shares in 1..1_000_000_000_000u64,
+        treasury in 1..1_000_000_000_000_000u64,
+        high_water_mark in 0..1_000_000_000_000u64,
+    ) {
+        let total_shares = user_shares + other_shares;
+        let mut config = config(total_shares, 0);
+        let mut user = position(user_shares, high_water_mark);
+        let (balance, pool, _) = user_balance(&config, &user, treasury, NOW).unwrap();
+
+        let (fee, shares_reduced) = charge_performance_fee(&mut config, &mut user, balance, pool).unwrap();
+        prop_assert!(fee <= balance.saturating_sub(high_water_mark) / 4);
+        prop_assert_eq!(config.total_shares, total_shares - shares_reduced);
+        prop_assert_eq!(config.accumulated_fees, fee);
+
+        // Price per share after the fee, for everyone left holding shares
+        let (pool_after, _) = vault_value_pool(&config, treasury, NOW).unwrap();
+        prop_assert!(ratio_at_least(pool_after, config.total_shares, pool, total_shares));
+        // The payer's remaining balance is what they had less the fee
+        let (balance_after, _, _) = user_balance(&config, &user, treasury, NOW).unwrap();
+        prop_assert!(balance_after <= balance - fee);
+    }
+
+    #[test]
+    fn valuations_count_until_stale(
+        deployed in 1..1_000_000_000_000u64,
+        marked in 0..1_000_000_000_000u64,
+        age in 0..3 * STALE_VALUATION_THRESHOLD,
+    ) {
+        let mut config = config(1, 0);
+        config.total_trading_deployed = deployed;
+        config.deployed_current_value = marked;
+        config.last_valuation_timestamp = NOW - age;
+
+        let (_, total) = vault_value_pool(&config, 0, NOW).unwrap();
+        let expected = if age <= STALE_VALUATION_THRESHOLD { marked } else { deployed };
+        prop_assert_eq!(total, expected);
+    }
+
+    #[test]
+    fn a_year_annualizes_to_its_own_return(start in 1..1_000_000_000_000u64, end in 0..1_000_000_000_000u64) {
+        let period_bps = (end as i128 - start as i128) * 10000 / start as i128;
+        let apy_bps = annualized_return_bps(start, end, SECONDS_PER_YEAR).unwrap() as i128;
+        prop_assert!((apy_bps - period_bps).abs() <= 1, "{} vs {}", apy_bps, period_bps);
+    }
+
+    #[test]
+    fn annualized_return_rises_with_the_end_price(
+        start in SHARE_PRICE_SCALE / 2..SHARE_PRICE_SCALE * 2,
+        end in SHARE_PRICE_SCALE / 2..SHARE_PRICE_SCALE * 2,
+        bump in 1..SHARE_PRICE_SCALE / 100,
+        elapsed in 24 * 60 * 60..SECONDS_PER_YEAR,
+    ) {
+        let lower = annualized_return_bps(start, end, elapsed).unwrap();
+        let higher = annualized_return_bps(start, end + bump, elapsed).unwrap();
+        prop_assert!(higher >= lower);
+        // Sub-basis-point moves truncate to zero, but never flip sign
+        prop_assert!(lower >= 0 || end < start);
+        prop_assert!(lower <= 0 || end > start);
+    }
+}
+
+#[test]
+fn fee_shares_round_against_the_payer() {
+    // 22_500 profit on a 245_000 pool of 200_000 shares: 5_625 fee is 4_591.8 shares
+    let mut config = config(200_000, 0);
+    let mut user = position(100_000, 100_000);
+    let (fee, shares) = charge_performance_fee(&mut config, &mut user, 122_500, 245_000).unwrap();
+    assert_eq!((fee, shares), (5_625, 4_592));
+    assert_eq!(user.high_water_mark, 116_875);
+}
+
+#[test]
+fn short_returns_compound_to_a_year() {
+    const DAY: i64 = 24 * 60 * 60;
+    // 1.05^2 over half a year
+    assert_eq!(annualized_return_bps(1_000_000, 1_050_000, SECONDS_PER_YEAR / 2).unwrap(), 1025);
+    // 1.01^(365 / 7) = 1.68007
+    assert_eq!(annualized_return_bps(1_000_000, 1_010_000, 7 * DAY).unwrap(), 6800);
+    // 0.99^(365 / 30) = 0.88490
+    assert_eq!(annualized_return_bps(1_000_000, 990_000, 30 * DAY).unwrap(), -1150);
+    // Too large to express saturates rather than failing the view
+    assert_eq!(annualized_return_bps(1, u64::MAX, 60).unwrap(), i64::MAX);
+}
+
+#[test]
+fn window_returns_use_the_newest_snapshot_old_enough() {
+    const DAY: i64 = 24 * 60 * 60;
+    let mut history: SharePriceHistory = bytemuck::Zeroable::zeroed();
+    assert_eq!(window_return(&history, 7 * DAY).unwrap(), None);
+
+    // Wrap the ring twice over; the price rises 1 bps a day
+    let days = 2 * SHARE_PRICE_HISTORY_CAPACITY as i64 + 3;
+    for day in 0..days {
+        history.push(NOW + day * DAY, SHARE_PRICE_SCALE + day as u64 * SHARE_PRICE_SCALE / 10000);
+    }
+    assert_eq!(history.count as usize, SHARE_PRICE_HISTORY_CAPACITY);
+    assert_eq!(history.latest().unwrap().timestamp, NOW + (days - 1) * DAY);
+
+    let week = window_return(&history, 7 * DAY).unwrap().unwrap();
+    assert_eq!(week.end_timestamp - week.start_timestamp, 7 * DAY);
+    assert_eq!(week.end_price - week.start_price, 7 * SHARE_PRICE_SCALE / 10000);
+    assert_eq!(week.period_return_bps, 6);
+    assert!(week.apy_bps > 0);
+
+    // Anything between snapshots measures from the next older one
+    let partial = window_return(&history, 7 * DAY + 1).unwrap().unwrap();
+    assert_eq!(partial.end_timestamp - partial.start_timestamp, 8 * DAY);
+
+    // Overwritten snapshots are gone
+    let held = (SHARE_PRICE_HISTORY_CAPACITY as i64 - 1) * DAY;
+    assert!(window_return(&history, held).unwrap().is_some());
+    assert_eq!(window_return(&history, held + 1).unwrap(), None);
+
+    assert_eq!(window_return(&history, 0).unwrap_err(), VaultError::InvalidReturnWindow.into());
+}
@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::{entrypoint::ProgramResult, instruction::Instruction},
+};
+use grid_vault::VaultError;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor's entry ties the slice and account lifetimes together
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    grid_vault::entry(program_id, accounts, data)
+}
+
+/// Program test running grid-vault natively alongside the bundled SPL programs
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new("grid_vault", grid_vault::ID, processor!(process_instruction));
+    program_test.prefer_bpf(false);
+    program_test
+}
+
+pub fn fund(program_test: &mut ProgramTest, signer: &Keypair) {
+    program_test.add_account(
+        signer.pubkey(),
+        Account::new(10_000_000_000, 0, &system_program::ID),
+    );
+}
+
+pub fn config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"protocol_config"], &grid_vault::ID).0
+}
+
+pub fn treasury_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &grid_vault::ID).0
+}
+
+pub fn position_pda(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"user_position", owner.as_ref()], &grid_vault::ID).0
+}
+
+pub fn attestation_pda(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"attestation", user.as_ref()], &grid_vault::ID).0
+}
+
+pub fn accepted_asset_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"accepted_asset", mint.as_ref()], &grid_vault::ID).0
+}
+
+pub fn asset_treasury_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"asset_treasury", mint.as_ref()], &grid_vault::ID).0
+}
+
+pub fn claims_escrow_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"claims_escrow"], &grid_vault::ID).0
+}
+
+pub fn claims_escrow_vault_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"claims_escrow_vault"], &grid_vault::ID).0
+}
+
+pub fn withdrawal_delegate_pda(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"withdrawal_delegate", owner.as_ref()], &grid_vault::ID).0
+}
+
+pub fn wsol_pda(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"wsol", owner.as_ref()], &grid_vault::ID).0
+}
+
+pub fn share_price_history_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"share_price_history"], &grid_vault::ID).0
+}
+
+pub async fn send(ctx: &mut ProgramTestContext, ix: Instruction, signer: &Keypair) -> Result<(), BanksClientError> {
+    send_all(ctx, &[ix], &[signer]).await
+}
+
+pub async fn send_all(
+    ctx: &mut ProgramTestContext,
+    ixs: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&signers[0].pubkey()), signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+/// Moves the cluster clock forward; the next transaction observes the new time
+pub async fn warp_clock(ctx: &mut ProgramTestContext, seconds: i64) -> i64 {
+    let mut clock = ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += seconds;
+    ctx.set_sysvar(&clock);
+    clock.unix_timestamp
+}
+
+pub async fn now(ctx: &mut ProgramTestContext) -> i64 {
+    ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+}
+
+pub fn assert_vault_error(result: Result<(), BanksClientError>, expected: VaultError) {
+    assert_custom_error(result, u32::from(expected))
+}
+
+/// For Anchor framework errors such as a failed seeds constraint
+pub fn assert_anchor_error(result: Result<(), BanksClientError>, expected: anchor_lang::error::ErrorCode) {
+    assert_custom_error(result, u32::from(expected))
+}
+
+fn assert_custom_error(result: Result<(), BanksClientError>, expected: u32) {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => assert_eq!(code, expected),
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
@@ -0,0 +1,330 @@
+mod common;
+
+use anchor_lang::{
+    prelude::Pubkey, solana_program::instruction::Instruction, AccountDeserialize, AnchorDeserialize, InstructionData,
+    ToAccountMetas,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee, ExtensionType, StateWithExtensions},
+    state::{Account as TokenAccountState, Mint as MintState},
+};
+use common::{
+    assert_vault_error, attestation_pda, config_pda, fund, position_pda, program_test, send, send_all, treasury_pda,
+};
+use grid_vault::{ProtocolConfig, UserPosition, VaultError, WithdrawPreview};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::Transaction,
+};
+
+const DECIMALS: u8 = 6;
+const TRANSFER_FEE_BPS: u16 = 100; // 1%
+
+struct Vault {
+    ctx: ProgramTestContext,
+    token_program: Pubkey,
+    mint: Pubkey,
+    user: Keypair,
+    user_token_account: Pubkey,
+}
+
+/// Boots a vault over a fresh mint and funds one user with 1_000_000 tokens.
+/// A fee-charging vault uses a Token-2022 mint with a 1% transfer fee.
+async fn start_vault(with_transfer_fee: bool) -> Vault {
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+    let user = keypair_from_seed(&[4; 32]).unwrap();
+    let mut program_test = program_test();
+    fund(&mut program_test, &admin);
+    fund(&mut program_test, &user);
+    let mut ctx = program_test.start_with_context().await;
+
+    let token_program = if with_transfer_fee { spl_token_2022::ID } else { anchor_spl::token::ID };
+    let (mint_extensions, account_extensions) = if with_transfer_fee {
+        (vec![ExtensionType::TransferFeeConfig], vec![ExtensionType::TransferFeeAmount])
+    } else {
+        (vec![], vec![])
+    };
+    let mint_len = ExtensionType::try_calculate_account_len::<MintState>(&mint_extensions).unwrap();
+    let account_len = ExtensionType::try_calculate_account_len::<TokenAccountState>(&account_extensions).unwrap();
+
+    // Mint with the user as mint authority
+    let mint = Keypair::new();
+    let mut ixs = vec![system_instruction::create_account(
+        &admin.pubkey(),
+        &mint.pubkey(),
+        Rent::default().minimum_balance(mint_len),
+        mint_len as u64,
+        &token_program,
+    )];
+    if with_transfer_fee {
+        ixs.push(
+            transfer_fee::instruction::initialize_transfer_fee_config(
+                &token_program,
+                &mint.pubkey(),
+                Some(&admin.pubkey()),
+                Some(&admin.pubkey()),
+                TRANSFER_FEE_BPS,
+                u64::MAX,
+            )
+            .unwrap(),
+        );
+    }
+    ixs.push(
+        spl_token_2022::instruction::initialize_mint2(&token_program, &mint.pubkey(), &user.pubkey(), None, DECIMALS)
+            .unwrap(),
+    );
+    send_all(&mut ctx, &ixs, &[&admin, &mint]).await.unwrap();
+
+    // User token account holding 1_000_000
+    let user_token_account = Keypair::new();
+    let ixs = [
+        system_instruction::create_account(
+            &user.pubkey(),
+            &user_token_account.pubkey(),
+            Rent::default().minimum_balance(account_len),
+            account_len as u64,
+            &token_program,
+        ),
+        spl_token_2022::instruction::initialize_account3(
+            &token_program,
+            &user_token_account.pubkey(),
+            &mint.pubkey(),
+            &user.pubkey(),
+        )
+        .unwrap(),
+        spl_token_2022::instruction::mint_to(
+            &token_program,
+            &mint.pubkey(),
+            &user_token_account.pubkey(),
+            &user.pubkey(),
+            &[],
+            1_000_000,
+        )
+        .unwrap(),
+    ];
+    send_all(&mut ctx, &ixs, &[&user, &user_token_account]).await.unwrap();
+
+    // Protocol, treasury and the user's position
+    let initialize = Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::InitializeProtocol {
+            authority: admin.pubkey(),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: mint.pubkey(),
+            system_program: system_program::ID,
+            token_program,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::InitializeProtocol {
+            admin: admin.pubkey(),
+            trading_bot: keypair_from_seed(&[2; 32]).unwrap().pubkey(),
+        }
+        .data(),
+    };
+    send(&mut ctx, initialize, &admin).await.unwrap();
+
+    let create_position = Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::CreateUserPosition {
+            owner: user.pubkey(),
+            user_position: position_pda(&user.pubkey()),
+            protocol_config: config_pda(),
+            attestation: attestation_pda(&user.pubkey()),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::CreateUserPosition { proof: vec![] }.data(),
+    };
+    send(&mut ctx, create_position, &user).await.unwrap();
+
+    Vault {
+        ctx,
+        token_program,
+        mint: mint.pubkey(),
+        user,
+        user_token_account: user_token_account.pubkey(),
+    }
+}
+
+impl Vault {
+    fn deposit_accounts(&self) -> Vec<anchor_lang::prelude::AccountMeta> {
+        grid_vault::accounts::Deposit {
+            owner: self.user.pubkey(),
+            user_position: position_pda(&self.user.pubkey()),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            user_token_account: self.user_token_account,
+            attestation: attestation_pda(&self.user.pubkey()),
+            token_program: self.token_program,
+        }
+        .to_account_metas(None)
+    }
+
+    async fn deposit(&mut self, amount: u64, min_shares: u64) -> Result<(), solana_program_test::BanksClientError> {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: self.deposit_accounts(),
+            data: grid_vault::instruction::Deposit { amount, min_shares, proof: vec![] }.data(),
+        };
+        let user = self.user.insecure_clone();
+        send(&mut self.ctx, ix, &user).await
+    }
+
+    async fn mint_shares(&mut self, shares: u64, max_assets: u64) -> Result<(), solana_program_test::BanksClientError> {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: self.deposit_accounts(),
+            data: grid_vault::instruction::Mint { shares, max_assets, proof: vec![] }.data(),
+        };
+        let user = self.user.insecure_clone();
+        send(&mut self.ctx, ix, &user).await
+    }
+
+    fn withdraw_accounts(&self) -> Vec<anchor_lang::prelude::AccountMeta> {
+        grid_vault::accounts::Withdraw {
+            owner: self.user.pubkey(),
+            user_position: position_pda(&self.user.pubkey()),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            user_token_account: self.user_token_account,
+            token_program: self.token_program,
+        }
+        .to_account_metas(None)
+    }
+
+    async fn withdraw(&mut self, amount: u64) -> Result<(), solana_program_test::BanksClientError> {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: self.withdraw_accounts(),
+            data: grid_vault::instruction::Withdraw { amount, max_shares: u64::MAX }.data(),
+        };
+        let user = self.user.insecure_clone();
+        send(&mut self.ctx, ix, &user).await
+    }
+
+    async fn preview_withdraw(&mut self, assets: u64) -> WithdrawPreview {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: grid_vault::accounts::PreviewPosition {
+                user_position: position_pda(&self.user.pubkey()),
+                owner: self.user.pubkey(),
+                protocol_config: config_pda(),
+                treasury_account: treasury_pda(),
+                token_mint: self.mint,
+            }
+            .to_account_metas(None),
+            data: grid_vault::instruction::PreviewWithdraw { assets }.data(),
+        };
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(&[ix], Some(&self.user.pubkey()), &[&self.user], blockhash);
+        let simulation = self.ctx.banks_client.simulate_transaction(tx).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        let data = simulation.simulation_details.unwrap().return_data.unwrap().data;
+        WithdrawPreview::try_from_slice(&data).unwrap()
+    }
+
+    async fn config(&mut self) -> ProtocolConfig {
+        let account = self.ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+        ProtocolConfig::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    async fn position(&mut self) -> UserPosition {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(position_pda(&self.user.pubkey()))
+            .await
+            .unwrap()
+            .unwrap();
+        *bytemuck::from_bytes(&account.data[8..])
+    }
+
+    async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        let account = self.ctx.banks_client.get_account(address).await.unwrap().unwrap();
+        StateWithExtensions::<TokenAccountState>::unpack(&account.data).unwrap().base.amount
+    }
+}
+
+#[tokio::test]
+async fn deposit_prices_shares_at_pre_deposit_nav() {
+    let mut vault = start_vault(false).await;
+
+    vault.deposit(100_000, 100_000).await.unwrap();
+    // Second deposit must not be diluted by its own tokens already sitting in the treasury
+    vault.deposit(50_000, 50_000).await.unwrap();
+
+    assert_eq!(vault.token_balance(treasury_pda()).await, 150_000);
+    assert_eq!(vault.token_balance(vault.user_token_account).await, 850_000);
+    let config = vault.config().await;
+    assert_eq!(config.total_shares, 150_000);
+    let position = vault.position().await;
+    assert_eq!(position.user_shares, 150_000);
+    assert_eq!(position.cost_basis, 150_000);
+    assert_eq!(position.high_water_mark, 150_000);
+}
+
+#[tokio::test]
+async fn deposit_credits_amount_received_after_transfer_fee() {
+    let mut vault = start_vault(true).await;
+
+    vault.deposit(100_000, 0).await.unwrap();
+    vault.deposit(50_000, 0).await.unwrap();
+
+    // 1% is withheld by the mint on each transfer
+    assert_eq!(vault.token_balance(treasury_pda()).await, 99_000 + 49_500);
+    assert_eq!(vault.token_balance(vault.user_token_account).await, 850_000);
+    let config = vault.config().await;
+    assert_eq!(config.total_shares, 148_500);
+    let position = vault.position().await;
+    assert_eq!(position.user_shares, 148_500);
+    assert_eq!(position.cost_basis, 148_500);
+    assert_eq!(position.high_water_mark, 148_500);
+}
+
+#[tokio::test]
+async fn deposit_slippage_uses_amount_received() {
+    let mut vault = start_vault(true).await;
+
+    // Only 99_000 arrives, so 100_000 shares cannot be minted
+    let result = vault.deposit(100_000, 100_000).await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+    assert_eq!(vault.token_balance(treasury_pda()).await, 0);
+    assert_eq!(vault.config().await.total_shares, 0);
+}
+
+#[tokio::test]
+async fn mint_grosses_up_for_transfer_fee() {
+    let mut vault = start_vault(true).await;
+    vault.deposit(100_000, 0).await.unwrap();
+
+    vault.mint_shares(9_900, 10_100).await.unwrap();
+
+    let position = vault.position().await;
+    assert_eq!(position.user_shares, 99_000 + 9_900);
+    assert!(vault.token_balance(treasury_pda()).await >= 99_000 + 9_900);
+}
+
+#[tokio::test]
+async fn withdraw_pays_amount_net_of_transfer_fee() {
+    let mut vault = start_vault(true).await;
+    vault.deposit(100_000, 0).await.unwrap();
+
+    // Shares are burned for the full 50_000 leaving the treasury; 1% is withheld on the way out
+    let preview = vault.preview_withdraw(50_000).await;
+    assert_eq!((preview.shares, preview.received, preview.transfer_fee), (50_000, 49_500, 500));
+
+    let before = vault.token_balance(vault.user_token_account).await;
+    vault.withdraw(50_000).await.unwrap();
+    assert_eq!(vault.token_balance(vault.user_token_account).await - before, preview.received);
+    assert_eq!(vault.token_balance(treasury_pda()).await, 99_000 - 50_000);
+    assert_eq!(vault.position().await.user_shares, 99_000 - preview.shares);
+    assert_eq!(vault.config().await.total_shares, 99_000 - preview.shares);
+}
@@ -0,0 +1,1738 @@
+mod common;
+
+use anchor_lang::{
+    error::ErrorCode,
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, keccak},
+    AccountDeserialize, AnchorDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{get_associated_token_address, spl_associated_token_account},
+    token::spl_token,
+};
+use common::{
+    accepted_asset_pda, assert_anchor_error, assert_vault_error, asset_treasury_pda, attestation_pda,
+    claims_escrow_pda, claims_escrow_vault_pda, config_pda, fund, now, position_pda, program_test, send, send_all,
+    share_price_history_pda, treasury_pda, warp_clock, withdrawal_delegate_pda,
+};
+use grid_vault::{
+    accounting, AccessMode, DeploymentValuation, DepositPreview, FeePreview, PositionSnapshot, ProtocolConfig,
+    UserPosition, VaultError, VaultLifecycle, VaultSnapshot, WindowReturn, WithdrawPreview, FEE_COLLECTION_INTERVAL,
+    MAX_PRICE_AGE, MAX_RETURN_WINDOWS, PAUSE_ALL, PAUSE_DEPOSITS, PAUSE_FEE_COLLECTION, SHARE_PRICE_SCALE,
+    SHARE_PRICE_SNAPSHOT_INTERVAL, STALE_VALUATION_THRESHOLD, WIND_DOWN_GRACE_PERIOD,
+};
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::{
+    instruction::AccountMeta,
+    program_pack::Pack,
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::Transaction,
+};
+
+const DECIMALS: u8 = 6;
+const STARTING_BALANCE: u64 = 1_000_000;
+
+/// A vault over a fresh SPL mint. Every actor holds an associated token account;
+/// the users start with `STARTING_BALANCE` tokens and an open position.
+struct Vault {
+    ctx: ProgramTestContext,
+    mint: Pubkey,
+    admin: Keypair,
+    bot: Keypair,
+    alice: Keypair,
+    bob: Keypair,
+    outsider: Keypair,
+}
+
+async fn start_vault() -> Vault {
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+    let bot = keypair_from_seed(&[2; 32]).unwrap();
+    let alice = keypair_from_seed(&[4; 32]).unwrap();
+    let bob = keypair_from_seed(&[5; 32]).unwrap();
+    let outsider = keypair_from_seed(&[9; 32]).unwrap();
+    let mut program_test = program_test();
+    for signer in [&admin, &bot, &alice, &bob, &outsider] {
+        fund(&mut program_test, signer);
+    }
+    let mut ctx = program_test.start_with_context().await;
+
+    let mint = Keypair::new();
+    let mut ixs = vec![
+        system_instruction::create_account(
+            &admin.pubkey(),
+            &mint.pubkey(),
+            Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), None, DECIMALS)
+            .unwrap(),
+    ];
+    for owner in [&admin, &bot, &alice, &bob, &outsider] {
+        ixs.push(spl_associated_token_account::instruction::create_associated_token_account(
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint.pubkey(),
+            &spl_token::ID,
+        ));
+    }
+    for owner in [&alice, &bob, &outsider] {
+        ixs.push(
+            spl_token::instruction::mint_to(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &get_associated_token_address(&owner.pubkey(), &mint.pubkey()),
+                &admin.pubkey(),
+                &[],
+                STARTING_BALANCE,
+            )
+            .unwrap(),
+        );
+    }
+    send_all(&mut ctx, &ixs, &[&admin, &mint]).await.unwrap();
+
+    let initialize = Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::InitializeProtocol {
+            authority: admin.pubkey(),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: mint.pubkey(),
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::InitializeProtocol { admin: admin.pubkey(), trading_bot: bot.pubkey() }.data(),
+    };
+    send(&mut ctx, initialize, &admin).await.unwrap();
+
+    for user in [&alice, &bob] {
+        let create_position = Instruction {
+            program_id: grid_vault::ID,
+            accounts: grid_vault::accounts::CreateUserPosition {
+                owner: user.pubkey(),
+                user_position: position_pda(&user.pubkey()),
+                protocol_config: config_pda(),
+                attestation: attestation_pda(&user.pubkey()),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: grid_vault::instruction::CreateUserPosition { proof: vec![] }.data(),
+        };
+        send(&mut ctx, create_position, user).await.unwrap();
+    }
+
+    Vault { ctx, mint: mint.pubkey(), admin, bot, alice, bob, outsider }
+}
+
+fn ix(accounts: Vec<AccountMeta>, data: Vec<u8>) -> Instruction {
+    Instruction { program_id: grid_vault::ID, accounts, data }
+}
+
+impl Vault {
+    fn tokens(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.mint)
+    }
+
+    fn deposit_ix(&self, owner: &Pubkey, amount: u64) -> Instruction {
+        self.deposit_min_ix(owner, amount, 0)
+    }
+
+    fn deposit_min_ix(&self, owner: &Pubkey, amount: u64, min_shares: u64) -> Instruction {
+        self.deposit_proof_ix(owner, amount, min_shares, vec![])
+    }
+
+    fn deposit_proof_ix(&self, owner: &Pubkey, amount: u64, min_shares: u64, proof: Vec<[u8; 32]>) -> Instruction {
+        ix(self.deposit_accounts(owner), grid_vault::instruction::Deposit { amount, min_shares, proof }.data())
+    }
+
+    fn mint_ix(&self, owner: &Pubkey, shares: u64) -> Instruction {
+        let data = grid_vault::instruction::Mint { shares, max_assets: u64::MAX, proof: vec![] }.data();
+        ix(self.deposit_accounts(owner), data)
+    }
+
+    /// Accounts for `deposit` and `mint`
+    fn deposit_accounts(&self, owner: &Pubkey) -> Vec<AccountMeta> {
+        grid_vault::accounts::Deposit {
+            owner: *owner,
+            user_position: position_pda(owner),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            user_token_account: self.tokens(owner),
+            attestation: attestation_pda(owner),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None)
+    }
+
+    /// Accounts for `withdraw` and friends; `position_owner` may differ from the signer
+    fn withdraw_accounts(&self, owner: &Pubkey, position_owner: &Pubkey) -> Vec<AccountMeta> {
+        grid_vault::accounts::Withdraw {
+            owner: *owner,
+            user_position: position_pda(position_owner),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            user_token_account: self.tokens(owner),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None)
+    }
+
+    fn withdraw_ix(&self, owner: &Pubkey, amount: u64) -> Instruction {
+        self.withdraw_max_ix(owner, amount, u64::MAX)
+    }
+
+    fn withdraw_max_ix(&self, owner: &Pubkey, amount: u64, max_shares: u64) -> Instruction {
+        let data = grid_vault::instruction::Withdraw { amount, max_shares }.data();
+        ix(self.withdraw_accounts(owner, owner), data)
+    }
+
+    fn redeem_ix(&self, owner: &Pubkey, shares: u64) -> Instruction {
+        ix(self.withdraw_accounts(owner, owner), grid_vault::instruction::Redeem { shares, min_assets: 0 }.data())
+    }
+
+    fn redeem_all_ix(&self, owner: &Pubkey) -> Instruction {
+        ix(self.withdraw_accounts(owner, owner), grid_vault::instruction::RedeemAll { min_assets: 0 }.data())
+    }
+
+    fn emergency_withdraw_ix(&self, owner: &Pubkey, shares: u64) -> Instruction {
+        ix(self.withdraw_accounts(owner, owner), grid_vault::instruction::EmergencyWithdraw { shares }.data())
+    }
+
+    fn deposit_for_ix(&self, payer: &Pubkey, beneficiary: &Pubkey, amount: u64) -> Instruction {
+        let accounts = grid_vault::accounts::DepositFor {
+            payer: *payer,
+            beneficiary: *beneficiary,
+            user_position: position_pda(beneficiary),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            payer_token_account: self.tokens(payer),
+            attestation: attestation_pda(beneficiary),
+            token_program: spl_token::ID,
+        };
+        let data = grid_vault::instruction::DepositFor { amount, min_shares: 0, proof: vec![] }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn set_withdrawal_delegate_ix(&self, owner: &Pubkey, delegate: &Pubkey, destination: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::SetWithdrawalDelegate {
+            owner: *owner,
+            user_position: position_pda(owner),
+            withdrawal_delegate: withdrawal_delegate_pda(owner),
+            treasury_account: treasury_pda(),
+            destination: *destination,
+            system_program: system_program::ID,
+        };
+        let data = grid_vault::instruction::SetWithdrawalDelegate { delegate: *delegate }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn revoke_withdrawal_delegate_ix(&self, owner: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::RevokeWithdrawalDelegate {
+            owner: *owner,
+            withdrawal_delegate: withdrawal_delegate_pda(owner),
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::RevokeWithdrawalDelegate {}.data())
+    }
+
+    fn withdraw_as_delegate_ix(
+        &self,
+        delegate: &Pubkey,
+        owner: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        let accounts = grid_vault::accounts::WithdrawAsDelegate {
+            delegate: *delegate,
+            owner: *owner,
+            withdrawal_delegate: withdrawal_delegate_pda(owner),
+            user_position: position_pda(owner),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            destination: *destination,
+            token_program: spl_token::ID,
+        };
+        let data = grid_vault::instruction::WithdrawAsDelegate { amount, max_shares: u64::MAX }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn deploy_ix(&self, trading_bot: &Pubkey, amount: u64) -> Instruction {
+        let accounts = grid_vault::accounts::DeployCapital {
+            trading_bot: *trading_bot,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            trading_account: self.tokens(trading_bot),
+            token_program: spl_token::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::DeployCapitalForTrading { amount }.data())
+    }
+
+    fn return_ix(&self, trading_bot: &Pubkey, returned_amount: u64, original_deployed: u64) -> Instruction {
+        let accounts = grid_vault::accounts::ReturnCapital {
+            trading_bot: *trading_bot,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            trading_account: self.tokens(trading_bot),
+            token_program: spl_token::ID,
+        };
+        let data = grid_vault::instruction::ReturnCapitalFromTrading { returned_amount, original_deployed }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn valuation_ix(
+        &self,
+        trading_bot: &Pubkey,
+        current_value: u64,
+        unrealized_pnl: i64,
+        timestamp: i64,
+    ) -> Instruction {
+        let accounts =
+            grid_vault::accounts::UpdateValuation { trading_bot: *trading_bot, protocol_config: config_pda() };
+        let valuation = DeploymentValuation {
+            deployment_id: 1,
+            orca_positions_value: current_value / 2,
+            drift_equity_value: current_value - current_value / 2,
+            uncollected_fees: 0,
+            unrealized_pnl,
+            timestamp,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::UpdateDeploymentValuation { valuation }.data())
+    }
+
+    fn collect_user_fees_ix(&self, caller: &Pubkey, owner: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::CollectUserFees {
+            caller: *caller,
+            protocol_config: config_pda(),
+            user_position: position_pda(owner),
+            treasury_account: treasury_pda(),
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CollectUserFees {}.data())
+    }
+
+    fn collect_batch_fees_ix(&self, caller: &Pubkey, owners: &[&Pubkey]) -> Instruction {
+        let mut accounts = grid_vault::accounts::CollectBatchFees {
+            caller: *caller,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        }
+        .to_account_metas(None);
+        accounts.extend(owners.iter().map(|owner| AccountMeta::new(position_pda(owner), false)));
+        ix(accounts, grid_vault::instruction::CollectBatchFees {}.data())
+    }
+
+    fn collect_performance_fees_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::CollectFees {
+            admin: *admin,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            admin_token_account: self.tokens(admin),
+            token_program: spl_token::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CollectPerformanceFees {}.data())
+    }
+
+    fn create_position_ix(&self, owner: &Pubkey, proof: Vec<[u8; 32]>) -> Instruction {
+        let accounts = grid_vault::accounts::CreateUserPosition {
+            owner: *owner,
+            user_position: position_pda(owner),
+            protocol_config: config_pda(),
+            attestation: attestation_pda(owner),
+            system_program: system_program::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CreateUserPosition { proof }.data())
+    }
+
+    fn close_position_ix(&self, owner: &Pubkey, position_owner: &Pubkey) -> Instruction {
+        let accounts =
+            grid_vault::accounts::CloseUserPosition { owner: *owner, user_position: position_pda(position_owner) };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CloseUserPosition {}.data())
+    }
+
+    fn set_access_mode_ix(&self, admin: &Pubkey, access_mode: AccessMode, merkle_root: [u8; 32]) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::SetAccessMode { access_mode, merkle_root }.data())
+    }
+
+    fn set_deposit_limits_ix(
+        &self,
+        admin: &Pubkey,
+        max_total_deposits: u64,
+        max_position_size: u64,
+        min_deposit: u64,
+    ) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        let data = grid_vault::instruction::SetDepositLimits { max_total_deposits, max_position_size, min_deposit };
+        ix(accounts.to_account_metas(None), data.data())
+    }
+
+    fn attestation_accounts(&self, admin: &Pubkey, user: &Pubkey) -> Vec<AccountMeta> {
+        grid_vault::accounts::ManageAttestation {
+            admin: *admin,
+            protocol_config: config_pda(),
+            attestation: attestation_pda(user),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None)
+    }
+
+    fn issue_attestation_ix(&self, admin: &Pubkey, user: &Pubkey, expires_at: i64) -> Instruction {
+        let data = grid_vault::instruction::IssueAttestation { user: *user, expires_at }.data();
+        ix(self.attestation_accounts(admin, user), data)
+    }
+
+    fn revoke_access_ix(&self, admin: &Pubkey, user: &Pubkey) -> Instruction {
+        ix(self.attestation_accounts(admin, user), grid_vault::instruction::RevokeAccess { user: *user }.data())
+    }
+
+    fn pause_ix(&self, authority: &Pubkey, operations: u8) -> Instruction {
+        let accounts = grid_vault::accounts::PauseAction { authority: *authority, protocol_config: config_pda() };
+        ix(
+            accounts.to_account_metas(None),
+            grid_vault::instruction::PauseProtocol { operations, reason_code: 1 }.data(),
+        )
+    }
+
+    fn set_guardian_ix(&self, admin: &Pubkey, guardian: Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::SetGuardian { guardian }.data())
+    }
+
+    fn unpause_ix(&self, admin: &Pubkey, operations: u8) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(
+            accounts.to_account_metas(None),
+            grid_vault::instruction::UnpauseProtocol { operations, reason_code: 1 }.data(),
+        )
+    }
+
+    fn enter_emergency_ix(&self, authority: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::PauseAction { authority: *authority, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::EnterEmergencyMode { reason_code: 1 }.data())
+    }
+
+    fn exit_emergency_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::ExitEmergencyMode { reason_code: 1 }.data())
+    }
+
+    fn initialize_ix(&self, authority: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::InitializeProtocol {
+            authority: *authority,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            rent: sysvar::rent::ID,
+        };
+        let data = grid_vault::instruction::InitializeProtocol { admin: *authority, trading_bot: *authority }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn close_deposits_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CloseDeposits {}.data())
+    }
+
+    fn begin_wind_down_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::AdminAction { admin: *admin, protocol_config: config_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::BeginWindDown {}.data())
+    }
+
+    fn claim_final_balance_ix(&self, owner: &Pubkey) -> Instruction {
+        ix(self.withdraw_accounts(owner, owner), grid_vault::instruction::ClaimFinalBalance {}.data())
+    }
+
+    fn close_vault_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::CloseVault {
+            admin: *admin,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            admin_token_account: self.tokens(admin),
+            claims_escrow: claims_escrow_pda(),
+            escrow_token_account: claims_escrow_vault_pda(),
+            token_mint: self.mint,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            rent: sysvar::rent::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CloseVault {}.data())
+    }
+
+    fn claim_from_escrow_ix(&self, owner: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::ClaimFromEscrow {
+            owner: *owner,
+            user_position: position_pda(owner),
+            protocol_config: config_pda(),
+            claims_escrow: claims_escrow_pda(),
+            escrow_token_account: claims_escrow_vault_pda(),
+            token_mint: self.mint,
+            user_token_account: self.tokens(owner),
+            admin_token_account: self.tokens(&self.admin.pubkey()),
+            token_program: spl_token::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::ClaimFromEscrow {}.data())
+    }
+
+    fn deposit_asset_ix(&self, owner: &Pubkey, asset_mint: &Pubkey, amount: u64) -> Instruction {
+        let accounts = grid_vault::accounts::DepositAsset {
+            owner: *owner,
+            user_position: position_pda(owner),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            accepted_asset: accepted_asset_pda(asset_mint),
+            asset_treasury: asset_treasury_pda(asset_mint),
+            asset_mint: *asset_mint,
+            user_asset_account: get_associated_token_address(owner, asset_mint),
+            attestation: attestation_pda(owner),
+            token_program: spl_token::ID,
+        };
+        let data = grid_vault::instruction::DepositAsset { amount, min_shares: 0, proof: vec![] }.data();
+        ix(accounts.to_account_metas(None), data)
+    }
+
+    fn update_asset_price_ix(
+        &self,
+        authority: &Pubkey,
+        asset_mint: &Pubkey,
+        price: u64,
+        others: &[Pubkey],
+    ) -> Instruction {
+        let accounts = grid_vault::accounts::UpdateAssetPrice {
+            authority: *authority,
+            protocol_config: config_pda(),
+            accepted_asset: accepted_asset_pda(asset_mint),
+            asset_treasury: asset_treasury_pda(asset_mint),
+        };
+        let mut ix = ix(accounts.to_account_metas(None), grid_vault::instruction::UpdateAssetPrice { price }.data());
+        ix.accounts.extend(others.iter().map(|mint| AccountMeta::new_readonly(accepted_asset_pda(mint), false)));
+        ix
+    }
+
+    fn sweep_ix(&self, trading_bot: &Pubkey, asset_mint: &Pubkey, amount: u64) -> Instruction {
+        let accounts = grid_vault::accounts::SweepAsset {
+            trading_bot: *trading_bot,
+            protocol_config: config_pda(),
+            accepted_asset: accepted_asset_pda(asset_mint),
+            asset_treasury: asset_treasury_pda(asset_mint),
+            asset_mint: *asset_mint,
+            trading_account: get_associated_token_address(trading_bot, asset_mint),
+            token_program: spl_token::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::SweepAssetToTrading { amount }.data())
+    }
+
+    /// Emergency exit that also takes the owner's share of each listed accepted asset
+    fn emergency_withdraw_assets_ix(&self, owner: &Pubkey, shares: u64, asset_mints: &[Pubkey]) -> Instruction {
+        let mut ix = self.emergency_withdraw_ix(owner, shares);
+        for asset_mint in asset_mints {
+            ix.accounts.extend([
+                AccountMeta::new(accepted_asset_pda(asset_mint), false),
+                AccountMeta::new(asset_treasury_pda(asset_mint), false),
+                AccountMeta::new_readonly(*asset_mint, false),
+                AccountMeta::new(get_associated_token_address(owner, asset_mint), false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+            ]);
+        }
+        ix
+    }
+
+    fn initialize_share_price_history_ix(&self, admin: &Pubkey) -> Instruction {
+        let accounts = grid_vault::accounts::InitializeSharePriceHistory {
+            admin: *admin,
+            protocol_config: config_pda(),
+            share_price_history: share_price_history_pda(),
+            system_program: system_program::ID,
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::InitializeSharePriceHistory {}.data())
+    }
+
+    fn check_buffer_ix(&self) -> Instruction {
+        let accounts =
+            grid_vault::accounts::CheckBuffer { protocol_config: config_pda(), treasury_account: treasury_pda() };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::CheckBuffer {}.data())
+    }
+
+    fn snapshot_ix(&self) -> Instruction {
+        let accounts = grid_vault::accounts::Snapshot {
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            share_price_history: share_price_history_pda(),
+        };
+        ix(accounts.to_account_metas(None), grid_vault::instruction::Snapshot {}.data())
+    }
+
+    async fn send(&mut self, ix: Instruction, signer: &Keypair) -> Result<(), BanksClientError> {
+        send(&mut self.ctx, ix, signer).await
+    }
+
+    /// Lists a new accepted asset at `price` base units per whole token and funds Alice and Bob with it
+    async fn add_asset(&mut self, price: u64) -> Pubkey {
+        let admin = self.admin.insecure_clone();
+        let asset_mint = Keypair::new();
+        let mut ixs = vec![
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &asset_mint.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &asset_mint.pubkey(),
+                &admin.pubkey(),
+                None,
+                DECIMALS,
+            )
+            .unwrap(),
+        ];
+        for owner in [self.alice.pubkey(), self.bob.pubkey()] {
+            let tokens = get_associated_token_address(&owner, &asset_mint.pubkey());
+            ixs.push(spl_associated_token_account::instruction::create_associated_token_account(
+                &admin.pubkey(),
+                &owner,
+                &asset_mint.pubkey(),
+                &spl_token::ID,
+            ));
+            ixs.push(
+                spl_token::instruction::mint_to(
+                    &spl_token::ID,
+                    &asset_mint.pubkey(),
+                    &tokens,
+                    &admin.pubkey(),
+                    &[],
+                    STARTING_BALANCE,
+                )
+                .unwrap(),
+            );
+        }
+        let accounts = grid_vault::accounts::AddAcceptedAsset {
+            admin: admin.pubkey(),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            asset_mint: asset_mint.pubkey(),
+            accepted_asset: accepted_asset_pda(&asset_mint.pubkey()),
+            asset_treasury: asset_treasury_pda(&asset_mint.pubkey()),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        };
+        let data =
+            grid_vault::instruction::AddAcceptedAsset { oracle: Pubkey::default(), haircut_bps: 0, price }.data();
+        ixs.push(ix(accounts.to_account_metas(None), data));
+        send_all(&mut self.ctx, &ixs, &[&admin, &asset_mint]).await.unwrap();
+        asset_mint.pubkey()
+    }
+
+    /// Mints trading profit straight into the bot's account
+    async fn credit_bot(&mut self, amount: u64) {
+        let ix = spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &self.mint,
+            &self.tokens(&self.bot.pubkey()),
+            &self.admin.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap();
+        let admin = self.admin.insecure_clone();
+        send(&mut self.ctx, ix, &admin).await.unwrap();
+    }
+
+    /// Runs a view instruction and returns its return data
+    async fn view(&mut self, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Vec<u8> {
+        self.try_view(accounts, data).await.unwrap()
+    }
+
+    /// Runs a view instruction that may fail the way its mutating counterpart would
+    async fn try_view(&mut self, accounts: Vec<AccountMeta>, data: Vec<u8>) -> Result<Vec<u8>, BanksClientError> {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let payer = self.outsider.insecure_clone();
+        let tx = Transaction::new_signed_with_payer(&[ix(accounts, data)], Some(&payer.pubkey()), &[&payer], blockhash);
+        let simulation = self.ctx.banks_client.simulate_transaction(tx).await.unwrap();
+        simulation.result.unwrap().map_err(BanksClientError::TransactionError)?;
+        Ok(simulation.simulation_details.unwrap().return_data.map(|data| data.data).unwrap_or_default())
+    }
+
+    fn preview_position_accounts(&self, owner: &Pubkey) -> Vec<AccountMeta> {
+        grid_vault::accounts::PreviewPosition {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+        }
+        .to_account_metas(None)
+    }
+
+    async fn preview_deposit(&mut self, owner: &Pubkey, assets: u64) -> Result<DepositPreview, BanksClientError> {
+        let accounts = self.preview_position_accounts(owner);
+        let data = self.try_view(accounts, grid_vault::instruction::PreviewDeposit { assets }.data()).await?;
+        Ok(DepositPreview::try_from_slice(&data).unwrap())
+    }
+
+    fn preview_accounts(&self) -> Vec<AccountMeta> {
+        grid_vault::accounts::Preview {
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: self.mint,
+        }
+        .to_account_metas(None)
+    }
+
+    async fn preview_mint(&mut self, shares: u64) -> u64 {
+        let data = self.view(self.preview_accounts(), grid_vault::instruction::PreviewMint { shares }.data()).await;
+        u64::try_from_slice(&data).unwrap()
+    }
+
+    async fn preview_redeem(&mut self, shares: u64) -> u64 {
+        let data = self.view(self.preview_accounts(), grid_vault::instruction::PreviewRedeem { shares }.data()).await;
+        u64::try_from_slice(&data).unwrap()
+    }
+
+    async fn preview_withdraw(&mut self, owner: &Pubkey, assets: u64) -> Result<WithdrawPreview, BanksClientError> {
+        let accounts = self.preview_position_accounts(owner);
+        let data = self.try_view(accounts, grid_vault::instruction::PreviewWithdraw { assets }.data()).await?;
+        Ok(WithdrawPreview::try_from_slice(&data).unwrap())
+    }
+
+    async fn unrealized_pnl(&mut self, owner: &Pubkey) -> i64 {
+        let accounts = grid_vault::accounts::GetUnrealizedPnl {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        };
+        let data =
+            self.view(accounts.to_account_metas(None), grid_vault::instruction::GetUnrealizedPnl {}.data()).await;
+        i64::try_from_slice(&data).unwrap()
+    }
+
+    async fn max_deposit(&mut self, owner: &Pubkey) -> u64 {
+        let accounts = grid_vault::accounts::PreviewLimits {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        };
+        let data = self.view(accounts.to_account_metas(None), grid_vault::instruction::MaxDeposit {}.data()).await;
+        u64::try_from_slice(&data).unwrap()
+    }
+
+    async fn preview_fee(&mut self, owner: &Pubkey) -> FeePreview {
+        let accounts = grid_vault::accounts::PreviewLimits {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        };
+        let data = self.view(accounts.to_account_metas(None), grid_vault::instruction::PreviewFee {}.data()).await;
+        FeePreview::try_from_slice(&data).unwrap()
+    }
+
+    async fn user_balance(&mut self, owner: &Pubkey) -> u64 {
+        let accounts = grid_vault::accounts::CalculateBalance {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        };
+        let data =
+            self.view(accounts.to_account_metas(None), grid_vault::instruction::CalculateUserBalance {}.data()).await;
+        u64::from_le_bytes(data.try_into().unwrap())
+    }
+
+    async fn fee_eligible(&mut self, owner: &Pubkey) -> bool {
+        let accounts = grid_vault::accounts::CheckFeeEligibility { user_position: position_pda(owner), owner: *owner };
+        let data =
+            self.view(accounts.to_account_metas(None), grid_vault::instruction::CheckFeeEligibility {}.data()).await;
+        data == [1]
+    }
+
+    async fn vault_snapshot(&mut self) -> VaultSnapshot {
+        let accounts =
+            grid_vault::accounts::GetProtocolStats { protocol_config: config_pda(), treasury_account: treasury_pda() };
+        let data =
+            self.view(accounts.to_account_metas(None), grid_vault::instruction::GetVaultSnapshot {}.data()).await;
+        VaultSnapshot::try_from_slice(&data).unwrap()
+    }
+
+    async fn position_snapshot(&mut self, owner: &Pubkey) -> PositionSnapshot {
+        let accounts = grid_vault::accounts::GetUserStats {
+            user_position: position_pda(owner),
+            owner: *owner,
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+        };
+        let data =
+            self.view(accounts.to_account_metas(None), grid_vault::instruction::GetPositionSnapshot {}.data()).await;
+        PositionSnapshot::try_from_slice(&data).unwrap()
+    }
+
+    async fn share_price_returns(&mut self, windows: Vec<i64>) -> Result<Vec<Option<WindowReturn>>, BanksClientError> {
+        let accounts = grid_vault::accounts::GetSharePriceReturns { share_price_history: share_price_history_pda() };
+        let data = grid_vault::instruction::GetSharePriceReturns { windows }.data();
+        let data = self.try_view(accounts.to_account_metas(None), data).await?;
+        Ok(Vec::try_from_slice(&data).unwrap())
+    }
+
+    async fn config(&mut self) -> ProtocolConfig {
+        let account = self.ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+        ProtocolConfig::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    async fn position(&mut self, owner: &Pubkey) -> UserPosition {
+        let account = self.ctx.banks_client.get_account(position_pda(owner)).await.unwrap().unwrap();
+        *bytemuck::from_bytes(&account.data[8..])
+    }
+
+    async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        let account = self.ctx.banks_client.get_account(address).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+}
+
+#[tokio::test]
+async fn trading_cycle_accrues_collects_and_sweeps_fees() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+
+    // At most 90% of the treasury can leave for trading
+    let result = vault.send(vault.deploy_ix(&bot.pubkey(), 180_001), &bot).await;
+    assert_vault_error(result, VaultError::ExceedsMaxDeployment);
+    vault.send(vault.deploy_ix(&bot.pubkey(), 180_000), &bot).await.unwrap();
+    assert_eq!(vault.token_balance(treasury_pda()).await, 20_000);
+    assert_eq!(vault.config().await.total_trading_deployed, 180_000);
+
+    // An hour short of the fee interval nothing is due yet
+    warp_clock(&mut vault.ctx, FEE_COLLECTION_INTERVAL - 3_600).await;
+    assert!(!vault.fee_eligible(&alice.pubkey()).await);
+    let result = vault.send(vault.collect_user_fees_ix(&bot.pubkey(), &alice.pubkey()), &bot).await;
+    assert_vault_error(result, VaultError::FeeCollectionTooSoon);
+
+    // Positions marked up 60_000; a quarter of it is held back as pending fees
+    let timestamp = now(&mut vault.ctx).await;
+    vault.send(vault.valuation_ix(&bot.pubkey(), 240_000, 60_000, timestamp), &bot).await.unwrap();
+    let config = vault.config().await;
+    assert_eq!((config.deployed_current_value, config.pending_unrealized_fees), (240_000, 15_000));
+    // (20_000 + 240_000 - 15_000) / 2
+    assert_eq!(vault.user_balance(&alice.pubkey()).await, 122_500);
+
+    // Cross the interval while the valuation is still fresh
+    let collected_at = warp_clock(&mut vault.ctx, 3_600).await;
+    assert!(vault.fee_eligible(&alice.pubkey()).await);
+    let batch = vault.collect_batch_fees_ix(&bot.pubkey(), &[&alice.pubkey(), &bob.pubkey()]);
+    vault.send(batch, &bot).await.unwrap();
+
+    let alice_position = vault.position(&alice.pubkey()).await;
+    // 25% of the 22_500 gain over the 100_000 high-water mark
+    assert_eq!(alice_position.lifetime_fees_paid, 5_625);
+    assert_eq!(alice_position.high_water_mark, 122_500 - 5_625);
+    assert_eq!(alice_position.user_shares, 100_000 - 4_592);
+    assert_eq!(alice_position.last_fee_collection, collected_at);
+    let bob_position = vault.position(&bob.pubkey()).await;
+    assert!(bob_position.lifetime_fees_paid > 0);
+    assert_eq!(bob_position.last_fee_collection, collected_at);
+    let config = vault.config().await;
+    let fees = alice_position.lifetime_fees_paid + bob_position.lifetime_fees_paid;
+    assert_eq!(config.accumulated_fees, fees);
+    assert_eq!(config.total_shares, alice_position.user_shares + bob_position.user_shares);
+
+    // Collected positions restart their interval
+    assert!(!vault.fee_eligible(&alice.pubkey()).await);
+    let batch = vault.collect_batch_fees_ix(&admin.pubkey(), &[&alice.pubkey(), &bob.pubkey()]);
+    vault.send(batch, &admin).await.unwrap();
+    assert_eq!(vault.config().await.accumulated_fees, fees);
+
+    // Admin sweeps the realized fees out of the treasury
+    vault.send(vault.collect_performance_fees_ix(&admin.pubkey()), &admin).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&admin.pubkey())).await, fees);
+    assert_eq!(vault.token_balance(treasury_pda()).await, 20_000 - fees);
+    let config = vault.config().await;
+    assert_eq!((config.accumulated_fees, config.last_fee_sweep), (0, collected_at));
+    let result = vault.send(vault.collect_performance_fees_ix(&admin.pubkey()), &admin).await;
+    assert_vault_error(result, VaultError::NoFeesToCollect);
+
+    // The bot closes out at the marked value; full return clears the valuation
+    vault.credit_bot(60_000).await;
+    vault.send(vault.return_ix(&bot.pubkey(), 240_000, 180_000), &bot).await.unwrap();
+    let config = vault.config().await;
+    assert_eq!(config.total_trading_deployed, 0);
+    assert_eq!(
+        (config.deployed_current_value, config.last_valuation_timestamp, config.pending_unrealized_fees),
+        (0, 0, 0)
+    );
+    assert_eq!(config.accumulated_fees, 15_000);
+    let treasury = vault.token_balance(treasury_pda()).await;
+    assert_eq!(treasury, 260_000 - fees);
+
+    // Everyone exits against the treasury, which keeps exactly the fees owed
+    for user in [&alice, &bob] {
+        vault.send(vault.redeem_all_ix(&user.pubkey()), user).await.unwrap();
+        assert_eq!(vault.position(&user.pubkey()).await.user_shares, 0);
+    }
+    let config = vault.config().await;
+    assert_eq!(config.total_shares, 0);
+    let treasury = vault.token_balance(treasury_pda()).await;
+    // Redemptions round down, so dust may stay behind
+    assert!(treasury >= config.accumulated_fees && treasury - config.accumulated_fees <= 2);
+    let alice_tokens = vault.token_balance(vault.tokens(&alice.pubkey())).await;
+    let bob_tokens = vault.token_balance(vault.tokens(&bob.pubkey())).await;
+    assert!(alice_tokens > STARTING_BALANCE && bob_tokens > STARTING_BALANCE);
+}
+
+#[tokio::test]
+async fn snapshots_report_vault_and_position_state() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+
+    // An empty vault prices shares 1:1
+    let snapshot = vault.vault_snapshot().await;
+    assert_eq!((snapshot.nav, snapshot.total_shares, snapshot.price_per_share), (0, 0, SHARE_PRICE_SCALE));
+    assert_eq!((snapshot.valuation_age, snapshot.valuation_is_fresh), (None, false));
+
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+    vault.send(vault.deploy_ix(&bot.pubkey(), 180_000), &bot).await.unwrap();
+    warp_clock(&mut vault.ctx, FEE_COLLECTION_INTERVAL - 60).await;
+    let timestamp = now(&mut vault.ctx).await;
+    vault.send(vault.valuation_ix(&bot.pubkey(), 240_000, 60_000, timestamp), &bot).await.unwrap();
+    vault.send(vault.pause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+
+    let at = warp_clock(&mut vault.ctx, 30).await;
+    assert_eq!(
+        vault.vault_snapshot().await,
+        VaultSnapshot {
+            nav: 245_000,
+            total_value: 260_000,
+            total_shares: 200_000,
+            price_per_share: 1_225_000_000,
+            liquid: 20_000,
+            buffer_target: 26_000,
+            buffer_deficit_since: None,
+            deployed_value: 240_000,
+            deployed_cost: 180_000,
+            asset_holdings_value: 0,
+            accumulated_fees: 0,
+            pending_unrealized_fees: 15_000,
+            valuation_age: Some(30),
+            valuation_is_fresh: true,
+            pause_flags: PAUSE_DEPOSITS,
+            emergency_mode: false,
+            lifecycle: VaultLifecycle::Active,
+            timestamp: at,
+        }
+    );
+
+    // Nothing is due until the fee interval has passed
+    let position = vault.position_snapshot(&alice.pubkey()).await;
+    let next_fee_collection = position.next_fee_collection;
+    assert_eq!(
+        position,
+        PositionSnapshot {
+            owner: alice.pubkey(),
+            shares: 100_000,
+            value: 122_500,
+            high_water_mark: 100_000,
+            cost_basis: 100_000,
+            unrealized_pnl: 22_500,
+            fee_due: 0,
+            next_fee_collection,
+            lifetime_fees_paid: 0,
+            timestamp: at,
+        }
+    );
+    assert!(next_fee_collection > at);
+
+    // Past it, the fee due is exactly what collection charges
+    warp_clock(&mut vault.ctx, next_fee_collection - at).await;
+    assert_eq!(vault.position_snapshot(&alice.pubkey()).await.fee_due, 5_625);
+    vault.send(vault.collect_user_fees_ix(&bot.pubkey(), &alice.pubkey()), &bot).await.unwrap();
+    let position = vault.position_snapshot(&alice.pubkey()).await;
+    assert_eq!((position.fee_due, position.lifetime_fees_paid), (0, 5_625));
+    assert_eq!(position.high_water_mark, 116_875);
+    assert_eq!(vault.vault_snapshot().await.accumulated_fees, 5_625);
+}
+
+#[tokio::test]
+async fn previews_match_the_mutating_instructions() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    vault.send(vault.deploy_ix(&bot.pubkey(), 90_000), &bot).await.unwrap();
+    let timestamp = now(&mut vault.ctx).await;
+    vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, timestamp), &bot).await.unwrap();
+
+    // 50_000 into a 122_500 pool of 100_000 shares; the preview is an exact slippage bound
+    let preview = vault.preview_deposit(&bob.pubkey(), 50_000).await.unwrap();
+    assert_eq!(preview, DepositPreview { shares: 40_816, received: 50_000, transfer_fee: 0 });
+    let result = vault.send(vault.deposit_min_ix(&bob.pubkey(), 50_000, preview.shares + 1), &bob).await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+    vault.send(vault.deposit_min_ix(&bob.pubkey(), 50_000, preview.shares), &bob).await.unwrap();
+    assert_eq!(vault.position(&bob.pubkey()).await.user_shares, preview.shares);
+
+    let before = vault.position(&alice.pubkey()).await;
+    let preview = vault.preview_withdraw(&alice.pubkey(), 20_000).await.unwrap();
+    assert_eq!((preview.received, preview.transfer_fee), (20_000, 0));
+    assert_eq!(preview.realized_pnl, 20_000 - preview.cost_basis_released as i64);
+    let result = vault.send(vault.withdraw_max_ix(&alice.pubkey(), 20_000, preview.shares - 1), &alice).await;
+    assert_vault_error(result, VaultError::SlippageExceeded);
+    vault.send(vault.withdraw_max_ix(&alice.pubkey(), 20_000, preview.shares), &alice).await.unwrap();
+    let after = vault.position(&alice.pubkey()).await;
+    assert_eq!(before.user_shares - after.user_shares, preview.shares);
+    assert_eq!(before.cost_basis - after.cost_basis, preview.cost_basis_released);
+
+    // Previews fail exactly where the instruction would
+    let treasury = vault.token_balance(treasury_pda()).await;
+    let result = vault.preview_withdraw(&alice.pubkey(), treasury + 1).await.map(drop);
+    assert_vault_error(result, VaultError::InsufficientLiquidity);
+    vault.send(vault.pause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+    let result = vault.preview_deposit(&bob.pubkey(), 1_000).await.map(drop);
+    assert_vault_error(result, VaultError::ProtocolPaused);
+
+    // The fee preview prices collection before the interval allows it
+    let early = vault.preview_fee(&alice.pubkey()).await;
+    assert!(!early.eligible && early.fee > 0);
+    let result = vault.send(vault.collect_user_fees_ix(&bot.pubkey(), &alice.pubkey()), &bot).await;
+    assert_vault_error(result, VaultError::FeeCollectionTooSoon);
+
+    let wait = early.next_fee_collection - now(&mut vault.ctx).await;
+    let timestamp = warp_clock(&mut vault.ctx, wait).await;
+    vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, timestamp), &bot).await.unwrap();
+    let preview = vault.preview_fee(&alice.pubkey()).await;
+    assert!(preview.eligible);
+    let before = vault.position(&alice.pubkey()).await;
+    vault.send(vault.collect_user_fees_ix(&bot.pubkey(), &alice.pubkey()), &bot).await.unwrap();
+    let after = vault.position(&alice.pubkey()).await;
+    assert_eq!(after.lifetime_fees_paid, preview.fee);
+    assert_eq!(before.user_shares - after.user_shares, preview.shares_reduced);
+    assert_eq!(after.high_water_mark, preview.high_water_mark);
+}
+
+#[tokio::test]
+async fn partial_withdrawals_release_average_cost_and_realize_pnl() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+
+    // A 10% gain: 22_000 out burns 20_000 shares carrying 20_000 of basis
+    let ix =
+        spl_token::instruction::mint_to(&spl_token::ID, &vault.mint, &treasury_pda(), &admin.pubkey(), &[], 20_000)
+            .unwrap();
+    vault.send(ix, &admin).await.unwrap();
+    let preview = vault.preview_withdraw(&alice.pubkey(), 22_000).await.unwrap();
+    assert_eq!((preview.shares, preview.cost_basis_released, preview.realized_pnl), (20_000, 20_000, 2_000));
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 22_000), &alice).await.unwrap();
+    let position = vault.position(&alice.pubkey()).await;
+    assert_eq!((position.user_shares, position.cost_basis), (80_000, 80_000));
+
+    // A 60_000 trading loss takes the price to 138_000 / 180_000
+    vault.send(vault.deploy_ix(&bot.pubkey(), 100_000), &bot).await.unwrap();
+    vault.send(vault.return_ix(&bot.pubkey(), 40_000, 100_000), &bot).await.unwrap();
+    let preview = vault.preview_withdraw(&alice.pubkey(), 23_000).await.unwrap();
+    assert_eq!((preview.shares, preview.cost_basis_released, preview.realized_pnl), (30_000, 30_000, -7_000));
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 23_000), &alice).await.unwrap();
+    let position = vault.position(&alice.pubkey()).await;
+    assert_eq!((position.user_shares, position.cost_basis), (50_000, 50_000));
+
+    // The rest of the basis goes with the last shares
+    let snapshot = vault.position_snapshot(&alice.pubkey()).await;
+    assert_eq!((snapshot.value, snapshot.cost_basis), (38_333, 50_000));
+    assert_eq!(snapshot.unrealized_pnl, -11_667);
+    vault.send(vault.redeem_all_ix(&alice.pubkey()), &alice).await.unwrap();
+    let position = vault.position(&alice.pubkey()).await;
+    assert_eq!((position.user_shares, position.cost_basis), (0, 0));
+}
+
+#[tokio::test]
+async fn buffer_deficit_blocks_deployment_until_capital_returns() {
+    let mut vault = start_vault().await;
+    let (bot, alice, outsider) =
+        (vault.bot.insecure_clone(), vault.alice.insecure_clone(), vault.outsider.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+
+    // Deployment may not take the treasury below 10% of total value
+    vault.send(vault.deploy_ix(&bot.pubkey(), 85_000), &bot).await.unwrap();
+    let result = vault.send(vault.deploy_ix(&bot.pubkey(), 5_001), &bot).await;
+    assert_vault_error(result, VaultError::LiquidityBufferDeficit);
+
+    // Withdrawals can; anyone may flag it
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 6_000), &alice).await.unwrap();
+    let flagged_at = warp_clock(&mut vault.ctx, 60).await;
+    vault.send(vault.check_buffer_ix(), &outsider).await.unwrap();
+    let snapshot = vault.vault_snapshot().await;
+    assert_eq!((snapshot.liquid, snapshot.buffer_target), (9_000, 9_400));
+    assert_eq!(snapshot.buffer_deficit_since, Some(flagged_at));
+
+    // Later checks keep the original start while the deficit lasts
+    warp_clock(&mut vault.ctx, 60).await;
+    vault.send(vault.check_buffer_ix(), &outsider).await.unwrap();
+    assert_eq!(vault.config().await.buffer_deficit_since, flagged_at);
+    let result = vault.send(vault.deploy_ix(&bot.pubkey(), 100), &bot).await;
+    assert_vault_error(result, VaultError::LiquidityBufferDeficit);
+
+    // Returned capital that restores the buffer clears the flag and reopens deployment
+    vault.send(vault.return_ix(&bot.pubkey(), 20_000, 20_000), &bot).await.unwrap();
+    assert_eq!(vault.config().await.buffer_deficit_since, 0);
+    vault.send(vault.deploy_ix(&bot.pubkey(), 100), &bot).await.unwrap();
+}
+
+#[tokio::test]
+async fn share_price_history_reports_annualized_returns() {
+    const DAY: i64 = SHARE_PRICE_SNAPSHOT_INTERVAL;
+    let mut vault = start_vault().await;
+    let (admin, alice, outsider) =
+        (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.outsider.insecure_clone());
+
+    let result = vault.send(vault.initialize_share_price_history_ix(&outsider.pubkey()), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    vault.send(vault.initialize_share_price_history_ix(&admin.pubkey()), &admin).await.unwrap();
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+
+    // Anyone may snapshot, once per interval
+    vault.send(vault.snapshot_ix(), &outsider).await.unwrap();
+    warp_clock(&mut vault.ctx, DAY - 1).await;
+    assert_vault_error(vault.send(vault.snapshot_ix(), &outsider).await, VaultError::SnapshotTooSoon);
+    assert_eq!(vault.share_price_returns(vec![DAY]).await.unwrap(), vec![None]);
+
+    // A week of 100 tokens a day landing in the treasury
+    warp_clock(&mut vault.ctx, 1).await;
+    for _ in 0..7 {
+        let ix =
+            spl_token::instruction::mint_to(&spl_token::ID, &vault.mint, &treasury_pda(), &admin.pubkey(), &[], 100)
+                .unwrap();
+        vault.send(ix, &admin).await.unwrap();
+        vault.send(vault.snapshot_ix(), &outsider).await.unwrap();
+        warp_clock(&mut vault.ctx, DAY).await;
+    }
+
+    let returns = vault.share_price_returns(vec![7 * DAY, 30 * DAY]).await.unwrap();
+    let week = returns[0].clone().unwrap();
+    assert_eq!((week.start_price, week.end_price), (SHARE_PRICE_SCALE, 1_007_000_000));
+    assert_eq!(week.end_timestamp - week.start_timestamp, 7 * DAY);
+    assert_eq!(week.period_return_bps, 70);
+    assert_eq!(week.apy_bps, accounting::annualized_return_bps(week.start_price, week.end_price, 7 * DAY).unwrap());
+    assert_eq!(returns[1], None);
+
+    let result = vault.share_price_returns(vec![DAY; MAX_RETURN_WINDOWS + 1]).await.map(|_| ());
+    assert_vault_error(result, VaultError::InvalidReturnWindow);
+
+    // Emergency NAV leaves out deployed capital, so it is not recorded
+    vault.send(vault.enter_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+    assert_vault_error(vault.send(vault.snapshot_ix(), &outsider).await, VaultError::EmergencyModeActive);
+}
+
+#[tokio::test]
+async fn stale_valuation_falls_back_to_deployed_cost() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice) = (vault.admin.insecure_clone(), vault.bot.insecure_clone(), vault.alice.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    vault.send(vault.deploy_ix(&bot.pubkey(), 90_000), &bot).await.unwrap();
+
+    // Valuations must be stamped within the last five minutes, never ahead of the cluster
+    let timestamp = now(&mut vault.ctx).await;
+    let result = vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, timestamp - 301), &bot).await;
+    assert_vault_error(result, VaultError::InvalidValuation);
+    let result = vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, timestamp + 1), &bot).await;
+    assert_vault_error(result, VaultError::InvalidValuation);
+
+    vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, timestamp), &bot).await.unwrap();
+    // 10_000 + 120_000 - 7_500
+    assert_eq!(vault.user_balance(&alice.pubkey()).await, 122_500);
+
+    // Still fresh at exactly the threshold
+    warp_clock(&mut vault.ctx, STALE_VALUATION_THRESHOLD).await;
+    assert_eq!(vault.user_balance(&alice.pubkey()).await, 122_500);
+
+    // One second later the deployed capital counts at cost
+    warp_clock(&mut vault.ctx, 1).await;
+    assert_eq!(vault.user_balance(&alice.pubkey()).await, 10_000 + 90_000 - 7_500);
+
+    // A stale mark shows no gain over the high-water mark, so collection charges nothing
+    let collected_at = warp_clock(&mut vault.ctx, FEE_COLLECTION_INTERVAL).await;
+    vault.send(vault.collect_user_fees_ix(&admin.pubkey(), &alice.pubkey()), &admin).await.unwrap();
+    let position = vault.position(&alice.pubkey()).await;
+    assert_eq!((position.lifetime_fees_paid, position.user_shares), (0, 100_000));
+    assert_eq!(position.last_fee_collection, collected_at);
+    assert_eq!(vault.config().await.accumulated_fees, 0);
+
+    // A fresh valuation restores the marked value
+    vault.send(vault.valuation_ix(&bot.pubkey(), 120_000, 30_000, collected_at), &bot).await.unwrap();
+    assert_eq!(vault.user_balance(&alice.pubkey()).await, 122_500);
+}
+
+#[tokio::test]
+async fn revocation_overrides_allowlist_proofs() {
+    let mut vault = start_vault().await;
+    let (admin, alice, outsider) =
+        (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.outsider.insecure_clone());
+    let [alice_leaf, outsider_leaf] =
+        [&alice, &outsider].map(|user| keccak::hashv(&[user.pubkey().as_ref()]).to_bytes());
+    let (low, high) =
+        if alice_leaf <= outsider_leaf { (alice_leaf, outsider_leaf) } else { (outsider_leaf, alice_leaf) };
+    let root = keccak::hashv(&[&low, &high]).to_bytes();
+    vault.send(vault.set_access_mode_ix(&admin.pubkey(), AccessMode::Restricted, root), &admin).await.unwrap();
+
+    // Allowlisted but revoked: the attestation PDA is always checked, so the proof doesn't help
+    let result = vault.send(vault.create_position_ix(&outsider.pubkey(), vec![]), &outsider).await;
+    assert_vault_error(result, VaultError::AccessDenied);
+    vault.send(vault.revoke_access_ix(&admin.pubkey(), &outsider.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.create_position_ix(&outsider.pubkey(), vec![alice_leaf]), &outsider).await;
+    assert_vault_error(result, VaultError::AccessRevoked);
+
+    // Alice's proof admits deposits until she is revoked; withdrawals stay open
+    vault.send(vault.deposit_proof_ix(&alice.pubkey(), 10_000, 0, vec![outsider_leaf]), &alice).await.unwrap();
+    vault.send(vault.revoke_access_ix(&admin.pubkey(), &alice.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.deposit_proof_ix(&alice.pubkey(), 10_000, 1, vec![outsider_leaf]), &alice).await;
+    assert_vault_error(result, VaultError::AccessRevoked);
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 5_000), &alice).await.unwrap();
+
+    // A fresh attestation lifts the revocation without a proof
+    vault.send(vault.issue_attestation_ix(&admin.pubkey(), &alice.pubkey(), 0), &admin).await.unwrap();
+    vault.send(vault.deposit_ix(&alice.pubkey(), 10_000), &alice).await.unwrap();
+    assert_eq!(vault.position(&alice.pubkey()).await.user_shares, 15_000);
+}
+
+#[tokio::test]
+async fn privileged_instructions_reject_other_signers() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob, outsider) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+        vault.outsider.insecure_clone(),
+    );
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    vault.send(vault.deploy_ix(&bot.pubkey(), 50_000), &bot).await.unwrap();
+    let timestamp = warp_clock(&mut vault.ctx, FEE_COLLECTION_INTERVAL).await;
+    let before = vault.config().await;
+
+    // Trading bot only
+    let outsider_key = outsider.pubkey();
+    let result = vault.send(vault.deploy_ix(&outsider_key, 10_000), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedTradingBot);
+    let result = vault.send(vault.deploy_ix(&admin.pubkey(), 10_000), &admin).await;
+    assert_vault_error(result, VaultError::UnauthorizedTradingBot);
+    let result = vault.send(vault.return_ix(&outsider_key, 10_000, 10_000), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedTradingBot);
+    let result = vault.send(vault.valuation_ix(&outsider_key, 1_000_000, 950_000, timestamp), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedTradingBot);
+
+    // Admin or trading bot
+    let result = vault.send(vault.collect_user_fees_ix(&outsider_key, &alice.pubkey()), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+    let result = vault.send(vault.collect_batch_fees_ix(&outsider_key, &[&alice.pubkey()]), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+    let result = vault.send(vault.collect_user_fees_ix(&alice.pubkey(), &alice.pubkey()), &alice).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+
+    // Admin (or guardian, for pausing) only
+    let result = vault.send(vault.collect_performance_fees_ix(&bot.pubkey()), &bot).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    let result = vault.send(vault.pause_ix(&bot.pubkey(), PAUSE_ALL), &bot).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+    let result = vault.send(vault.enter_emergency_ix(&outsider_key), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+    vault.send(vault.pause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+    let result = vault.send(vault.unpause_ix(&bot.pubkey(), PAUSE_DEPOSITS), &bot).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    vault.send(vault.enter_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.exit_emergency_ix(&outsider_key), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    vault.send(vault.exit_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+    vault.send(vault.unpause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+
+    // Positions are bound to their owner's signature
+    let data = grid_vault::instruction::Withdraw { amount: 1_000, max_shares: u64::MAX }.data();
+    let steal = ix(vault.withdraw_accounts(&outsider_key, &alice.pubkey()), data);
+    assert_anchor_error(vault.send(steal, &outsider).await, ErrorCode::ConstraintSeeds);
+    let result = vault.send(vault.withdraw_ix(&bob.pubkey(), 1_000), &bob).await;
+    assert_vault_error(result, VaultError::InsufficientBalance);
+
+    // None of it moved funds or touched accounting
+    let after = vault.config().await;
+    assert_eq!(after.total_shares, before.total_shares);
+    assert_eq!(after.total_trading_deployed, before.total_trading_deployed);
+    assert_eq!(after.accumulated_fees, before.accumulated_fees);
+    assert_eq!((after.pause_flags, after.emergency_mode), (0, false));
+    assert_eq!(after.last_valuation_timestamp, 0);
+    assert_eq!(vault.token_balance(treasury_pda()).await, 50_000);
+    assert_eq!(
+        vault.position(&alice.pubkey()).await.last_fee_collection,
+        vault.position(&bob.pubkey()).await.last_fee_collection
+    );
+}
+
+#[tokio::test]
+async fn pauses_and_emergency_mode_gate_operations() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+    vault.send(vault.deploy_ix(&bot.pubkey(), 100_000), &bot).await.unwrap();
+
+    // Pausing deposits leaves withdrawals open
+    vault.send(vault.pause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&alice.pubkey(), 1_000), &alice).await;
+    assert_vault_error(result, VaultError::ProtocolPaused);
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 10_000), &alice).await.unwrap();
+
+    // Withdrawals are capped by what the treasury actually holds
+    let result = vault.send(vault.withdraw_ix(&bob.pubkey(), 95_000), &bob).await;
+    assert_vault_error(result, VaultError::InsufficientLiquidity);
+
+    vault.send(vault.pause_ix(&admin.pubkey(), PAUSE_FEE_COLLECTION), &admin).await.unwrap();
+    warp_clock(&mut vault.ctx, FEE_COLLECTION_INTERVAL).await;
+    let result = vault.send(vault.collect_batch_fees_ix(&bot.pubkey(), &[&alice.pubkey()]), &bot).await;
+    assert_vault_error(result, VaultError::ProtocolPaused);
+    vault.send(vault.unpause_ix(&admin.pubkey(), PAUSE_ALL), &admin).await.unwrap();
+    assert_eq!(vault.config().await.pause_flags, 0);
+
+    // Emergency mode stops everything except pro-rata exits and returning capital
+    vault.send(vault.enter_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.withdraw_ix(&bob.pubkey(), 1_000), &bob).await;
+    assert_vault_error(result, VaultError::EmergencyModeActive);
+    let result = vault.send(vault.deposit_ix(&bob.pubkey(), 1_000), &bob).await;
+    assert_vault_error(result, VaultError::EmergencyModeActive);
+    let result = vault.send(vault.deploy_ix(&bot.pubkey(), 1_000), &bot).await;
+    assert_vault_error(result, VaultError::EmergencyModeActive);
+    let result = vault.send(vault.collect_batch_fees_ix(&bot.pubkey(), &[&alice.pubkey()]), &bot).await;
+    assert_vault_error(result, VaultError::EmergencyModeActive);
+
+    // Bob's half of the shares against the 90_000 left in the treasury
+    let bob_tokens = vault.token_balance(vault.tokens(&bob.pubkey())).await;
+    vault.send(vault.emergency_withdraw_ix(&bob.pubkey(), 100_000), &bob).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&bob.pubkey())).await - bob_tokens, 100_000 * 90_000 / 190_000);
+
+    // Capital comes home, the admin lifts emergency mode and the rest exit at full value
+    vault.send(vault.return_ix(&bot.pubkey(), 100_000, 100_000), &bot).await.unwrap();
+    vault.send(vault.exit_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+    let treasury = vault.token_balance(treasury_pda()).await;
+    let alice_tokens = vault.token_balance(vault.tokens(&alice.pubkey())).await;
+    vault.send(vault.redeem_all_ix(&alice.pubkey()), &alice).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&alice.pubkey())).await - alice_tokens, treasury);
+    assert_eq!(vault.config().await.total_shares, 0);
+}
+
+#[tokio::test]
+async fn guardian_pauses_only_known_operations() {
+    let mut vault = start_vault().await;
+    let (admin, alice, outsider) =
+        (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.outsider.insecure_clone());
+
+    // Pausing or unpausing nothing, or bits outside `PAUSE_ALL`, is refused
+    for operations in [0, PAUSE_ALL + 1, u8::MAX] {
+        let result = vault.send(vault.pause_ix(&admin.pubkey(), operations), &admin).await;
+        assert_vault_error(result, VaultError::InvalidPauseFlags);
+        let result = vault.send(vault.unpause_ix(&admin.pubkey(), operations), &admin).await;
+        assert_vault_error(result, VaultError::InvalidPauseFlags);
+    }
+    assert_eq!(vault.config().await.pause_flags, 0);
+
+    // Only the admin appoints a guardian, who may then pause but not unpause
+    let result = vault.send(vault.pause_ix(&outsider.pubkey(), PAUSE_DEPOSITS), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedCaller);
+    let result = vault.send(vault.set_guardian_ix(&outsider.pubkey(), outsider.pubkey()), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    vault.send(vault.set_guardian_ix(&admin.pubkey(), outsider.pubkey()), &admin).await.unwrap();
+    assert_eq!(vault.config().await.guardian, outsider.pubkey());
+    vault.send(vault.pause_ix(&outsider.pubkey(), PAUSE_DEPOSITS), &outsider).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&alice.pubkey(), 1_000), &alice).await;
+    assert_vault_error(result, VaultError::ProtocolPaused);
+    let result = vault.send(vault.unpause_ix(&outsider.pubkey(), PAUSE_DEPOSITS), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    vault.send(vault.unpause_ix(&admin.pubkey(), PAUSE_DEPOSITS), &admin).await.unwrap();
+    vault.send(vault.deposit_ix(&alice.pubkey(), 1_000), &alice).await.unwrap();
+}
+
+#[tokio::test]
+async fn wind_down_claims_and_escrow_charge_the_final_fee() {
+    let mut vault = start_vault().await;
+    let (admin, alice, bob) = (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.bob.insecure_clone());
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+    // 20_000 of profit per position; a quarter of it is owed as fees on exit
+    let ix =
+        spl_token::instruction::mint_to(&spl_token::ID, &vault.mint, &treasury_pda(), &admin.pubkey(), &[], 40_000)
+            .unwrap();
+    vault.send(ix, &admin).await.unwrap();
+
+    vault.send(vault.close_deposits_ix(&admin.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&alice.pubkey(), 1_000), &alice).await;
+    assert_vault_error(result, VaultError::DepositsClosed);
+    let result = vault.send(vault.claim_final_balance_ix(&alice.pubkey()), &alice).await;
+    assert_vault_error(result, VaultError::InvalidLifecycleTransition);
+    vault.send(vault.begin_wind_down_ix(&admin.pubkey()), &admin).await.unwrap();
+
+    // Alice claims during the grace period and leaves her fee in the treasury
+    let alice_tokens = vault.token_balance(vault.tokens(&alice.pubkey())).await;
+    vault.send(vault.claim_final_balance_ix(&alice.pubkey()), &alice).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&alice.pubkey())).await - alice_tokens, 115_000);
+    assert_eq!(vault.config().await.accumulated_fees, 5_000);
+
+    let result = vault.send(vault.close_vault_ix(&admin.pubkey()), &admin).await;
+    assert_vault_error(result, VaultError::GracePeriodActive);
+    warp_clock(&mut vault.ctx, WIND_DOWN_GRACE_PERIOD).await;
+    vault.send(vault.close_vault_ix(&admin.pubkey()), &admin).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&admin.pubkey())).await, 5_000);
+    assert_eq!(vault.token_balance(claims_escrow_vault_pda()).await, 120_000);
+
+    // The config outlives the close, so the vault can't be initialized again
+    let config = vault.config().await;
+    assert_eq!((config.lifecycle, config.accumulated_fees), (VaultLifecycle::Closed, 0));
+    assert!(vault.send(vault.initialize_ix(&alice.pubkey()), &alice).await.is_err());
+
+    // Bob's unclaimed balance pays the same fee out of escrow
+    let bob_tokens = vault.token_balance(vault.tokens(&bob.pubkey())).await;
+    vault.send(vault.claim_from_escrow_ix(&bob.pubkey()), &bob).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&bob.pubkey())).await - bob_tokens, 115_000);
+    assert_eq!(vault.token_balance(vault.tokens(&admin.pubkey())).await, 10_000);
+    assert_eq!(vault.token_balance(claims_escrow_vault_pda()).await, 0);
+    let position = vault.position(&bob.pubkey()).await;
+    assert_eq!((position.user_shares, position.lifetime_fees_paid), (0, 5_000));
+    let result = vault.send(vault.claim_from_escrow_ix(&bob.pubkey()), &bob).await;
+    assert_vault_error(result, VaultError::InsufficientBalance);
+}
+
+#[tokio::test]
+async fn emergency_exits_pay_accepted_assets_in_kind() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+    // Two base units per asset unit: Alice's 50_000 tokens buy 100_000 shares
+    let asset = vault.add_asset(2 * 10u64.pow(DECIMALS as u32)).await;
+    vault.send(vault.deposit_asset_ix(&alice.pubkey(), &asset, 50_000), &alice).await.unwrap();
+    assert_eq!(vault.config().await.asset_holdings_value, 100_000);
+    vault.send(vault.deploy_ix(&bot.pubkey(), 100_000), &bot).await.unwrap();
+    vault.send(vault.enter_emergency_ix(&admin.pubkey()), &admin).await.unwrap();
+
+    // Leaving the asset out, or listing it twice, is refused
+    let result = vault.send(vault.emergency_withdraw_ix(&bob.pubkey(), 100_000), &bob).await;
+    assert_vault_error(result, VaultError::InvalidAccounts);
+    let result = vault.send(vault.emergency_withdraw_assets_ix(&bob.pubkey(), 100_000, &[asset, asset]), &bob).await;
+    assert_vault_error(result, VaultError::InvalidAccounts);
+
+    // A third of the shares takes a third of the liquid base and of the asset holdings
+    let bob_tokens = vault.token_balance(vault.tokens(&bob.pubkey())).await;
+    let bob_assets = vault.token_balance(get_associated_token_address(&bob.pubkey(), &asset)).await;
+    vault.send(vault.emergency_withdraw_assets_ix(&bob.pubkey(), 100_000, &[asset]), &bob).await.unwrap();
+    assert_eq!(vault.token_balance(vault.tokens(&bob.pubkey())).await - bob_tokens, 33_333);
+    assert_eq!(vault.token_balance(get_associated_token_address(&bob.pubkey(), &asset)).await - bob_assets, 16_666);
+    let position = vault.position(&bob.pubkey()).await;
+    assert_eq!((position.user_shares, position.cost_basis), (0, 0));
+    let config = vault.config().await;
+    assert_eq!((config.total_shares, config.asset_holdings_value), (200_000, 66_668));
+
+    // Partial exits release cost basis pro rata through the same accounting as `withdraw`
+    vault.send(vault.emergency_withdraw_assets_ix(&alice.pubkey(), 50_000, &[asset]), &alice).await.unwrap();
+    let position = vault.position(&alice.pubkey()).await;
+    assert_eq!((position.user_shares, position.cost_basis, position.high_water_mark), (150_000, 150_000, 150_000));
+}
+
+#[tokio::test]
+async fn stale_asset_prices_block_share_issuance() {
+    let mut vault = start_vault().await;
+    let (admin, alice, bob) = (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.bob.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    vault.send(vault.initialize_share_price_history_ix(&admin.pubkey()), &admin).await.unwrap();
+    let price = 2 * 10u64.pow(DECIMALS as u32);
+    let (usdt, sol) = (vault.add_asset(price).await, vault.add_asset(price).await);
+    for asset in [&usdt, &sol] {
+        vault.send(vault.deposit_asset_ix(&alice.pubkey(), asset, 10_000), &alice).await.unwrap();
+    }
+    let snapshot = vault.vault_snapshot().await;
+    assert_eq!((snapshot.asset_holdings_value, snapshot.total_value), (40_000, 140_000));
+
+    // Once a price is too old, holdings keep their last mark but no shares are issued against it
+    warp_clock(&mut vault.ctx, MAX_PRICE_AGE + 1).await;
+    let snapshot = vault.vault_snapshot().await;
+    assert_eq!((snapshot.asset_holdings_value, snapshot.total_value), (40_000, 140_000));
+    let result = vault.send(vault.deposit_ix(&bob.pubkey(), 10_000), &bob).await;
+    assert_vault_error(result, VaultError::AssetPriceStale);
+    let result = vault.send(vault.mint_ix(&bob.pubkey(), 10_000), &bob).await;
+    assert_vault_error(result, VaultError::AssetPriceStale);
+    let result = vault.send(vault.deposit_asset_ix(&alice.pubkey(), &sol, 1_000), &alice).await;
+    assert_vault_error(result, VaultError::AssetPriceStale);
+    assert_vault_error(vault.send(vault.snapshot_ix(), &bob).await, VaultError::AssetPriceStale);
+    assert_eq!(vault.max_deposit(&bob.pubkey()).await, 0);
+    assert_eq!(vault.preview_mint(10_000).await, 0);
+    assert_eq!(vault.preview_deposit(&bob.pubkey(), 10_000).await.unwrap().shares, 0);
+
+    // Re-pricing one asset alone can't vouch for the other's mark
+    vault.send(vault.update_asset_price_ix(&admin.pubkey(), &usdt, price, &[]), &admin).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&bob.pubkey(), 10_000), &bob).await;
+    assert_vault_error(result, VaultError::AssetPriceStale);
+    let result = vault.send(vault.update_asset_price_ix(&admin.pubkey(), &sol, price, &[sol]), &admin).await;
+    assert_vault_error(result, VaultError::InvalidAccounts);
+
+    // Listing every other asset with holdings brings the fresh marks back
+    vault.send(vault.update_asset_price_ix(&admin.pubkey(), &sol, 3 * price / 2, &[usdt]), &admin).await.unwrap();
+    let snapshot = vault.vault_snapshot().await;
+    assert_eq!((snapshot.asset_holdings_value, snapshot.total_value), (50_000, 150_000));
+    vault.send(vault.deposit_ix(&bob.pubkey(), 15_000), &bob).await.unwrap();
+    assert_eq!(vault.position(&bob.pubkey()).await.user_shares, 14_000);
+    vault.send(vault.snapshot_ix(), &bob).await.unwrap();
+
+    // Only the admin or the asset's oracle key may push prices
+    let outsider = vault.outsider.insecure_clone();
+    let result = vault.send(vault.update_asset_price_ix(&outsider.pubkey(), &sol, 1, &[]), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedOracle);
+}
+
+#[tokio::test]
+async fn swept_assets_become_deployed_capital_until_wind_down() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice) = (vault.admin.insecure_clone(), vault.bot.insecure_clone(), vault.alice.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    let asset = vault.add_asset(2 * 10u64.pow(DECIMALS as u32)).await;
+    vault.send(vault.deposit_asset_ix(&alice.pubkey(), &asset, 50_000), &alice).await.unwrap();
+    let ix = spl_associated_token_account::instruction::create_associated_token_account(
+        &admin.pubkey(),
+        &bot.pubkey(),
+        &asset,
+        &spl_token::ID,
+    );
+    vault.send(ix, &admin).await.unwrap();
+
+    // The swept mark moves from holdings to deployed capital
+    vault.send(vault.sweep_ix(&bot.pubkey(), &asset, 20_000), &bot).await.unwrap();
+    assert_eq!(vault.token_balance(get_associated_token_address(&bot.pubkey(), &asset)).await, 20_000);
+    let config = vault.config().await;
+    assert_eq!((config.asset_holdings_value, config.total_trading_deployed), (60_000, 40_000));
+
+    // Like deployments, sweeps stop once the vault winds down
+    vault.send(vault.close_deposits_ix(&admin.pubkey()), &admin).await.unwrap();
+    vault.send(vault.sweep_ix(&bot.pubkey(), &asset, 10_000), &bot).await.unwrap();
+    vault.send(vault.begin_wind_down_ix(&admin.pubkey()), &admin).await.unwrap();
+    let result = vault.send(vault.sweep_ix(&bot.pubkey(), &asset, 10_000), &bot).await;
+    assert_vault_error(result, VaultError::VaultWindingDown);
+    let result = vault.send(vault.deploy_ix(&bot.pubkey(), 10_000), &bot).await;
+    assert_vault_error(result, VaultError::VaultWindingDown);
+}
+
+#[tokio::test]
+async fn delegates_withdraw_only_within_the_owners_position() {
+    let mut vault = start_vault().await;
+    let (alice, bob, outsider) =
+        (vault.alice.insecure_clone(), vault.bob.insecure_clone(), vault.outsider.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+    // Bob funds Alice's position from his own tokens
+    let bob_tokens = vault.token_balance(vault.tokens(&bob.pubkey())).await;
+    vault.send(vault.deposit_for_ix(&bob.pubkey(), &alice.pubkey(), 50_000), &bob).await.unwrap();
+    assert_eq!(bob_tokens - vault.token_balance(vault.tokens(&bob.pubkey())).await, 50_000);
+    assert_eq!(vault.position(&alice.pubkey()).await.user_shares, 150_000);
+    assert_eq!(vault.position(&bob.pubkey()).await.user_shares, 0);
+    vault.send(vault.deposit_ix(&bob.pubkey(), 100_000), &bob).await.unwrap();
+
+    let alice_tokens = vault.tokens(&alice.pubkey());
+    let set = vault.set_withdrawal_delegate_ix(&alice.pubkey(), &outsider.pubkey(), &alice_tokens);
+    vault.send(set, &alice).await.unwrap();
+
+    // Only the registered delegate, only into the approved destination, only up to Alice's balance
+    let result =
+        vault.send(vault.withdraw_as_delegate_ix(&bob.pubkey(), &alice.pubkey(), &alice_tokens, 1_000), &bob).await;
+    assert_vault_error(result, VaultError::UnauthorizedDelegate);
+    let outsider_tokens = vault.tokens(&outsider.pubkey());
+    let result = vault
+        .send(vault.withdraw_as_delegate_ix(&outsider.pubkey(), &alice.pubkey(), &outsider_tokens, 1_000), &outsider)
+        .await;
+    assert_vault_error(result, VaultError::InvalidDestination);
+    let result = vault
+        .send(vault.withdraw_as_delegate_ix(&outsider.pubkey(), &alice.pubkey(), &alice_tokens, 150_001), &outsider)
+        .await;
+    assert_vault_error(result, VaultError::InsufficientBalance);
+
+    let before = vault.token_balance(alice_tokens).await;
+    vault
+        .send(vault.withdraw_as_delegate_ix(&outsider.pubkey(), &alice.pubkey(), &alice_tokens, 40_000), &outsider)
+        .await
+        .unwrap();
+    assert_eq!(vault.token_balance(alice_tokens).await - before, 40_000);
+    assert_eq!(vault.position(&alice.pubkey()).await.user_shares, 110_000);
+    assert_eq!(vault.position(&bob.pubkey()).await.user_shares, 100_000);
+
+    // Revoking closes the delegation
+    vault.send(vault.revoke_withdrawal_delegate_ix(&alice.pubkey()), &alice).await.unwrap();
+    let result = vault
+        .send(vault.withdraw_as_delegate_ix(&outsider.pubkey(), &alice.pubkey(), &alice_tokens, 1_000), &outsider)
+        .await;
+    assert_anchor_error(result, ErrorCode::AccountNotInitialized);
+}
+
+#[tokio::test]
+async fn share_conversions_round_in_the_vaults_favor() {
+    let mut vault = start_vault().await;
+    let (admin, alice, bob) = (vault.admin.insecure_clone(), vault.alice.insecure_clone(), vault.bob.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 300_000), &alice).await.unwrap();
+    // A donation moves the share price to 4/3, so conversions no longer divide evenly
+    let donation =
+        spl_token::instruction::mint_to(&spl_token::ID, &vault.mint, &treasury_pda(), &admin.pubkey(), &[], 100_000)
+            .unwrap();
+    vault.send(donation, &admin).await.unwrap();
+
+    // Redeeming 1_000 shares is worth 1_333.33, paid as 1_333
+    assert_eq!(vault.preview_redeem(1_000).await, 1_333);
+    let alice_tokens = vault.tokens(&alice.pubkey());
+    let before = vault.token_balance(alice_tokens).await;
+    vault.send(vault.redeem_ix(&alice.pubkey(), 1_000), &alice).await.unwrap();
+    assert_eq!(vault.token_balance(alice_tokens).await - before, 1_333);
+
+    // Minting 1_000 shares against 398_667 / 299_000 costs 1_333.33, charged as 1_334
+    assert_eq!(vault.preview_mint(1_000).await, 1_334);
+    let bob_tokens = vault.tokens(&bob.pubkey());
+    let before = vault.token_balance(bob_tokens).await;
+    vault.send(vault.mint_ix(&bob.pubkey(), 1_000), &bob).await.unwrap();
+    assert_eq!(before - vault.token_balance(bob_tokens).await, 1_334);
+    assert_eq!(vault.position(&bob.pubkey()).await.user_shares, 1_000);
+
+    // Withdrawing 1_000 assets against 400_001 / 300_000 needs 749.998 shares, burned as 750
+    assert_eq!(vault.preview_withdraw(&alice.pubkey(), 1_000).await.unwrap().shares, 750);
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 1_000), &alice).await.unwrap();
+    assert_eq!(vault.position(&alice.pubkey()).await.user_shares, 299_000 - 750);
+    let config = vault.config().await;
+    assert_eq!(config.total_shares, 300_000 - 750);
+    assert_eq!(vault.token_balance(treasury_pda()).await, 399_001);
+}
+
+#[tokio::test]
+async fn deposit_limits_reject_with_distinct_errors() {
+    let mut vault = start_vault().await;
+    let (admin, alice, bob, outsider) = (
+        vault.admin.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+        vault.outsider.insecure_clone(),
+    );
+
+    // Only the admin sets limits, and the minimum can't exceed the per-user cap
+    let result = vault.send(vault.set_deposit_limits_ix(&outsider.pubkey(), 300_000, 200_000, 10_000), &outsider).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+    let result = vault.send(vault.set_deposit_limits_ix(&admin.pubkey(), 300_000, 200_000, 200_001), &admin).await;
+    assert_vault_error(result, VaultError::InvalidAmount);
+    vault.send(vault.set_deposit_limits_ix(&admin.pubkey(), 300_000, 200_000, 10_000), &admin).await.unwrap();
+    let config = vault.config().await;
+    assert_eq!((config.max_total_deposits, config.max_position_size, config.min_deposit), (300_000, 200_000, 10_000));
+
+    // Each limit rejects one unit past it and accepts a deposit landing exactly on it
+    let result = vault.send(vault.deposit_ix(&alice.pubkey(), 9_999), &alice).await;
+    assert_vault_error(result, VaultError::DepositBelowMinimum);
+    vault.send(vault.deposit_ix(&alice.pubkey(), 10_000), &alice).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&alice.pubkey(), 190_001), &alice).await;
+    assert_vault_error(result, VaultError::PositionLimitExceeded);
+    vault.send(vault.deposit_ix(&alice.pubkey(), 190_000), &alice).await.unwrap();
+    let result = vault.send(vault.deposit_ix(&bob.pubkey(), 100_001), &bob).await;
+    assert_vault_error(result, VaultError::VaultCapExceeded);
+    vault.send(vault.deposit_ix(&bob.pubkey(), 100_000), &bob).await.unwrap();
+
+    assert_eq!(vault.position(&alice.pubkey()).await.user_shares, 200_000);
+    assert_eq!(vault.config().await.total_shares, 300_000);
+    assert_eq!(vault.max_deposit(&bob.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn only_the_owner_closes_an_empty_position() {
+    let mut vault = start_vault().await;
+    let (alice, outsider) = (vault.alice.insecure_clone(), vault.outsider.insecure_clone());
+    vault.send(vault.deposit_ix(&alice.pubkey(), 100_000), &alice).await.unwrap();
+
+    let result = vault.send(vault.close_position_ix(&alice.pubkey(), &alice.pubkey()), &alice).await;
+    assert_vault_error(result, VaultError::PositionNotEmpty);
+    vault.send(vault.redeem_all_ix(&alice.pubkey()), &alice).await.unwrap();
+
+    // Nobody else can close it, even once it's empty
+    let result = vault.send(vault.close_position_ix(&outsider.pubkey(), &alice.pubkey()), &outsider).await;
+    assert_anchor_error(result, ErrorCode::ConstraintSeeds);
+
+    // Closing deletes the account and refunds its rent to the owner, who pays the 5_000 lamport signature fee
+    let rent = vault.ctx.banks_client.get_balance(position_pda(&alice.pubkey())).await.unwrap();
+    assert_eq!(rent, Rent::default().minimum_balance(UserPosition::LEN));
+    let before = vault.ctx.banks_client.get_balance(alice.pubkey()).await.unwrap();
+    vault.send(vault.close_position_ix(&alice.pubkey(), &alice.pubkey()), &alice).await.unwrap();
+    let after = vault.ctx.banks_client.get_balance(alice.pubkey()).await.unwrap();
+    assert_eq!(after - before, rent - 5_000);
+    assert!(vault.ctx.banks_client.get_account(position_pda(&alice.pubkey())).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn unrealized_pnl_tracks_value_against_remaining_basis() {
+    let mut vault = start_vault().await;
+    let (admin, bot, alice, bob) = (
+        vault.admin.insecure_clone(),
+        vault.bot.insecure_clone(),
+        vault.alice.insecure_clone(),
+        vault.bob.insecure_clone(),
+    );
+    for user in [&alice, &bob] {
+        vault.send(vault.deposit_ix(&user.pubkey(), 100_000), user).await.unwrap();
+    }
+    assert_eq!(vault.unrealized_pnl(&alice.pubkey()).await, 0);
+
+    // A 25% gain on the pool shows up as a gain on each position
+    let gain =
+        spl_token::instruction::mint_to(&spl_token::ID, &vault.mint, &treasury_pda(), &admin.pubkey(), &[], 50_000)
+            .unwrap();
+    vault.send(gain, &admin).await.unwrap();
+    assert_eq!(vault.unrealized_pnl(&alice.pubkey()).await, 25_000);
+
+    // Withdrawing half realizes half the gain; the rest stays unrealized against half the basis
+    vault.send(vault.withdraw_ix(&alice.pubkey(), 62_500), &alice).await.unwrap();
+    assert_eq!(vault.position(&alice.pubkey()).await.cost_basis, 50_000);
+    assert_eq!(vault.unrealized_pnl(&alice.pubkey()).await, 12_500);
+
+    // Trading loses 60_000 of the remaining 187_500, leaving a price of 0.85 per share
+    vault.send(vault.deploy_ix(&bot.pubkey(), 100_000), &bot).await.unwrap();
+    vault.send(vault.return_ix(&bot.pubkey(), 40_000, 100_000), &bot).await.unwrap();
+    assert_eq!(vault.unrealized_pnl(&alice.pubkey()).await, -7_500);
+    assert_eq!(vault.unrealized_pnl(&bob.pubkey()).await, -15_000);
+}
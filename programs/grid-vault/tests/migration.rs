@@ -0,0 +1,196 @@
+mod common;
+
+use anchor_lang::{
+    prelude::Pubkey, solana_program::instruction::Instruction, AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use common::{assert_vault_error, config_pda, fund, position_pda, program_test, send};
+use grid_vault::{
+    AccessMode, ProtocolConfig, UserPosition, VaultError, VaultLifecycle, PAUSE_DEPOSITS, PAUSE_WITHDRAWALS,
+    PROTOCOL_CONFIG_VERSION, USER_POSITION_VERSION,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    account::Account,
+    rent::Rent,
+    signature::{keypair_from_seed, Signer},
+    system_program,
+};
+
+// Fixtures are byte-for-byte accounts written by the pre-versioning program.
+// protocol_config_v0.bin: admin = keypair_from_seed([1; 32]), 32 trailing padding bytes.
+// protocol_config_v1.bin: same fields with `is_paused = true`, trimmed to the v1 length.
+// user_position_v0.bin: owner = keypair_from_seed([4; 32]), 7 trailing padding bytes.
+const PROTOCOL_CONFIG_V0: &[u8] = include_bytes!("fixtures/protocol_config_v0.bin");
+const PROTOCOL_CONFIG_V1: &[u8] = include_bytes!("fixtures/protocol_config_v1.bin");
+const USER_POSITION_V0: &[u8] = include_bytes!("fixtures/user_position_v0.bin");
+
+fn fixture_account(data: &[u8]) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data: data.to_vec(),
+        owner: grid_vault::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+async fn start_with_fixtures(config_fixture: &[u8]) -> ProgramTestContext {
+    let mut program_test = program_test();
+    program_test.add_account(config_pda(), fixture_account(config_fixture));
+    let owner = keypair_from_seed(&[4; 32]).unwrap();
+    program_test.add_account(position_pda(&owner.pubkey()), fixture_account(USER_POSITION_V0));
+    for seed in [1u8, 4, 9] {
+        fund(&mut program_test, &keypair_from_seed(&[seed; 32]).unwrap());
+    }
+    program_test.start_with_context().await
+}
+
+fn migrate_config_ix(admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::MigrateConfig {
+            admin: *admin,
+            protocol_config: config_pda(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::MigrateConfig {}.data(),
+    }
+}
+
+fn migrate_position_ix(owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::MigratePosition {
+            owner: *owner,
+            user_position: position_pda(owner),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::MigratePosition {}.data(),
+    }
+}
+
+#[test]
+fn legacy_configs_keep_version_at_fixed_offset() {
+    assert_eq!(PROTOCOL_CONFIG_V0[ProtocolConfig::VERSION_OFFSET], 0);
+    assert_eq!(PROTOCOL_CONFIG_V1[ProtocolConfig::VERSION_OFFSET], 1);
+    assert_eq!(PROTOCOL_CONFIG_V1.len(), ProtocolConfig::VERSION_OFFSET + 1);
+    assert_eq!(USER_POSITION_V0[UserPosition::VERSION_OFFSET], 0);
+}
+
+#[tokio::test]
+async fn migrate_config_upgrades_legacy_layout() {
+    let mut ctx = start_with_fixtures(PROTOCOL_CONFIG_V0).await;
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+
+    send(&mut ctx, migrate_config_ix(&admin.pubkey()), &admin).await.unwrap();
+
+    let account = ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), ProtocolConfig::LEN);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(ProtocolConfig::LEN));
+
+    let config = ProtocolConfig::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(config.version, PROTOCOL_CONFIG_VERSION);
+    assert_eq!(config.admin, admin.pubkey());
+    assert_eq!(config.trading_bot, keypair_from_seed(&[2; 32]).unwrap().pubkey());
+    assert_eq!(config.total_shares, 1_000_000);
+    assert_eq!(config.total_trading_deployed, 250_000);
+    assert_eq!(config.accumulated_fees, 1_234);
+    assert_eq!(config.performance_fee_bps, 2500);
+    assert_eq!(config.pause_flags, 0);
+    assert_eq!(config.bump, 252);
+    assert_eq!(config.last_fee_sweep, 1_700_000_000);
+    assert_eq!(config.deployed_current_value, 260_000);
+    assert_eq!(config.last_valuation_timestamp, 1_700_000_500);
+    assert_eq!(config.pending_unrealized_fees, 2_500);
+    assert_eq!(config.guardian, Pubkey::default());
+    assert!(!config.emergency_mode);
+    assert_eq!(config.lifecycle, VaultLifecycle::Active);
+    assert_eq!(config.wind_down_started_at, 0);
+    assert_eq!(config.max_total_deposits, 0);
+    assert_eq!(config.max_position_size, 0);
+    assert_eq!(config.min_deposit, 0);
+    assert_eq!(config.access_mode, AccessMode::Open);
+    assert_eq!(config.merkle_root, [0; 32]);
+    assert_eq!(config.asset_holdings_value, 0);
+    assert_eq!(config.buffer_deficit_since, 0);
+    assert_eq!(config.asset_prices_updated_at, 0);
+
+    // Second run is rejected once the account is current
+    let result = send(&mut ctx, migrate_config_ix(&admin.pubkey()), &admin).await;
+    assert_vault_error(result, VaultError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_config_maps_legacy_pause_to_flags() {
+    let mut ctx = start_with_fixtures(PROTOCOL_CONFIG_V1).await;
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+
+    send(&mut ctx, migrate_config_ix(&admin.pubkey()), &admin).await.unwrap();
+
+    let account = ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), ProtocolConfig::LEN);
+    let config = ProtocolConfig::try_deserialize(&mut &account.data[..]).unwrap();
+    assert_eq!(config.version, PROTOCOL_CONFIG_VERSION);
+    assert_eq!(config.pause_flags, PAUSE_DEPOSITS | PAUSE_WITHDRAWALS);
+    assert_eq!(config.total_shares, 1_000_000);
+    assert_eq!(config.guardian, Pubkey::default());
+}
+
+#[tokio::test]
+async fn migrate_config_requires_admin() {
+    let mut ctx = start_with_fixtures(PROTOCOL_CONFIG_V0).await;
+    let intruder = keypair_from_seed(&[9; 32]).unwrap();
+
+    let result = send(&mut ctx, migrate_config_ix(&intruder.pubkey()), &intruder).await;
+    assert_vault_error(result, VaultError::UnauthorizedAdmin);
+
+    let account = ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+    assert_eq!(account.data, PROTOCOL_CONFIG_V0);
+}
+
+#[tokio::test]
+async fn migrate_position_upgrades_legacy_layout() {
+    let mut ctx = start_with_fixtures(PROTOCOL_CONFIG_V0).await;
+    let owner = keypair_from_seed(&[4; 32]).unwrap();
+
+    send(&mut ctx, migrate_position_ix(&owner.pubkey()), &owner).await.unwrap();
+
+    let account = ctx.banks_client.get_account(position_pda(&owner.pubkey())).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), UserPosition::LEN);
+    let position: &UserPosition = bytemuck::from_bytes(&account.data[8..]);
+    assert_eq!(position.version, USER_POSITION_VERSION);
+    assert_eq!(position.owner, owner.pubkey());
+    assert_eq!(position.cost_basis, 500_000);
+    assert_eq!(position.user_shares, 480_000);
+    assert_eq!(position.high_water_mark, 500_000);
+    assert_eq!(position.last_fee_collection, 1_700_000_000);
+    assert_eq!(position.lifetime_fees_paid, 42);
+    assert_eq!(position.is_active, 1);
+
+    let result = send(&mut ctx, migrate_position_ix(&owner.pubkey()), &owner).await;
+    assert_vault_error(result, VaultError::AlreadyMigrated);
+}
+
+#[tokio::test]
+async fn migrate_position_recovers_cost_basis_after_withdrawals() {
+    // The legacy program scaled the high-water mark by 240_000 / 480_000 on a withdrawal of half
+    // the shares, but left the deposit total untouched
+    let mut fixture = USER_POSITION_V0.to_vec();
+    let legacy: &mut UserPosition = bytemuck::from_bytes_mut(&mut fixture[8..]);
+    legacy.user_shares = 240_000;
+    legacy.high_water_mark = 250_000;
+    let mut program_test = program_test();
+    program_test.add_account(config_pda(), fixture_account(PROTOCOL_CONFIG_V0));
+    let owner = keypair_from_seed(&[4; 32]).unwrap();
+    program_test.add_account(position_pda(&owner.pubkey()), fixture_account(&fixture));
+    fund(&mut program_test, &owner);
+    let mut ctx = program_test.start_with_context().await;
+
+    send(&mut ctx, migrate_position_ix(&owner.pubkey()), &owner).await.unwrap();
+
+    let account = ctx.banks_client.get_account(position_pda(&owner.pubkey())).await.unwrap().unwrap();
+    let position: &UserPosition = bytemuck::from_bytes(&account.data[8..]);
+    assert_eq!((position.cost_basis, position.user_shares, position.high_water_mark), (250_000, 240_000, 250_000));
+}
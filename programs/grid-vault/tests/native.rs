@@ -0,0 +1,180 @@
+mod common;
+
+use anchor_lang::{
+    prelude::Pubkey, solana_program::instruction::Instruction, AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::token::spl_token;
+use common::{
+    assert_vault_error, attestation_pda, config_pda, fund, position_pda, program_test, send, send_all, treasury_pda,
+    wsol_pda,
+};
+use grid_vault::{ProtocolConfig, VaultError};
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::{
+    program_pack::Pack,
+    rent::Rent,
+    signature::{keypair_from_seed, Keypair, Signer},
+    system_instruction, system_program, sysvar,
+};
+
+/// Lamports charged per transaction signature
+const SIGNATURE_FEE: u64 = 5_000;
+
+struct Vault {
+    ctx: ProgramTestContext,
+    mint: Pubkey,
+    user: Keypair,
+}
+
+/// Boots a vault over wrapped SOL, or over a fresh SPL mint, with an open position for one user
+async fn start_vault(native: bool) -> Vault {
+    let admin = keypair_from_seed(&[1; 32]).unwrap();
+    let user = keypair_from_seed(&[4; 32]).unwrap();
+    let mut program_test = program_test();
+    fund(&mut program_test, &admin);
+    fund(&mut program_test, &user);
+    let mut ctx = program_test.start_with_context().await;
+
+    let mint = if native {
+        spl_token::native_mint::ID
+    } else {
+        let mint = Keypair::new();
+        let ixs = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &mint.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), None, 9).unwrap(),
+        ];
+        send_all(&mut ctx, &ixs, &[&admin, &mint]).await.unwrap();
+        mint.pubkey()
+    };
+
+    let initialize = Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::InitializeProtocol {
+            authority: admin.pubkey(),
+            protocol_config: config_pda(),
+            treasury_account: treasury_pda(),
+            token_mint: mint,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::InitializeProtocol {
+            admin: admin.pubkey(),
+            trading_bot: keypair_from_seed(&[2; 32]).unwrap().pubkey(),
+        }
+        .data(),
+    };
+    send(&mut ctx, initialize, &admin).await.unwrap();
+
+    let create_position = Instruction {
+        program_id: grid_vault::ID,
+        accounts: grid_vault::accounts::CreateUserPosition {
+            owner: user.pubkey(),
+            user_position: position_pda(&user.pubkey()),
+            protocol_config: config_pda(),
+            attestation: attestation_pda(&user.pubkey()),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: grid_vault::instruction::CreateUserPosition { proof: vec![] }.data(),
+    };
+    send(&mut ctx, create_position, &user).await.unwrap();
+
+    Vault { ctx, mint, user }
+}
+
+impl Vault {
+    async fn deposit_sol(&mut self, amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: grid_vault::accounts::DepositSol {
+                owner: self.user.pubkey(),
+                user_position: position_pda(&self.user.pubkey()),
+                protocol_config: config_pda(),
+                treasury_account: treasury_pda(),
+                wsol_account: wsol_pda(&self.user.pubkey()),
+                native_mint: spl_token::native_mint::ID,
+                attestation: attestation_pda(&self.user.pubkey()),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: grid_vault::instruction::DepositSol { amount, min_shares: 0, proof: vec![] }.data(),
+        };
+        let user = self.user.insecure_clone();
+        send(&mut self.ctx, ix, &user).await
+    }
+
+    async fn withdraw_sol(&mut self, amount: u64) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: grid_vault::ID,
+            accounts: grid_vault::accounts::WithdrawSol {
+                owner: self.user.pubkey(),
+                user_position: position_pda(&self.user.pubkey()),
+                protocol_config: config_pda(),
+                treasury_account: treasury_pda(),
+                wsol_account: wsol_pda(&self.user.pubkey()),
+                native_mint: spl_token::native_mint::ID,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: grid_vault::instruction::WithdrawSol { amount, max_shares: u64::MAX }.data(),
+        };
+        let user = self.user.insecure_clone();
+        send(&mut self.ctx, ix, &user).await
+    }
+
+    async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.ctx.banks_client.get_balance(address).await.unwrap()
+    }
+
+    async fn treasury_balance(&mut self) -> u64 {
+        let account = self.ctx.banks_client.get_account(treasury_pda()).await.unwrap().unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    async fn config(&mut self) -> ProtocolConfig {
+        let account = self.ctx.banks_client.get_account(config_pda()).await.unwrap().unwrap();
+        ProtocolConfig::try_deserialize(&mut &account.data[..]).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn sol_deposits_wrap_and_withdrawals_unwrap() {
+    let mut vault = start_vault(true).await;
+    assert_eq!(vault.mint, spl_token::native_mint::ID);
+    let user = vault.user.pubkey();
+
+    // Lamports leave the owner as wSOL in the treasury; the transient account's rent comes back
+    let before = vault.lamports(user).await;
+    vault.deposit_sol(2_000_000).await.unwrap();
+    assert_eq!(before - vault.lamports(user).await, 2_000_000 + SIGNATURE_FEE);
+    assert_eq!(vault.treasury_balance().await, 2_000_000);
+    assert_eq!(vault.config().await.total_shares, 2_000_000);
+    assert!(vault.ctx.banks_client.get_account(wsol_pda(&user)).await.unwrap().is_none());
+
+    // Withdrawals unwrap back into lamports and close the transient account again
+    let before = vault.lamports(user).await;
+    vault.withdraw_sol(500_000).await.unwrap();
+    assert_eq!(vault.lamports(user).await - before, 500_000 - SIGNATURE_FEE);
+    assert_eq!(vault.treasury_balance().await, 1_500_000);
+    assert_eq!(vault.config().await.total_shares, 1_500_000);
+    assert!(vault.ctx.banks_client.get_account(wsol_pda(&user)).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn sol_instructions_require_a_native_vault() {
+    let mut vault = start_vault(false).await;
+    assert_ne!(vault.mint, spl_token::native_mint::ID);
+
+    assert_vault_error(vault.deposit_sol(1_000_000).await, VaultError::NotNativeVault);
+    assert_vault_error(vault.withdraw_sol(1_000_000).await, VaultError::NotNativeVault);
+}